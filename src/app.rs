@@ -1,528 +1,7538 @@
-use crate::inside_curve::check_inside_curve;
-use chrono::{Duration, Local, NaiveDateTime};
+use crate::audit::{current_user, AuditLog};
+use crate::cleaned::Replacement;
+use crate::columns::{split_header, ParseReport, RawTable, Table};
+use crate::dataset::{
+    coverage_table, covered_rows, exclude_rows, first_marker, format_markers, matrix_cells,
+    parse_markers, write_cleaned, ChannelData, Coverage, DataPoint, MatrixCell, Stamp, TimeSeries,
+    POINT_BYTES,
+};
+use crate::direction::{
+    angular_difference, direction_axis, format_sectors, in_sector, is_direction, parse_sectors,
+    rose, rose_index, rose_sector, seam_offsets,
+};
+use crate::duplicates::DuplicatePolicy;
+use crate::exclusions::{
+    category_label, covered, header_columns, merge_period_groups, merge_periods, read_exclusions,
+    validate_rows, Category, ExclusionRow, ExportColumn, ExportFormat, ExportLayout, ReviewStatus,
+};
+use crate::expiration::{self, License};
+use crate::expression::Expression;
+use crate::inside_curve::{
+    check_inside_curve_with_offsets, nearest_index, nearest_point, scaled_distance,
+};
+use crate::loader::{Content, LoadJob, Parsed};
+use crate::lod::{decimate_line, decimate_points, View};
+use crate::memory::{available_memory, estimate_dataset_bytes, format_bytes};
+use crate::naming::{sensor_height, sensor_kind, Statistic};
+use crate::parquet_file::{is_parquet, read_parquet, write_parquet};
+use crate::plot_image::{write_png, write_svg, ImageFormat, Layer, Mark, PlotDrawing};
+use crate::project::{Project, ProjectChannel, ProjectFile};
+use crate::report::{Recovery, Report};
+use crate::rules::{flagged_runs, icing_flags, Detection, GroupStats, Rule, RuleJob};
+use crate::sensors::SensorConfig;
+use crate::settings::{PlausibleRange, Settings};
+use crate::shortcuts::{Action, Shortcut};
+use crate::stats::{
+    group_means, histogram, out_of_band, rolling_mean_std, shear_exponent, summary, Summary,
+};
+use crate::theme::{color, PlotTheme, Rgb, UiTheme};
+use crate::timeline::{
+    cadence, detect_format, format_time, gaps, line_segments, nearest_time, parse_date_range,
+    parse_time, write_gaps, Gap, TIMESTAMP_FORMAT,
+};
+use crate::windfarmer;
+use crate::workbook::{is_workbook, read_sheet, sheet_names};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
 use eframe::egui;
-use eframe::egui::ecolor::Rgba;
-use eframe::egui::plot::{Line, Plot, Points};
+use eframe::egui::plot::{Bar, BarChart, Legend, Line, Plot, PlotBounds, Points, VLine};
 use eframe::egui::{Button, ComboBox, DragValue, TextEdit};
 use itertools::izip;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::{self, File};
-use std::io::{BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration as StdDuration, Instant};
 
-enum DataPoint {
-    Valid(f64),
-    NaN,
-    Excluded(f64, String),
+// Channels the rules are run on
+#[derive(Clone, Copy, PartialEq)]
+enum RuleScope {
+    Plotted,
+    All,
+    SensorType,
 }
 
-struct TimeSeries {
-    name: String,
-    data: Vec<DataPoint>,
+#[derive(Clone, Copy, PartialEq)]
+enum PlotMode {
+    Scatter,
+    TimeSeries,
 }
 
-fn unwrap_name(name: &str) -> Result<(String, String), String> {
-    let names: Vec<&str> = name.split('~').collect();
-    match names.len() {
-        2 | 3 => Ok((names[0].to_string(), names[1].to_string())),
-        _ => Err("Unsupported number of names".to_string()),
+// An excluded point, as (series, row, stamp)
+type PointStamp = (usize, usize, Stamp);
+
+// Exclusion state at one moment, with every excluded point
+struct Snapshot {
+    label: String,
+    taken_at: NaiveDateTime,
+    exclusions: Vec<PointStamp>,
+}
+
+// A data file loaded alongside the one shown, kept as it was left
+struct OpenFile {
+    file_path: String,
+    index_name: String,
+    index: Vec<String>,
+    times: Vec<f64>,
+    time_step: f64,
+    time_format: Option<&'static str>,
+    timeseries: Vec<TimeSeries>,
+    raw: Option<RawTable>,
+    load_report: Option<LoadReport>,
+    partial: bool,
+    xaxis: usize,
+    yaxis: usize,
+    snapshots: Vec<Snapshot>,
+    undo_stack: Vec<ExclusionAction>,
+    redo_stack: Vec<ExclusionAction>,
+}
+
+// Selection, revision and plotted x axis, y axis and mode
+type BrushKey = (Selection, u64, (usize, usize, PlotMode));
+
+// Selection drawn on the linked plot, revision, main x axis, linked y axis,
+// main plot mode and whether the linked plot is over time
+type WindowBrushKey = (Vec<[f64; 2]>, u64, (usize, usize, PlotMode), bool);
+
+// Channels and heights, revision, direction channel, sectors and period a
+// shear profile was worked out for
+type ShearKey = (
+    Vec<(usize, f64)>,
+    u64,
+    Option<usize>,
+    usize,
+    Option<(f64, f64)>,
+);
+
+// Named line of mean speed against height
+type ShearLine = (String, Vec<[f64; 2]>);
+
+// Valid points, excluded ones by reason color and brushed ones of a plot
+type LinkedPoints = (Rc<[[f64; 2]]>, Vec<(Rgb, Rc<[[f64; 2]]>)>, Vec<[f64; 2]>);
+
+// A channel, the summary of its valid values and its missing, excluded and
+// total records
+type ChannelStats = (usize, Summary, usize, usize, usize);
+
+// Channels and revision the statistics window was worked out for
+type StatsKey = (Vec<usize>, u64);
+
+// Periods of a channel detected by the rules and their points
+type DetectedPoints = (Vec<Range<usize>>, Rc<[[f64; 2]]>);
+
+// Plotted points along with their row
+type RowPoints = Rc<[(usize, [f64; 2])]>;
+
+// Direction channel, anemometer weighting it if any, sectors and revision a
+// wind rose was worked out for
+type RoseKey = (usize, Option<usize>, usize, u64);
+
+// Revision and cadence setting the gaps of the index were found for
+type GapsKey = (u64, f64);
+
+// Detected cadence, cadence used and gaps of the index
+type IndexGaps = (Option<f64>, Option<f64>, Rc<[Gap]>);
+
+// Revision and grouping, monthly or not, the coverage was counted for
+type CoverageKey = (u64, bool);
+
+// Revision and channels the scatter matrix was taken for
+type MatrixKey = (u64, Vec<usize>);
+
+// Exclusion curve points and whether the curve is closed
+type Selection = (Vec<[f64; 2]>, bool);
+
+// An excluded timestamp of a channel, and its period widened by the time
+// buffer
+type ExclusionPoint = (NaiveDateTime, ExclusionRow);
+
+// Mast, sensor, reason and timestamp of an excluded point. Review states are
+// kept by point, so they outlive the merging of the periods into rows.
+type PointKey = (String, String, String, NaiveDateTime);
+
+// A merged exclusion period, its review state and the points it's made of
+type ReviewedRow = (ExclusionRow, ReviewStatus, Vec<PointKey>);
+
+fn point_key((time, period): &ExclusionPoint) -> PointKey {
+    (
+        period.mast.clone(),
+        period.sensor.clone(),
+        period.reason.clone(),
+        *time,
+    )
+}
+
+// An undoable operation: the points it excluded and restored, and the
+// selection before and after it
+struct ExclusionAction {
+    label: String,
+    exclusions: Vec<PointStamp>,
+    restorations: Vec<PointStamp>,
+    selection: [Selection; 2],
+}
+
+// What the cached plot points were extracted from: the plotted channels,
+// which rows are shown and the revision of the data
+#[derive(Clone, PartialEq)]
+struct PointsKey {
+    xaxis: usize,
+    yaxis: usize,
+    extra_yaxes: Vec<usize>,
+    plot_mode: PlotMode,
+    concurrent_only: bool,
+    color_by: Option<usize>,
+    revision: u64,
+}
+
+// Plot points of each channel, reused between frames until the key changes.
+// Excluded points are kept by reason, in the order of the exclusion names.
+// They are shared, not copied, with each frame drawing them.
+#[derive(Default)]
+struct PointsCache {
+    key: Option<PointsKey>,
+    valid: HashMap<usize, Rc<[[f64; 2]]>>,
+    excluded: HashMap<usize, Vec<Rc<[[f64; 2]]>>>,
+    segments: HashMap<usize, Rc<[Vec<[f64; 2]>]>>,
+    // Valid points of the y axis by bin of the color channel value
+    colored: Option<Vec<Rc<[[f64; 2]]>>>,
+    // Lowest and highest valid value of each channel
+    value_ranges: HashMap<usize, Option<(f64, f64)>>,
+    // Excluded points of each channel with their row, to show on hover
+    excluded_rows: HashMap<usize, RowPoints>,
+    // Points of each channel in the periods detected by the rules, which
+    // change apart from the data
+    detected: HashMap<usize, DetectedPoints>,
+}
+
+// Which part of a data file to load: every n-th row and, when given, only
+// the selected columns after the index
+struct LoadOptions {
+    every: usize,
+    columns: Option<Vec<bool>>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            every: 1,
+            columns: None,
+        }
+    }
+}
+
+// A file being read on a background thread
+struct Loading {
+    job: LoadJob,
+    path: String,
+    source: String,
+    options: LoadOptions,
+    // Applied to the timestamps repeated in the file
+    duplicates: DuplicatePolicy,
+}
+
+// Malformed cells and repeated timestamps found in a file, as it was loaded
+// and as its columns were parsed, with its header names
+struct LoadReport {
+    malformed: Option<Box<ParseReport>>,
+    duplicates: Vec<(String, Vec<usize>)>,
+    policy: DuplicatePolicy,
+    headers: Vec<String>,
+    // Whether the malformed cells were looked at, as nothing is exported
+    // until they are
+    acknowledged: bool,
+}
+
+impl LoadReport {
+    fn malformed(&self) -> Option<&ParseReport> {
+        self.malformed.as_deref().filter(|report| report.total > 0)
+    }
+
+    fn has_findings(&self) -> bool {
+        self.malformed().is_some() || !self.duplicates.is_empty()
+    }
+
+    // Adds the malformed cells of a column parsed after loading. Cells already
+    // found when the file was checked at load are not counted twice. Returns
+    // how many were new.
+    fn record(&mut self, column: usize, report: ParseReport) -> usize {
+        let malformed = self.malformed.get_or_insert_with(Default::default);
+        if report.total == 0 || malformed.checked.contains(&column) {
+            malformed.checked.insert(column);
+            return 0;
+        }
+        let found = report.total;
+        malformed.merge(report);
+        self.acknowledged = false;
+        found
+    }
+}
+
+// A file estimated to need more memory than available, waiting for the user
+// to sub-sample it or pick fewer columns
+struct PendingLoad {
+    path: String,
+    headers: Vec<String>,
+    // Columns of each mast, by the naming convention
+    masts: BTreeMap<String, Vec<usize>>,
+    rows: usize,
+    limit: usize,
+    every: usize,
+    columns: Vec<bool>,
+}
+
+impl PendingLoad {
+    fn estimate(&self) -> usize {
+        let columns = self.columns.iter().filter(|&&c| c).count();
+        estimate_dataset_bytes(self.rows.div_ceil(self.every.max(1)), columns, POINT_BYTES)
+    }
+}
+
+// A workbook with several sheets, waiting for the user to pick one
+struct PendingSheet {
+    path: String,
+    sheets: Vec<String>,
+    selected: usize,
+}
+
+// Memory limit when the platform doesn't report the available memory
+const DEFAULT_MEMORY_LIMIT: usize = 4 << 30;
+const MAX_SNAPSHOTS: usize = 20;
+const MAX_UNDO: usize = 200;
+// Datasets with more points than this are decimated for drawing, to one point
+// per cell of a LOD_CELLS×LOD_CELLS grid over the view
+const LOD_MIN_POINTS: usize = 20_000;
+const LOD_CELLS: usize = 1500;
+// Steps of the gradient points are colored with
+const COLOR_BINS: usize = 16;
+const PRESENTATION_FONT_SCALE: f32 = 1.6;
+const PRESENTATION_POINT_SCALE: f32 = 2.0;
+// Most bars the histogram draws before asking for a wider bin
+const MAX_HISTOGRAM_BINS: usize = 2000;
+// Longer than a click, so releasing a long press never adds a vertex
+const LONG_PRESS_SECONDS: f64 = 0.8;
+// Screen distance from the first vertex within which a click closes the selection
+const CLOSE_DISTANCE_PIXELS: f64 = 10.0;
+// Reach of Ctrl+click around a point to exclude it
+const PICK_DISTANCE_PIXELS: f64 = 8.0;
+// Screen distance from a point within which a new vertex snaps to it
+const SNAP_DISTANCE_PIXELS: f64 = 12.0;
+const LINKED_PLOTS: &str = "linked_plots";
+// Channels of the scatter matrix, the points drawn in each of its plots and
+// the size of the plots
+const MAX_MATRIX_CHANNELS: usize = 8;
+const MAX_MATRIX_POINTS: usize = 2000;
+const MATRIX_CELL_SIZE: f32 = 120.0;
+
+// Channels of a vertical profile, as (series, height)
+type ProfileChannels = Vec<(usize, f64)>;
+
+fn file_label(path: &str) -> String {
+    Path::new(path).file_name().map_or_else(
+        || path.to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+// A heading followed by the first few items, one per line
+fn listed(heading: String, items: &[String]) -> String {
+    let shown = 5;
+    let mut msg = heading;
+    for item in items.iter().take(shown) {
+        msg += &format!("\n  {}", item);
+    }
+    if items.len() > shown {
+        msg += &format!("\n  ... and {} more", items.len() - shown);
+    }
+    msg
+}
+
+// Recovery of a channel. One not parsed yet is parsed only to be counted,
+// so that a report doesn't keep every channel of a large file in memory.
+fn channel_recovery(
+    file: &str,
+    ts: &TimeSeries,
+    raw: Option<&RawTable>,
+    missing: &[f64],
+) -> Recovery {
+    match (ts.column, raw) {
+        (Some(column), Some(raw)) => {
+            let data = ChannelData::read(raw.column(column).0, missing);
+            Recovery::of(file, &ts.name, &data)
+        }
+        _ => Recovery::of(file, &ts.name, &ts.data),
+    }
+}
+
+// The values and exclusions of each channel, as saved in a project
+fn project_channels(timeseries: &[TimeSeries]) -> Vec<ProjectChannel> {
+    timeseries
+        .iter()
+        .map(|ts| {
+            let mut excluded: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+            let mut analysts: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+            let mut categories: BTreeMap<Category, Vec<usize>> = BTreeMap::new();
+            let values = ts
+                .data
+                .iter()
+                .enumerate()
+                .map(|(row, val)| match val {
+                    DataPoint::Valid(v) => Some(v),
+                    DataPoint::Excluded(v, reason) => {
+                        excluded.entry(reason.to_owned()).or_default().push(row);
+                        let stamp = ts.data.stamp(row).cloned().unwrap_or_default();
+                        if !stamp.analyst.is_empty() {
+                            analysts.entry(stamp.analyst).or_default().push(row);
+                        }
+                        if let Some(category) = stamp.category {
+                            categories.entry(category).or_default().push(row);
+                        }
+                        Some(v)
+                    }
+                    DataPoint::NaN => None,
+                })
+                .collect();
+            ProjectChannel {
+                name: ts.name.clone(),
+                sources: ts.sources.clone(),
+                values,
+                excluded,
+                analysts,
+                categories,
+            }
+        })
+        .collect()
+}
+
+// Timestamp format, timestamps as plot coordinates, and the step beyond
+// which lines are broken
+fn read_times(index: &[String]) -> (Option<&'static str>, Vec<f64>, f64) {
+    let time_format = detect_format(index);
+    let format = time_format.unwrap_or(TIMESTAMP_FORMAT);
+    let times: Vec<f64> = index.iter().map(|t| parse_time(t, format)).collect();
+    let time_step = cadence(&times).map_or(f64::INFINITY, |step| 1.5 * step);
+    (time_format, times, time_step)
+}
+
+// Enlarges every text style for presenting, or restores the default sizes
+fn set_presentation_fonts(ctx: &egui::Context, presentation: bool) {
+    let scale = if presentation {
+        PRESENTATION_FONT_SCALE
+    } else {
+        1.0
+    };
+    let mut style = (*ctx.style()).clone();
+    style.text_styles = egui::Style::default().text_styles;
+    for font in style.text_styles.values_mut() {
+        font.size *= scale;
     }
+    ctx.set_style(style);
 }
 
 pub struct ManualDataCleanerApp {
     msg: String,
     xaxis: usize,
     yaxis: usize,
+    extra_yaxes: Vec<usize>,
+    exclude_extra_y: bool,
     excludex: bool,
     excludey: bool,
     file_path: String,
     file_loaded: bool,
+    // The other data files of the session, whose exclusions are exported too
+    other_files: Vec<OpenFile>,
+    // Whether the file being opened is added to the session, keeping the
+    // shown one, rather than replacing it
+    adding_file: bool,
     timeseries: Vec<TimeSeries>,
-    nan: f64,
+    // Values read as missing, besides blank and NaN cells, and their list as edited
+    missing: Vec<f64>,
+    missing_text: String,
+    // Rows with malformed cells are left out instead of read as missing
+    skip_malformed: bool,
+    duplicate_policy: DuplicatePolicy,
+    // Text files open with the column picker, whatever their size
+    choose_columns: bool,
+    load_report: Option<LoadReport>,
+    show_load_report: bool,
+    // The file was loaded sub-sampled or with some columns left out, so its
+    // cleaned data can't be exported
+    partial_load: bool,
     index: Vec<String>,
+    times: Vec<f64>,
+    time_step: f64,
+    // Format of the index timestamps, when recognised
+    time_format: Option<&'static str>,
+    plot_mode: PlotMode,
+    compare_periods: bool,
+    periods: [[String; 2]; 2],
     reason: String,
+    // Stamped on the exclusions made from now on, with the reason
+    category: Option<Category>,
     exclusion_names: Vec<String>,
     time_buffer: u64,
     exclusion_curve: Vec<[f64; 2]>,
     exclusion_curve_is_closed: bool,
     show_excluded: bool,
+    snapshots: Vec<Snapshot>,
+    snapshot_every: u32,
+    operations_since_snapshot: u32,
+    snap_to_data: bool,
+    compass_labels: bool,
+    // Expression of the next computed channel, as "TI = M1~WSSD80 / M1~WS80"
+    expression: String,
+    difference_a: usize,
+    difference_b: usize,
+    // Anemometers compared as a ratio, or a difference when false
+    comparison: (usize, usize),
+    comparison_ratio: bool,
+    // Sources of derived y axes that their exclusions also apply to
+    exclude_sources: Vec<usize>,
+    theme: PlotTheme,
+    reason_colors: BTreeMap<String, Rgb>,
+    read_only: bool,
+    read_only_locked: bool,
+    // An expired license replaces the app with a message screen
+    license: License,
+    license_key: String,
+    show_license: bool,
+    review_states: HashMap<PointKey, ReviewStatus>,
+    show_review: bool,
+    // Plot bounds to move the view to on the next frame, as [min, max]
+    jump_to: Option<[[f64; 2]; 2]>,
+    show_icing: bool,
+    // Temperature and anemometer standard deviation channels of the icing check
+    icing_channels: (Option<usize>, Option<usize>),
+    icing_max_temperature: f64,
+    icing_max_std: f64,
+    show_sectors: bool,
+    // Direction channel and anemometer of the sector exclusion
+    sector_channels: (Option<usize>, Option<usize>),
+    sectors: String,
+    sector_reason: String,
+    // Sectors of the wind rose, and whether it's weighted by the anemometer
+    rose_sectors: usize,
+    rose_weighted: bool,
+    rose_cache: Option<(RoseKey, Rc<[f64]>)>,
+    show_naming: bool,
+    show_appearance: bool,
+    show_shortcuts: bool,
+    show_shortcut_settings: bool,
+    // Action whose shortcut is being remapped, waiting for a key press
+    remapping: Option<Action>,
+    // Folder of the last data file opened, where the file dialogs start
+    last_dir: Option<PathBuf>,
+    // Names of the x and y channels last plotted, by data file
+    saved_axes: HashMap<String, [String; 2]>,
+    // Whether the window visuals were last set dark, None before the first frame
+    dark_visuals: Option<bool>,
+    export_approved_only: bool,
+    // The export merges into the file picked instead of replacing it
+    export_append: bool,
+    audit: AuditLog,
+    analyst: String,
+    export_index_precision: bool,
+    export_layout: ExportLayout,
+    export_comment: String,
+    show_export_options: bool,
+    export_format: ExportFormat,
+    presentation: bool,
+    touch_drawing: bool,
+    // Plain clicks add vertices, as with E held down
+    draw_mode: bool,
+    show_plot_window: bool,
+    window_yaxis: usize,
+    show_left_panel: bool,
+    // The main plot is kept square, or else fills the whole panel
+    square_plot: bool,
+    // Legend and axis labels over the main plot
+    show_legend: bool,
+    // The linked plot shows its channel over time, whatever the main plot mode
+    window_over_time: bool,
+    // Rows inside the selection drawn on the main plot, with the selection,
+    // revision and plotted axes they were found for
+    brush_cache: Option<(BrushKey, Vec<bool>)>,
+    // Selection drawn on the linked plot, highlighting its rows on the main
+    // one, and the rows inside it
+    window_curve: Vec<[f64; 2]>,
+    window_brush_cache: Option<(WindowBrushKey, Vec<bool>)>,
+    show_hud: bool,
+    plotted_points: usize,
+    last_operation: Option<(String, StdDuration)>,
+    pending_load: Option<PendingLoad>,
+    loading: Option<Loading>,
+    pending_sheet: Option<PendingSheet>,
+    // File dropped on the window while there were unsaved exclusions, loaded
+    // once confirmed
+    pending_drop: Option<String>,
+    // Exclusions changed since the session was last saved or exported
+    unsaved_changes: bool,
+    raw: Option<RawTable>,
+    rules: Vec<(bool, Rule)>,
+    rule_job: Option<RuleJob>,
+    rule_scope: RuleScope,
+    rule_sensor_type: String,
+    range_reason: String,
+    detections: Vec<Detection>,
+    show_rolling: bool,
+    rolling_window: usize,
+    rolling_k: f64,
+    spike_delta: f64,
+    exclude_sensor_group: bool,
+    concurrent_only: bool,
+    index_name: String,
+    cleaned_replacement: Replacement,
+    sensor_config: SensorConfig,
+    show_profile: bool,
+    profile_type: String,
+    hovered_row: Option<usize>,
+    pinned_row: Option<usize>,
+    show_shear: bool,
+    shear_mast: String,
+    // Direction channel the shear profile is split into sectors by, and how many
+    shear_direction: Option<usize>,
+    shear_sectors: usize,
+    // Only the timestamps within the time axis of the main plot
+    shear_shown_period: bool,
+    shear_cache: Option<(ShearKey, Vec<ShearLine>)>,
+    show_histogram: bool,
+    histogram_bin: f64,
+    // Excluded values are drawn on the histogram too, apart from the main plot
+    histogram_excluded: bool,
+    show_stats: bool,
+    stats_cache: Option<(StatsKey, Rc<[ChannelStats]>)>,
+    show_coverage: bool,
+    show_gaps: bool,
+    // Expected spacing of the index in minutes, detected when 0
+    gap_cadence: f64,
+    gaps_cache: Option<(GapsKey, IndexGaps)>,
+    // Coverage per day, or per month when set
+    coverage_monthly: bool,
+    // Coverage of each measured channel, along with the revision and grouping
+    // it was counted for
+    coverage_cache: Option<(CoverageKey, Rc<[Coverage]>)>,
+    show_matrix: bool,
+    matrix_channels: Vec<usize>,
+    // Points of every pair of the scatter matrix
+    matrix_cache: Option<(MatrixKey, Rc<[MatrixCell]>)>,
+    last_reason: String,
+    settings: Settings,
+    auto_export_path: Option<PathBuf>,
+    auto_export_minutes: u32,
+    auto_export_session: bool,
+    last_auto_export: Instant,
+    undo_stack: Vec<ExclusionAction>,
+    redo_stack: Vec<ExclusionAction>,
+    // Points excluded since the last operation was recorded
+    pending_exclusions: Vec<PointStamp>,
+    pending_restorations: Vec<PointStamp>,
+    // Start of a time range being selected in time series mode
+    time_range_start: Option<f64>,
+    // Further channels every exclusion applies to
+    exclude_targets: Vec<usize>,
+    exclude_same_height: bool,
+    // Channel whose value colors the points of the y axis
+    color_by: Option<usize>,
+    level_of_detail: bool,
+    // Plot view of the last frame, along with the x axis, y axis and mode it showed
+    view: Option<(View, (usize, usize, PlotMode))>,
+    // Where the main plot was last drawn, in points
+    plot_rect: Option<egui::Rect>,
+    // Plot image to cut from the screenshot of the frame, with the corners of
+    // the plot in pixels
+    pending_plot_image: Option<(PathBuf, [usize; 2], [usize; 2])>,
+    // Plots added to the report, as caption and SVG
+    report_plots: Vec<(String, String)>,
+    // Counts the changes to the data, so cached points know when to refresh
+    revision: u64,
+    points_cache: PointsCache,
 }
 
+const REASON_COLORS_KEY: &str = "reason_colors";
+const ANALYST_KEY: &str = "analyst";
+const SETTINGS_KEY: &str = "settings";
+const THEME_KEY: &str = "theme";
+const NAN_KEY: &str = "nan";
+const MISSING_KEY: &str = "missing_values";
+const TIME_BUFFER_KEY: &str = "time_buffer";
+const CHOOSE_COLUMNS_KEY: &str = "choose_columns";
+const EXPORT_LAYOUT_KEY: &str = "export_layout";
+const LAST_DIR_KEY: &str = "last_dir";
+const AXES_KEY: &str = "axes";
+
 impl Default for ManualDataCleanerApp {
     fn default() -> Self {
         Self {
             msg: "".to_owned(),
             xaxis: 0,
             yaxis: 0,
+            extra_yaxes: Vec::new(),
+            exclude_extra_y: false,
             excludex: true,
             excludey: true,
             file_path: "".to_owned(),
             file_loaded: false,
+            other_files: Vec::new(),
+            adding_file: false,
             timeseries: Vec::new(),
-            nan: 99999.0,
+            missing: vec![99999.0],
+            missing_text: "99999".into(),
+            skip_malformed: false,
+            duplicate_policy: DuplicatePolicy::KeepFirst,
+            choose_columns: false,
+            load_report: None,
+            show_load_report: false,
+            partial_load: false,
             index: Vec::new(),
+            times: Vec::new(),
+            time_step: f64::INFINITY,
+            time_format: None,
+            plot_mode: PlotMode::Scatter,
+            compare_periods: false,
+            periods: Default::default(),
             reason: "".to_owned(),
+            category: None,
             exclusion_names: Vec::new(),
             time_buffer: 10,
             exclusion_curve: Vec::new(),
             exclusion_curve_is_closed: false,
             show_excluded: false,
+            snapshots: Vec::new(),
+            snapshot_every: 10,
+            operations_since_snapshot: 0,
+            snap_to_data: false,
+            compass_labels: false,
+            expression: String::new(),
+            difference_a: 0,
+            difference_b: 0,
+            comparison: (0, 0),
+            comparison_ratio: true,
+            exclude_sources: Vec::new(),
+            theme: PlotTheme::default(),
+            reason_colors: BTreeMap::new(),
+            read_only: false,
+            read_only_locked: false,
+            license: License::Valid(NaiveDate::MAX),
+            license_key: String::new(),
+            show_license: false,
+            review_states: HashMap::new(),
+            show_review: false,
+            jump_to: None,
+            show_icing: false,
+            icing_channels: (None, None),
+            icing_max_temperature: 2.0,
+            icing_max_std: 0.05,
+            show_sectors: false,
+            sector_channels: (None, None),
+            sectors: String::new(),
+            sector_reason: "tower shadow".to_owned(),
+            rose_sectors: 16,
+            rose_weighted: false,
+            rose_cache: None,
+            show_naming: false,
+            show_appearance: false,
+            show_shortcuts: false,
+            show_shortcut_settings: false,
+            remapping: None,
+            last_dir: None,
+            saved_axes: HashMap::new(),
+            dark_visuals: None,
+            export_approved_only: false,
+            export_append: false,
+            audit: AuditLog::default(),
+            analyst: current_user(),
+            export_index_precision: false,
+            export_layout: ExportLayout::default(),
+            export_comment: String::new(),
+            show_export_options: false,
+            export_format: ExportFormat::Tsv,
+            presentation: false,
+            touch_drawing: false,
+            draw_mode: false,
+            show_plot_window: false,
+            show_left_panel: true,
+            square_plot: true,
+            show_legend: true,
+            window_over_time: false,
+            brush_cache: None,
+            window_curve: Vec::new(),
+            window_brush_cache: None,
+            window_yaxis: 0,
+            show_hud: false,
+            plotted_points: 0,
+            last_operation: None,
+            pending_load: None,
+            loading: None,
+            pending_sheet: None,
+            pending_drop: None,
+            unsaved_changes: false,
+            raw: None,
+            rules: vec![
+                (
+                    true,
+                    Rule::Range {
+                        min: 0.0,
+                        max: 100.0,
+                    },
+                ),
+                (true, Rule::Spike { max_step: 10.0 }),
+                (
+                    true,
+                    Rule::FlatLine {
+                        min_length: 6,
+                        tolerance: 0.0,
+                    },
+                ),
+                (true, Rule::MinMaxConsistency),
+            ],
+            rule_job: None,
+            rule_scope: RuleScope::Plotted,
+            rule_sensor_type: String::new(),
+            range_reason: "out of range".to_owned(),
+            detections: Vec::new(),
+            show_rolling: false,
+            rolling_window: 144,
+            rolling_k: 3.0,
+            spike_delta: 5.0,
+            exclude_sensor_group: false,
+            concurrent_only: false,
+            index_name: String::new(),
+            cleaned_replacement: Replacement::Sentinel,
+            sensor_config: SensorConfig::default(),
+            show_profile: false,
+            profile_type: "WS".to_owned(),
+            hovered_row: None,
+            pinned_row: None,
+            show_shear: false,
+            shear_mast: String::new(),
+            shear_direction: None,
+            shear_sectors: 1,
+            shear_shown_period: false,
+            shear_cache: None,
+            show_histogram: false,
+            histogram_bin: 1.0,
+            histogram_excluded: false,
+            show_stats: false,
+            stats_cache: None,
+            show_coverage: false,
+            show_gaps: false,
+            gap_cadence: 0.0,
+            gaps_cache: None,
+            coverage_monthly: true,
+            coverage_cache: None,
+            show_matrix: false,
+            matrix_channels: Vec::new(),
+            matrix_cache: None,
+            last_reason: String::new(),
+            settings: Settings::default(),
+            auto_export_path: None,
+            auto_export_minutes: 5,
+            auto_export_session: false,
+            last_auto_export: Instant::now(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending_exclusions: Vec::new(),
+            pending_restorations: Vec::new(),
+            time_range_start: None,
+            exclude_targets: Vec::new(),
+            exclude_same_height: false,
+            color_by: None,
+            level_of_detail: true,
+            view: None,
+            plot_rect: None,
+            pending_plot_image: None,
+            report_plots: Vec::new(),
+            revision: 0,
+            points_cache: PointsCache::default(),
         }
     }
 }
 
 impl ManualDataCleanerApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Default::default()
+    // With `review` set the session is locked in read-only mode
+    pub fn new(cc: &eframe::CreationContext<'_>, review: bool, license: License) -> Self {
+        let mut app = Self {
+            read_only: review,
+            read_only_locked: review,
+            license,
+            ..Default::default()
+        };
+        if let Some(storage) = cc.storage {
+            app.reason_colors = eframe::get_value(storage, REASON_COLORS_KEY).unwrap_or_default();
+            if let Some(analyst) = eframe::get_value(storage, ANALYST_KEY) {
+                app.analyst = analyst;
+            }
+            if let Some(settings) = eframe::get_value(storage, SETTINGS_KEY) {
+                app.settings = settings;
+            }
+            if let Some(theme) = eframe::get_value(storage, THEME_KEY) {
+                app.theme = theme;
+            }
+            // A single missing value was kept before the list
+            if let Some(missing) = eframe::get_value(storage, MISSING_KEY)
+                .or_else(|| eframe::get_value(storage, NAN_KEY).map(|nan: f64| vec![nan]))
+            {
+                app.missing = missing;
+                app.missing_text = format_markers(&app.missing);
+            }
+            if let Some(time_buffer) = eframe::get_value(storage, TIME_BUFFER_KEY) {
+                app.time_buffer = time_buffer;
+            }
+            app.choose_columns = eframe::get_value(storage, CHOOSE_COLUMNS_KEY).unwrap_or(false);
+            app.export_layout = eframe::get_value(storage, EXPORT_LAYOUT_KEY)
+                .map_or_else(ExportLayout::default, ExportLayout::complete);
+            app.last_dir = eframe::get_value(storage, LAST_DIR_KEY);
+            app.saved_axes = eframe::get_value(storage, AXES_KEY).unwrap_or_default();
+        }
+        app
     }
 
-    fn parse_data_file(&mut self) -> Result<(), String> {
-        // Clear existing data
-        self.index.clear();
-        self.timeseries.clear();
+    // Runs an operation, keeping its duration for the performance HUD
+    fn timed<T>(&mut self, label: &str, operation: impl FnOnce(&mut Self) -> T) -> T {
+        let start = Instant::now();
+        let result = operation(self);
+        self.last_operation = Some((label.to_owned(), start.elapsed()));
+        result
+    }
+
+    // Approximate heap size of the loaded dataset
+    fn dataset_bytes(&self) -> usize {
+        let index: usize = self
+            .index
+            .iter()
+            .map(|t| std::mem::size_of::<String>() + t.capacity())
+            .sum();
+        let series: usize = self
+            .timeseries
+            .iter()
+            .map(|ts| ts.name.capacity() + ts.data.heap_bytes())
+            .sum();
+
+        index + series + self.times.capacity() * std::mem::size_of::<f64>()
+    }
+
+    fn show_hud(&self, ctx: &egui::Context) {
+        let frame_time = ctx.input(|i| i.unstable_dt);
+        egui::Area::new("performance_hud")
+            .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!("Frame time: {:.1} ms", frame_time * 1000.0));
+                    ui.label(format!(
+                        "Plotted points: {}{}",
+                        self.plotted_points,
+                        if self.decimation_view().is_some() {
+                            " (decimated)"
+                        } else {
+                            ""
+                        }
+                    ));
+                    ui.label(format!(
+                        "Dataset memory: {}",
+                        format_bytes(self.dataset_bytes())
+                    ));
+                    if let Some((label, duration)) = &self.last_operation {
+                        ui.label(format!(
+                            "Last {}: {:.1} ms",
+                            label,
+                            duration.as_secs_f64() * 1000.0
+                        ));
+                    }
+                });
+            });
+    }
 
-        // Read file content
-        let content =
-            fs::read_to_string(&self.file_path).map_err(|e| format!("File read error: {}", e))?;
+    // View to decimate the plotted data over, if it still shows the same channels
+    fn decimation_view(&self) -> Option<View> {
+        let (view, shown) = self.view?;
+        let points = self.index.len() * (1 + self.plotted_extra_yaxes().len());
+        (self.level_of_detail
+            && points > LOD_MIN_POINTS
+            && shown == (self.xaxis, self.yaxis, self.plot_mode))
+            .then_some(view)
+    }
+
+    // Points to draw: all of them for small datasets, otherwise one per grid
+    // cell of the view. Selections always work on the full data.
+    fn decimated(&self, points: &[[f64; 2]]) -> Vec<[f64; 2]> {
+        match self.decimation_view() {
+            Some(view) => decimate_points(points, &view, LOD_CELLS),
+            None => points.to_vec(),
+        }
+    }
 
-        let mut lines = content.lines();
+    fn decimated_lines(&self, lines: &[Vec<[f64; 2]>]) -> Vec<Vec<[f64; 2]>> {
+        match self.decimation_view() {
+            Some(view) => lines
+                .iter()
+                .map(|line| decimate_line(line, &view, LOD_CELLS))
+                .filter(|line| !line.is_empty())
+                .collect(),
+            None => lines.to_vec(),
+        }
+    }
+
+    fn reason_color(&self, reason: &str) -> Rgb {
+        self.reason_colors
+            .get(reason)
+            .copied()
+            .unwrap_or(self.theme.excluded)
+    }
 
-        // Parse headers
+    // Column names and number of data rows, without loading any values
+    fn inspect_data_file(path: &str) -> Result<(Vec<String>, usize), String> {
+        let file = File::open(path).map_err(|e| format!("File read error: {}", e))?;
+        let mut lines = BufReader::new(file).lines();
         let headers = lines
             .next()
             .ok_or("Empty file")?
-            .split('\t')
-            .collect::<Vec<&str>>();
+            .map_err(|e| format!("File read error: {}", e))?;
+        let headers = split_header(&headers).into_iter().skip(1).collect();
 
-        // Handle first column as index
-        self.index = match headers.first() {
-            Some(&_name) => Vec::new(),
-            None => return Err("No headers found".into()),
-        };
+        Ok((headers, lines.count()))
+    }
 
-        // Create TimeSeries for remaining columns
-        self.timeseries = headers
-            .iter()
-            .skip(1)
-            .map(|&h| TimeSeries {
-                name: h.to_string(),
-                data: Vec::new(),
-            })
-            .collect();
+    fn file_dialog(&self) -> rfd::FileDialog {
+        match &self.last_dir {
+            Some(dir) => rfd::FileDialog::new().set_directory(dir),
+            None => rfd::FileDialog::new(),
+        }
+    }
 
-        // Parse data rows
-        for (line_num, line) in lines.enumerate() {
-            let values: Vec<&str> = line.split('\t').collect();
+    fn remember_axes(&mut self) {
+        if !self.file_loaded {
+            return;
+        }
+        if let (Some(x), Some(y)) = (
+            self.timeseries.get(self.xaxis),
+            self.timeseries.get(self.yaxis),
+        ) {
+            let names = [x.name.clone(), y.name.clone()];
+            self.saved_axes.insert(self.file_path.clone(), names);
+        }
+    }
 
-            // Store index value
-            self.index.push(
-                values
-                    .first()
-                    .ok_or(format!("Line {}: Missing index value", line_num + 2))?
-                    .to_string(),
-            );
+    // Plots the channels last plotted from the same file, when it still has them
+    fn restore_axes(&mut self) {
+        let Some([x, y]) = self.saved_axes.get(&self.file_path) else {
+            return;
+        };
+        let position = |name: &String| self.timeseries.iter().position(|ts| ts.name == *name);
+        if let (Some(x), Some(y)) = (position(x), position(y)) {
+            self.xaxis = x;
+            self.yaxis = y;
+        }
+    }
 
-            // Store timeseries values
-            for (i, value) in values.iter().skip(1).enumerate() {
-                if let Some(series) = self.timeseries.get_mut(i) {
-                    let num = value.parse::<f64>().map_err(|_| {
-                        format!(
-                            "Line {}: Invalid numeric value '{}' in column '{}'",
-                            line_num + 2,
-                            value,
-                            series.name
-                        )
-                    })?;
+    // Takes the shown file out of the session state
+    fn stash_file(&mut self) -> OpenFile {
+        self.file_loaded = false;
+        OpenFile {
+            file_path: std::mem::take(&mut self.file_path),
+            index_name: std::mem::take(&mut self.index_name),
+            index: std::mem::take(&mut self.index),
+            times: std::mem::take(&mut self.times),
+            time_step: self.time_step,
+            time_format: self.time_format,
+            timeseries: std::mem::take(&mut self.timeseries),
+            raw: self.raw.take(),
+            load_report: self.load_report.take(),
+            partial: self.partial_load,
+            xaxis: self.xaxis,
+            yaxis: self.yaxis,
+            snapshots: std::mem::take(&mut self.snapshots),
+            undo_stack: std::mem::take(&mut self.undo_stack),
+            redo_stack: std::mem::take(&mut self.redo_stack),
+        }
+    }
 
-                    series.data.push(match num {
-                        x if x.is_nan() || x == self.nan => DataPoint::NaN,
-                        x => DataPoint::Valid(x),
-                    });
-                }
-            }
+    // Shows another file of the session, keeping the current one as it is.
+    // Review states stay, as they belong to the combined exclusion list.
+    fn switch_file(&mut self, n: usize) {
+        let mut other_files = std::mem::take(&mut self.other_files);
+        let file = other_files.remove(n);
+        if self.file_loaded {
+            other_files.insert(n, self.stash_file());
         }
 
-        Ok(())
+        let review_states = std::mem::take(&mut self.review_states);
+        self.reset_session();
+        self.other_files = other_files;
+        self.review_states = review_states;
+        self.file_path = file.file_path;
+        self.index_name = file.index_name;
+        self.index = file.index;
+        self.times = file.times;
+        self.time_step = file.time_step;
+        self.time_format = file.time_format;
+        self.timeseries = file.timeseries;
+        self.raw = file.raw;
+        self.load_report = file.load_report;
+        self.partial_load = file.partial;
+        self.xaxis = file.xaxis;
+        self.yaxis = file.yaxis;
+        self.snapshots = file.snapshots;
+        self.undo_stack = file.undo_stack;
+        self.redo_stack = file.redo_stack;
+        self.file_loaded = true;
+        self.exclusion_curve.clear();
+        self.exclusion_curve_is_closed = false;
+        self.msg = format!("Switched to {}", file_label(&self.file_path));
     }
 
-    fn process_points<F>(&self, handler: F) -> Vec<[f64; 2]>
-    where
-        F: Fn(&DataPoint, &DataPoint) -> Option<[f64; 2]>,
-    {
-        let x_series = &self.timeseries[self.xaxis];
-        let y_series = &self.timeseries[self.yaxis];
-
-        izip!(&x_series.data, &y_series.data)
-            .filter_map(|(x, y)| handler(x, y))
-            .collect()
+    // Loads another file into the session, keeping the current one once the
+    // new one is read
+    fn add_data_file(&mut self, path: String) {
+        let mut open = std::iter::once(&self.file_path)
+            .chain(self.other_files.iter().map(|file| &file.file_path));
+        if open.any(|open| Path::new(open) == Path::new(&path)) {
+            self.msg = format!("{} is already in the session", file_label(&path));
+            return;
+        }
+        self.open_data_file(path);
+        self.adding_file = true;
     }
 
-    fn convert_points(&self) -> Vec<[f64; 2]> {
-        self.process_points(|x, y| {
-            match (x, y) {
-                (DataPoint::Valid(x_val), DataPoint::Valid(y_val)) => Some([*x_val, *y_val]),
-                _ => Some([-100.0, -100.0]), // Default value for invalid
-            }
-        })
+    // Keeps the shown file in the session, under the one about to be
+    // installed, when it's being added
+    fn stash_for_added_file(&mut self) -> Option<(Vec<OpenFile>, HashMap<PointKey, ReviewStatus>)> {
+        if !std::mem::take(&mut self.adding_file) || !self.file_loaded {
+            return None;
+        }
+        self.remember_axes();
+        let current = self.stash_file();
+        let mut other_files = std::mem::take(&mut self.other_files);
+        other_files.push(current);
+        Some((other_files, std::mem::take(&mut self.review_states)))
     }
 
-    fn extract_valid_points(&self) -> Vec<[f64; 2]> {
-        self.process_points(|x, y| {
-            match (x, y) {
-                (DataPoint::Valid(x_val), DataPoint::Valid(y_val)) => Some([*x_val, *y_val]),
-                _ => None, // Filter out invalid
-            }
-        })
+    fn pick_data_file(&mut self) {
+        if let Some(path) = self.file_dialog().pick_file() {
+            self.open_data_file(path.display().to_string());
+        } else {
+            self.msg = "No file selected.".into();
+        }
     }
 
-    fn extract_excluded_points(&self) -> Vec<[f64; 2]> {
-        self.process_points(|x, y| {
-            match (x, y) {
-                (DataPoint::Excluded(x_val, _), DataPoint::Excluded(y_val, _)) => {
-                    Some([*x_val, *y_val])
+    // Loads the file right away, unless it is expected not to fit in memory
+    fn open_data_file(&mut self, path: String) {
+        self.adding_file = false;
+        self.remember_axes();
+        self.last_dir = Path::new(&path).parent().map(Path::to_path_buf);
+        if is_parquet(&path) {
+            self.load_table(path, "Parquet file", read_parquet);
+            return;
+        }
+        if is_workbook(&path) {
+            match sheet_names(&path) {
+                Ok(sheets) if sheets.len() == 1 => self.load_sheet(path, &sheets[0]),
+                Ok(sheets) if sheets.is_empty() => self.msg = "Load error: No sheets found".into(),
+                Ok(sheets) => {
+                    self.msg = "Pick the sheet to load".into();
+                    self.pending_sheet = Some(PendingSheet {
+                        path,
+                        sheets,
+                        selected: 0,
+                    });
                 }
-                _ => None, // Filter out invalid
+                Err(e) => self.msg = format!("Load error: {}", e),
             }
-        })
-    }
-
-    fn export_exclusions(&self, path: PathBuf) -> std::io::Result<()> {
-        let exclusions = self
-            .timeseries
-            .iter()
-            .flat_map(|ts| {
-                ts.data
-                    .iter()
-                    .zip(&self.index)
-                    .filter_map(|(val, timestamp)| match val {
-                        DataPoint::Excluded(_, reason) => {
-                            Some(((*timestamp).clone(), (*reason).clone()))
-                        }
-                        _ => None,
-                    })
-                    .map(|(timestamp, reason)| {
-                        let (mast, sensor) = unwrap_name(&ts.name).unwrap();
-                        let time =
-                            NaiveDateTime::parse_from_str(&timestamp, "%Y-%m-%d %H:%M").unwrap();
-                        let time_ini = time - Duration::minutes(self.time_buffer as i64);
-                        let time_end = time + Duration::minutes(self.time_buffer as i64);
-                        (mast, sensor, reason, time_ini, time_end)
-                    })
-                    .collect::<Vec<(String, String, String, NaiveDateTime, NaiveDateTime)>>()
-            })
-            .collect::<Vec<(String, String, String, NaiveDateTime, NaiveDateTime)>>();
-
-        let mut groups: HashMap<(&str, &str, &str), Vec<_>> = HashMap::new();
-        for item in &exclusions {
-            groups
-                .entry((&item.0, &item.1, &item.2))
-                .or_default()
-                .push((item.3, item.4));
+            return;
         }
 
-        let mut merged = Vec::new();
-        let now = Local::now().naive_local();
-        for ((s1, s2, s3), mut ranges) in groups.into_iter() {
-            ranges.sort_by_key(|(start, _)| *start);
-
-            let (mut current_start, mut current_end) = ranges[0];
+        let (headers, rows) = match Self::inspect_data_file(&path) {
+            Ok(shape) => shape,
+            Err(e) => {
+                self.msg = format!("Load error: {}", e);
+                return;
+            }
+        };
 
-            for (start, end) in ranges.into_iter().skip(1) {
-                if start <= current_end {
-                    current_end = current_end.max(end);
-                } else {
-                    merged.push((
-                        s1.to_string(),
-                        s2.to_string(),
-                        s3.to_string(),
-                        current_start,
-                        current_end,
-                        now,
-                    ));
-                    current_start = start;
-                    current_end = end;
-                }
+        let mut masts: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (n, name) in headers.iter().enumerate() {
+            if let Ok((mast, _)) = self.settings.name_convention.split(name) {
+                masts.entry(mast).or_default().push(n);
             }
+        }
+        let pending = PendingLoad {
+            path,
+            columns: vec![true; headers.len()],
+            headers,
+            masts,
+            rows,
+            limit: available_memory().map_or(DEFAULT_MEMORY_LIMIT, |bytes| bytes / 2),
+            every: 1,
+        };
 
-            merged.push((
-                s1.to_string(),
-                s2.to_string(),
-                s3.to_string(),
-                current_start,
-                current_end,
-                now,
-            ));
+        if pending.estimate() > pending.limit {
+            self.msg = format!(
+                "Loading this file needs about {}",
+                format_bytes(pending.estimate())
+            );
+            self.pending_load = Some(pending);
+        } else if self.choose_columns {
+            self.msg = "Pick the columns to load".into();
+            self.pending_load = Some(pending);
+        } else {
+            self.load_data_file(pending.path, LoadOptions::default());
         }
+    }
 
-        let file = File::create(Path::new(&path))?;
-        let mut writer = BufWriter::new(file);
-        let fmt = "%Y-%m-%d %H:%M:%S";
-        for ex in merged.iter() {
-            writeln!(
-                writer,
-                "{}\t{}\t{}\t{}\t{}\t{}",
-                ex.0,
-                ex.1,
-                ex.2,
-                ex.3.format(fmt),
-                ex.4.format(fmt),
-                ex.5.format(fmt)
-            )?;
+    fn time_format_note(&self) -> String {
+        match self.time_format {
+            None => ", timestamps not recognised".to_owned(),
+            Some(TIMESTAMP_FORMAT) => String::new(),
+            Some(format) => format!(", timestamps read as {}", format),
         }
+    }
 
-        Ok(())
+    // Maps the text file and reads only its index, columns are parsed when used
+    fn load_data_file(&mut self, path: String, options: LoadOptions) {
+        let every = options.every;
+        let skip = self.skip_malformed;
+        let reader = path.clone();
+        self.start_load(path, "File", options, move |progress| {
+            Parsed::mapped(&reader, every, skip, progress)
+        });
     }
 
-    fn exclude_timeseries_data(&mut self, axis: usize, is_inside_curve: &[bool]) {
-        self.timeseries[axis]
-            .data
-            .iter_mut()
-            .zip(is_inside_curve.iter())
-            .for_each(|(val, exclude)| {
-                if *exclude {
-                    match val {
-                        DataPoint::Valid(v) => *val = DataPoint::Excluded(*v, self.reason.clone()),
-                        _ => (),
-                    }
-                }
-            });
+    fn load_sheet(&mut self, path: String, sheet: &str) {
+        let source = format!("Sheet '{}'", sheet);
+        let sheet = sheet.to_owned();
+        self.load_table(path, &source, move |path| read_sheet(path, &sheet));
     }
 
-    fn exclude_data(&mut self) {
-        if self.reason.is_empty() {
-            self.msg = "Write a reason for exclusion".to_owned();
-        } else if self.exclusion_curve.len() < 3 {
-            self.msg = "At least 3 points are needed to define an exclusion area".to_owned();
-        } else if !self.exclusion_curve_is_closed {
-            self.msg = "The exclusion area must be closed".to_owned();
-        } else {
-            let curve = self.exclusion_curve.clone();
-            let data = self.convert_points();
-            let is_inside = check_inside_curve(curve, data);
+    // Workbooks and Parquet files are read whole, with their first column as
+    // the index
+    fn load_table(
+        &mut self,
+        path: String,
+        source: &str,
+        read: impl FnOnce(&str) -> Result<Table, String> + Send + 'static,
+    ) {
+        let reader = path.clone();
+        self.start_load(path, source, LoadOptions::default(), move |_| {
+            Parsed::table(read(&reader)?)
+        });
+    }
 
-            if !self.exclusion_names.contains(&self.reason) {
-                self.exclusion_names.push(self.reason.clone());
-            }
+    // Reads the file on a background thread, the current one staying in place
+    // until it's done
+    fn start_load(
+        &mut self,
+        path: String,
+        source: &str,
+        options: LoadOptions,
+        read: impl FnOnce(&dyn Fn(f32)) -> Result<Parsed, String> + Send + 'static,
+    ) {
+        self.msg = format!("Loading {}...", file_label(&path));
+        let duplicates = self.duplicate_policy;
+        let missing = self.missing.clone();
+        let direction_tokens = self.settings.direction_tokens.clone();
+        self.loading = Some(Loading {
+            job: LoadJob::spawn(move |progress| {
+                read(progress)?.resolve_duplicates(duplicates, &missing, &direction_tokens)
+            }),
+            path,
+            source: source.to_owned(),
+            options,
+            duplicates,
+        });
+    }
 
-            if self.excludex {
-                self.exclude_timeseries_data(self.xaxis, &is_inside);
-            }
+    fn poll_load(&mut self, ctx: &egui::Context) {
+        let Some(loading) = self.loading.as_mut() else {
+            return;
+        };
+        let Some(result) = loading.job.poll() else {
+            ctx.request_repaint();
+            return;
+        };
+        let Some(loading) = self.loading.take() else {
+            return;
+        };
 
-            if self.excludey {
-                self.exclude_timeseries_data(self.yaxis, &is_inside);
+        // A file that fails to load leaves the current one in place
+        match result {
+            Ok(mut parsed) => {
+                let kept = self.stash_for_added_file();
+                self.file_path = loading.path.clone();
+                let malformed = parsed.report.take();
+                let duplicates = std::mem::take(&mut parsed.duplicates);
+                self.load_report = Some(LoadReport {
+                    acknowledged: malformed.as_ref().map_or(true, |report| report.total == 0),
+                    malformed,
+                    duplicates,
+                    policy: loading.duplicates,
+                    headers: parsed.headers.clone(),
+                });
+                let columns = loading.options.columns;
+                self.partial_load = loading.options.every > 1
+                    || columns
+                        .as_ref()
+                        .is_some_and(|columns| columns.contains(&false));
+                self.install_file(parsed, columns);
+                match kept {
+                    Some((other_files, review_states)) => {
+                        self.other_files = other_files;
+                        self.review_states = review_states;
+                    }
+                    None => self.unsaved_changes = false,
+                }
+                let separated = match self.raw.as_ref().map(|raw| raw.delimiter()) {
+                    Some(b';') => " (semicolon separated)",
+                    Some(b',') => " (comma separated)",
+                    _ => "",
+                };
+                self.msg = format!(
+                    "{} loaded successfully{}{}",
+                    loading.source,
+                    separated,
+                    self.time_format_note()
+                );
+                if loading.options.every > 1 {
+                    self.msg += &format!(", keeping one row every {}", loading.options.every);
+                }
+                if let Some(load_report) = self.load_report.as_ref().filter(|r| r.has_findings()) {
+                    if let Some(report) = load_report.malformed() {
+                        self.msg += &format!(
+                            ", {} malformed cells in {} rows {}",
+                            report.total,
+                            report.rows,
+                            match report.skipped {
+                                true => "skipped",
+                                false => "read as missing",
+                            }
+                        );
+                    }
+                    if !load_report.duplicates.is_empty() {
+                        self.msg += &format!(
+                            ", {} repeated timestamps ({})",
+                            load_report.duplicates.len(),
+                            load_report.policy.label().to_lowercase()
+                        );
+                    }
+                    self.show_load_report = true;
+                }
+                let breaks = cadence(&self.times).map_or(0, |step| gaps(&self.times, step).len());
+                if breaks > 0 {
+                    self.msg += &format!(", {} timestamp gaps (see Timestamp gaps)", breaks);
+                }
+                self.file_loaded = true;
+                self.restore_axes();
             }
+            Err(e) => {
+                self.adding_file = false;
+                self.msg = format!("Load error: {}", e);
+            }
+        }
+        self.audit.record(format!(
+            "Load {} ({}): {}",
+            loading.path, loading.source, self.msg
+        ));
+    }
 
-            self.exclusion_curve.clear();
-            self.exclusion_curve_is_closed = false;
-            self.msg = format!("Data excluded by '{}' reason", self.reason).to_owned();
+    // Replaces the data with a parsed file, keeping only the selected columns
+    // after the index when given
+    fn install_file(&mut self, parsed: Parsed, columns: Option<Vec<bool>>) {
+        self.reset_session();
+        self.index_name = parsed.headers[0].clone();
+        self.index = parsed.index;
+        self.times = parsed.times;
+        self.time_format = parsed.time_format;
+        self.time_step = cadence(&self.times).map_or(f64::INFINITY, |step| 1.5 * step);
+
+        let selected = |i: usize| match &columns {
+            Some(columns) => columns.get(i - 1).copied().unwrap_or(false),
+            None => true,
+        };
+        let names = parsed.headers.into_iter().enumerate().skip(1);
+        match parsed.content {
+            Content::Mapped(raw) => {
+                self.timeseries = names
+                    .filter(|(i, _)| selected(*i))
+                    .map(|(i, name)| TimeSeries {
+                        name,
+                        data: ChannelData::default(),
+                        sources: Vec::new(),
+                        column: Some(i),
+                    })
+                    .collect();
+                self.raw = Some(raw);
+            }
+            Content::Columns(values) => {
+                self.timeseries = izip!(names, values)
+                    .filter(|((i, _), _)| selected(*i))
+                    .map(|((_, name), values)| TimeSeries {
+                        name,
+                        data: ChannelData::read(values, &self.missing),
+                        sources: Vec::new(),
+                        column: None,
+                    })
+                    .collect();
+                self.raw = None;
+            }
         }
     }
-}
 
-impl eframe::App for ManualDataCleanerApp {
-    fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
-        eframe::egui::SidePanel::left("left_panel")
-            .show_separator_line(true)
+    // Malformed cells of the last file loaded, by column and by line, and its
+    // repeated timestamps
+    fn show_load_report_window(&mut self, ctx: &egui::Context) {
+        let Some(load_report) = &self.load_report else {
+            return;
+        };
+        let header = |column: usize| load_report.headers.get(column).map_or("?", String::as_str);
+
+        let mut open = self.show_load_report;
+        let mut acknowledge = false;
+        egui::Window::new("Load report")
+            .open(&mut open)
+            .default_width(400.0)
             .show(ctx, |ui| {
-                eframe::egui::Grid::new("left_grid")
-                    .striped(false)
-                    .num_columns(3)
-                    .spacing([10.0, 10.0])
-                    .show(ui, |ui| {
-                        ui.end_row();
+                if let Some(report) = load_report.malformed() {
+                    ui.label(match report.skipped {
+                        true => format!(
+                            "{} cells in {} rows are not numbers, their rows were skipped.",
+                            report.total, report.rows
+                        ),
+                        false => format!(
+                            "{} cells are not numbers, they were read as missing.",
+                            report.total
+                        ),
+                    });
+                    if !load_report.acknowledged {
+                        ui.horizontal(|ui| {
+                            ui.label("Nothing is exported until they are acknowledged.");
+                            acknowledge = ui.button("Acknowledge").clicked();
+                        });
+                    }
+                    ui.separator();
 
-                        ui.label("Missing value");
-                        ui.add_sized([100., 20.], DragValue::new(&mut self.nan));
-                        let load_button = ui.add_sized([100., 20.], Button::new("Load File"));
-                        if load_button.clicked() {
-                            if let Some(path) = rfd::FileDialog::new().pick_file() {
-                                self.file_path = path.display().to_string();
-                                match self.parse_data_file() {
-                                    Ok(()) => {
-                                        self.msg = "File loaded successfully".into();
-                                        self.file_loaded = true;
-                                    }
-                                    Err(e) => self.msg = format!("Load error: {}", e),
-                                }
-                            } else {
-                                self.msg = "No file selected.".into();
+                    egui::Grid::new("load_report_columns")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Column");
+                            ui.strong("Cells");
+                            ui.end_row();
+                            for (&column, count) in &report.columns {
+                                ui.label(header(column));
+                                ui.label(count.to_string());
+                                ui.end_row();
                             }
-                        }
-
-                        ui.end_row();
+                        });
+                    ui.separator();
+
+                    if report.total > report.cells.len() {
+                        ui.label(format!("The first {} cells:", report.cells.len()));
+                    }
+                    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                    egui::ScrollArea::vertical()
+                        .id_source("load_report_cells")
+                        .max_height(300.0)
+                        .show_rows(ui, row_height, report.cells.len(), |ui, rows| {
+                            egui::Grid::new("load_report_cells")
+                                .num_columns(3)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for (line, column, text) in &report.cells[rows] {
+                                        ui.label(format!("Line {}", line));
+                                        ui.label(header(*column));
+                                        ui.label(format!("'{}'", text));
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+
+                let duplicates = &load_report.duplicates;
+                if !duplicates.is_empty() {
+                    ui.separator();
+                    ui.label(format!(
+                        "{} timestamps are on several rows, resolved as {}.",
+                        duplicates.len(),
+                        load_report.policy.label().to_lowercase()
+                    ));
+                    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                    egui::ScrollArea::vertical()
+                        .id_source("load_report_duplicates")
+                        .max_height(300.0)
+                        .show_rows(ui, row_height, duplicates.len(), |ui, rows| {
+                            egui::Grid::new("load_report_duplicates")
+                                .num_columns(2)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for (timestamp, lines) in &duplicates[rows] {
+                                        let lines: Vec<String> =
+                                            lines.iter().map(usize::to_string).collect();
+                                        ui.label(timestamp);
+                                        ui.label(format!("Lines {}", lines.join(", ")));
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+            });
+        self.show_load_report = open;
+        if let (true, Some(load_report)) = (acknowledge, &mut self.load_report) {
+            load_report.acknowledged = true;
+            self.audit.record(format!(
+                "Acknowledge the malformed cells of {}",
+                self.file_path
+            ));
+        }
+    }
+
+    fn show_pending_sheet_window(&mut self, ctx: &egui::Context) {
+        let Some(pending) = self.pending_sheet.as_mut() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut load = false;
+        egui::Window::new("Select sheet")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                for (n, sheet) in pending.sheets.iter().enumerate() {
+                    ui.radio_value(&mut pending.selected, n, sheet);
+                }
+                ui.separator();
+                load = ui.button("Load").clicked();
+            });
+
+        if load {
+            if let Some(pending) = self.pending_sheet.take() {
+                self.load_sheet(pending.path, &pending.sheets[pending.selected]);
+            }
+        } else if !open {
+            self.pending_sheet = None;
+            self.msg = "Load cancelled".into();
+        }
+    }
+
+    // Asks before a dropped file replaces a session with unsaved exclusions
+    fn show_pending_drop_window(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.pending_drop.clone() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut load = false;
+        let mut cancel = false;
+        egui::Window::new("Replace the session?")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Loading {} drops the exclusions not yet saved or exported.",
+                    file_label(&path)
+                ));
+                ui.horizontal(|ui| {
+                    load = ui.button("Load anyway").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if load {
+            self.pending_drop = None;
+            self.open_data_file(path);
+        } else if cancel || !open {
+            self.pending_drop = None;
+            self.msg = "Load cancelled".into();
+        }
+    }
+
+    fn show_pending_load_window(&mut self, ctx: &egui::Context) {
+        let Some(pending) = self.pending_load.as_mut() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut load = false;
+        egui::Window::new("Load columns")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if pending.estimate() > pending.limit {
+                    ui.label(format!(
+                        "{} rows and {} columns need about {}, over the {} limit.",
+                        pending.rows,
+                        pending.headers.len(),
+                        format_bytes(pending.estimate()),
+                        format_bytes(pending.limit)
+                    ));
+                    ui.label("Sub-sample the rows or load fewer columns:");
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label("Keep one row every");
+                    ui.add(DragValue::new(&mut pending.every).clamp_range(1..=1000));
+                });
+
+                // Shortcuts adding the columns of a whole mast
+                ui.horizontal_wrapped(|ui| {
+                    if ui.button("All").clicked() {
+                        pending.columns.fill(true);
+                    }
+                    if ui.button("None").clicked() {
+                        pending.columns.fill(false);
+                    }
+                    for (mast, columns) in &pending.masts {
+                        let button = ui
+                            .button(format!("All from {}", mast))
+                            .on_hover_text(format!("{} columns", columns.len()));
+                        if button.clicked() {
+                            for &n in columns {
+                                pending.columns[n] = true;
+                            }
+                        }
+                    }
+                });
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (name, selected) in
+                            pending.headers.iter().zip(pending.columns.iter_mut())
+                        {
+                            ui.checkbox(selected, name);
+                        }
+                    });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let selected = pending.columns.iter().filter(|&&c| c).count();
+                    ui.label(format!(
+                        "{} of {} columns, estimated size: {}",
+                        selected,
+                        pending.columns.len(),
+                        format_bytes(pending.estimate())
+                    ));
+                    load = ui.add_enabled(selected > 0, Button::new("Load")).clicked();
+                });
+            });
+
+        if load {
+            if let Some(pending) = self.pending_load.take() {
+                self.load_data_file(
+                    pending.path,
+                    LoadOptions {
+                        every: pending.every,
+                        columns: Some(pending.columns),
+                    },
+                );
+            }
+        } else if !open {
+            self.pending_load = None;
+            self.msg = "Load cancelled".into();
+        }
+    }
+
+    // Parses the values of a channel the first time it is needed
+    fn materialize(&mut self, series: usize) {
+        let Some(ts) = self.timeseries.get_mut(series) else {
+            return;
+        };
+        let Some((column, report)) = ts.parse(self.raw.as_ref(), &self.missing) else {
+            return;
+        };
+        let found = self
+            .load_report
+            .as_mut()
+            .map_or(0, |load_report| load_report.record(column, report));
+        self.revision += 1;
+        if found > 0 {
+            self.msg = format!(
+                "{} cells of '{}' are not numbers and were read as missing, see the load report",
+                found, ts.name
+            );
+            self.show_load_report = true;
+        }
+    }
+
+    // Nothing is exported while malformed cells of any file of the session
+    // haven't been looked at, as they were read as missing
+    fn check_acknowledged(&self) -> Result<(), String> {
+        let reports = std::iter::once(&self.load_report)
+            .chain(self.other_files.iter().map(|file| &file.load_report));
+        match reports.flatten().any(|report| !report.acknowledged) {
+            true => Err("Some cells are not numbers and were read as missing. Acknowledge them in the load report first".into()),
+            false => Ok(()),
+        }
+    }
+
+    // Makes sure every channel shown is parsed
+    fn materialize_plotted(&mut self) {
+        let mut used = vec![self.xaxis, self.yaxis];
+        used.extend(self.extra_yaxes.iter().copied());
+        used.extend(self.color_by);
+        if self.show_plot_window {
+            used.push(self.window_yaxis);
+        }
+        if self.show_profile {
+            let (speeds, directions) = self.profile_channels();
+            used.extend(
+                speeds
+                    .into_iter()
+                    .chain(directions)
+                    .map(|(series, _)| series),
+            );
+        }
+        for series in used {
+            self.materialize(series);
+        }
+    }
+
+    // Clears the data and everything that refers to it
+    fn reset_session(&mut self) {
+        self.revision += 1;
+        self.index.clear();
+        self.timeseries.clear();
+        self.extra_yaxes.clear();
+        self.window_curve.clear();
+        self.exclude_targets.clear();
+        self.exclude_sources.clear();
+        self.color_by = None;
+        self.matrix_channels.clear();
+        self.icing_channels = (None, None);
+        self.sector_channels = (None, None);
+        self.shear_direction = None;
+        self.snapshots.clear();
+        self.operations_since_snapshot = 0;
+        self.clear_history();
+        self.window_yaxis = 0;
+        self.hovered_row = None;
+        self.pinned_row = None;
+        self.rule_job = None;
+        self.detections.clear();
+        self.report_plots.clear();
+        self.review_states.clear();
+        self.other_files.clear();
+    }
+
+    // Timestamps as plot coordinates, and the step beyond which lines are broken
+    fn update_times(&mut self) {
+        (self.time_format, self.times, self.time_step) = read_times(&self.index);
+    }
+
+    // The whole session, with every channel of every file parsed
+    fn project(&mut self) -> Project {
+        for n in 0..self.timeseries.len() {
+            self.materialize(n);
+        }
+        for file in &mut self.other_files {
+            for ts in &mut file.timeseries {
+                let parsed = ts.parse(file.raw.as_ref(), &self.missing);
+                if let (Some((column, report)), Some(load_report)) =
+                    (parsed, file.load_report.as_mut())
+                {
+                    load_report.record(column, report);
+                }
+            }
+        }
+
+        let other_files = self
+            .other_files
+            .iter()
+            .map(|file| ProjectFile {
+                data_file: file.file_path.clone(),
+                index_name: file.index_name.clone(),
+                index: file.index.clone(),
+                partial: file.partial,
+                channels: project_channels(&file.timeseries),
+                xaxis: file.xaxis,
+                yaxis: file.yaxis,
+            })
+            .collect();
+
+        Project {
+            data_file: self.file_path.clone(),
+            index_name: self.index_name.clone(),
+            index: self.index.clone(),
+            partial: self.partial_load,
+            missing: self.missing.clone(),
+            nan: None,
+            channels: project_channels(&self.timeseries),
+            xaxis: self.xaxis,
+            yaxis: self.yaxis,
+            extra_yaxes: self.extra_yaxes.clone(),
+            time_buffer: self.time_buffer,
+            audit: std::mem::take(&mut self.audit.entries),
+            other_files,
+        }
+    }
+
+    fn save_project(&mut self, path: &Path) -> Result<(), String> {
+        if !self.file_loaded {
+            return Err("Load a data file first".into());
+        }
+
+        let mut project = self.project();
+        let result = project.save(path);
+        self.audit.entries = std::mem::take(&mut project.audit);
+        result
+    }
+
+    fn open_project(&mut self, path: &Path) -> Result<(), String> {
+        let project = Project::load(path)?;
+
+        self.reset_session();
+        self.unsaved_changes = false;
+        self.raw = None;
+        self.load_report = None;
+        self.file_path = project.data_file;
+        self.index_name = project.index_name;
+        self.index = project.index;
+        self.partial_load = project.partial;
+        self.missing = project.missing;
+        self.missing_text = format_markers(&self.missing);
+        self.update_times();
+        self.timeseries = self.restore_channels(project.channels);
+
+        self.xaxis = project.xaxis;
+        self.yaxis = project.yaxis;
+        self.extra_yaxes = project.extra_yaxes;
+        self.time_buffer = project.time_buffer;
+        self.audit.entries = project.audit;
+        self.file_loaded = true;
+
+        for file in project.other_files {
+            let (time_format, times, time_step) = read_times(&file.index);
+            let timeseries = self.restore_channels(file.channels);
+            self.other_files.push(OpenFile {
+                file_path: file.data_file,
+                index_name: file.index_name,
+                index: file.index,
+                times,
+                time_step,
+                time_format,
+                timeseries,
+                raw: None,
+                load_report: None,
+                partial: file.partial,
+                xaxis: file.xaxis,
+                yaxis: file.yaxis,
+                snapshots: Vec::new(),
+                undo_stack: Vec::new(),
+                redo_stack: Vec::new(),
+            });
+        }
+
+        Ok(())
+    }
+
+    // Channels of a project as time series, with their exclusions stamped
+    fn restore_channels(&mut self, channels: Vec<ProjectChannel>) -> Vec<TimeSeries> {
+        let mut timeseries = Vec::new();
+        for channel in channels {
+            let mut data: ChannelData = channel
+                .values
+                .into_iter()
+                .map(|v| v.map_or(DataPoint::NaN, DataPoint::Valid))
+                .collect();
+            let analysts: HashMap<usize, &String> = channel
+                .analysts
+                .iter()
+                .flat_map(|(analyst, rows)| rows.iter().map(move |&row| (row, analyst)))
+                .collect();
+            let categories: HashMap<usize, Category> = channel
+                .categories
+                .iter()
+                .flat_map(|(&category, rows)| rows.iter().map(move |&row| (row, category)))
+                .collect();
+            for (reason, rows) in &channel.excluded {
+                for &row in rows {
+                    let stamp = Stamp {
+                        reason: reason.clone(),
+                        category: categories.get(&row).copied(),
+                        analyst: analysts
+                            .get(&row)
+                            .map_or_else(String::new, |a| a.to_string()),
+                    };
+                    data.exclude(row, &stamp);
+                }
+                self.register_reason(reason);
+            }
+            timeseries.push(TimeSeries {
+                name: channel.name,
+                data,
+                sources: channel.sources,
+                column: None,
+            });
+        }
+        timeseries
+    }
+
+    // Rows where every plotted channel is valid
+    fn concurrent_rows(&self) -> Vec<bool> {
+        let mut channels = vec![self.yaxis];
+        if self.plot_mode == PlotMode::Scatter {
+            channels.push(self.xaxis);
+        }
+        channels.extend(self.plotted_extra_yaxes());
+
+        (0..self.index.len())
+            .map(|row| {
+                channels
+                    .iter()
+                    .all(|&n| matches!(self.timeseries[n].data.get(row), Some(DataPoint::Valid(_))))
+            })
+            .collect()
+    }
+
+    // Whether each row is shown: all of them, or only the concurrent ones
+    fn shown_rows(&self) -> Vec<bool> {
+        match self.concurrent_only {
+            true => self.concurrent_rows(),
+            false => vec![true; self.index.len()],
+        }
+    }
+
+    fn process_points<F>(&self, yaxis: usize, handler: F) -> Vec<[f64; 2]>
+    where
+        F: Fn(DataPoint<'_>, DataPoint<'_>) -> Option<[f64; 2]>,
+    {
+        let y_series = &self.timeseries[yaxis];
+        let shown = self.shown_rows();
+
+        match self.plot_mode {
+            PlotMode::Scatter => {
+                let x_series = &self.timeseries[self.xaxis];
+                izip!(x_series.data.iter(), y_series.data.iter(), &shown)
+                    .filter_map(|(x, y, shown)| match shown {
+                        true => handler(x, y),
+                        false => handler(DataPoint::NaN, DataPoint::NaN),
+                    })
+                    .collect()
+            }
+            PlotMode::TimeSeries => izip!(&self.times, y_series.data.iter(), &shown)
+                .filter_map(|(t, y, shown)| match t.is_finite() && *shown {
+                    true => handler(DataPoint::Valid(*t), y),
+                    false => handler(DataPoint::NaN, DataPoint::NaN),
+                })
+                .collect(),
+        }
+    }
+
+    fn convert_points(&self, yaxis: usize) -> Vec<[f64; 2]> {
+        self.process_points(yaxis, |x, y| {
+            match (x, y) {
+                (DataPoint::Valid(x_val), DataPoint::Valid(y_val)) => Some([x_val, y_val]),
+                _ => Some([f64::NAN, f64::NAN]), // Never inside any curve
+            }
+        })
+    }
+
+    fn extract_valid_points(&self, yaxis: usize) -> Vec<[f64; 2]> {
+        self.process_points(yaxis, |x, y| {
+            match (x, y) {
+                (DataPoint::Valid(x_val), DataPoint::Valid(y_val)) => Some([x_val, y_val]),
+                _ => None, // Filter out invalid
+            }
+        })
+    }
+
+    // Excluded points of a channel as plotted, with their row and reason
+    fn excluded_rows(&self, yaxis: usize) -> Vec<(usize, [f64; 2], &str)> {
+        let shown = self.shown_rows();
+        izip!(self.timeseries[yaxis].data.iter(), &shown)
+            .enumerate()
+            .filter_map(|(row, (y, shown))| {
+                let (DataPoint::Excluded(y_val, reason), true) = (y, shown) else {
+                    return None;
+                };
+                let x_val = match self.plot_mode {
+                    PlotMode::Scatter => match self.timeseries[self.xaxis].data.get(row)? {
+                        DataPoint::Excluded(x_val, _) => x_val,
+                        _ => return None,
+                    },
+                    PlotMode::TimeSeries => {
+                        self.times.get(row).copied().filter(|t| t.is_finite())?
+                    }
+                };
+                Some((row, [x_val, y_val], reason))
+            })
+            .collect()
+    }
+
+    fn extract_excluded_points(&self, yaxis: usize) -> Vec<Vec<[f64; 2]>> {
+        let mut points = vec![Vec::new(); self.exclusion_names.len()];
+        for (_, point, reason) in self.excluded_rows(yaxis) {
+            if let Some(n) = self.exclusion_names.iter().position(|name| name == reason) {
+                points[n].push(point);
+            }
+        }
+        points
+    }
+
+    fn cached_excluded_rows(&mut self, yaxis: usize) -> RowPoints {
+        self.check_points_cache();
+        if !self.points_cache.excluded_rows.contains_key(&yaxis) {
+            let rows = self
+                .excluded_rows(yaxis)
+                .into_iter()
+                .map(|(row, point, _)| (row, point))
+                .collect();
+            self.points_cache.excluded_rows.insert(yaxis, rows);
+        }
+        Rc::clone(&self.points_cache.excluded_rows[&yaxis])
+    }
+
+    // Empties the point cache when the plotted data has changed since it was filled
+    fn check_points_cache(&mut self) {
+        let key = PointsKey {
+            xaxis: self.xaxis,
+            yaxis: self.yaxis,
+            extra_yaxes: self.extra_yaxes.clone(),
+            plot_mode: self.plot_mode,
+            concurrent_only: self.concurrent_only,
+            color_by: self.color_by,
+            revision: self.revision,
+        };
+        if self.points_cache.key.as_ref() != Some(&key) {
+            self.points_cache = PointsCache {
+                key: Some(key),
+                ..Default::default()
+            };
+        }
+    }
+
+    fn cached_valid_points(&mut self, yaxis: usize) -> Rc<[[f64; 2]]> {
+        self.check_points_cache();
+        if !self.points_cache.valid.contains_key(&yaxis) {
+            let points = self.extract_valid_points(yaxis).into();
+            self.points_cache.valid.insert(yaxis, points);
+        }
+        Rc::clone(&self.points_cache.valid[&yaxis])
+    }
+
+    // Excluded points by reason, with the color of each reason
+    fn cached_excluded_points(&mut self, yaxis: usize) -> Vec<(Rgb, Rc<[[f64; 2]]>)> {
+        self.check_points_cache();
+        if !self.points_cache.excluded.contains_key(&yaxis) {
+            let points = self.extract_excluded_points(yaxis);
+            let points = points.into_iter().map(Rc::from).collect();
+            self.points_cache.excluded.insert(yaxis, points);
+        }
+        izip!(&self.exclusion_names, &self.points_cache.excluded[&yaxis])
+            .map(|(name, points)| (self.reason_color(name), Rc::clone(points)))
+            .collect()
+    }
+
+    // Lowest and highest valid value of a channel
+    fn value_range(&self, series: usize) -> Option<(f64, f64)> {
+        let (min, max) = self
+            .series_values(series)
+            .into_iter()
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(v), max.max(v))
+            });
+        (min <= max).then_some((min, max))
+    }
+
+    fn cached_value_range(&mut self, series: usize) -> Option<(f64, f64)> {
+        self.check_points_cache();
+        if !self.points_cache.value_ranges.contains_key(&series) {
+            let range = self.value_range(series);
+            self.points_cache.value_ranges.insert(series, range);
+        }
+        self.points_cache.value_ranges[&series]
+    }
+
+    // Range of values the gradient spans, the full circle for directions
+    fn color_range(&self, series: usize) -> Option<(f64, f64)> {
+        if self.is_direction_series(series) {
+            return Some((0.0, 360.0));
+        }
+        self.value_range(series)
+    }
+
+    // Valid points of a channel in COLOR_BINS bins of the color channel value,
+    // and a last bin for the rows where that value is missing
+    fn extract_colored_points(&self, yaxis: usize, color_by: usize) -> Vec<Vec<[f64; 2]>> {
+        let mut bins = vec![Vec::new(); COLOR_BINS + 1];
+        let range = self.color_range(color_by);
+        for (point, value) in izip!(self.convert_points(yaxis), self.series_values(color_by)) {
+            if !point[0].is_finite() {
+                continue;
+            }
+            let bin = match range {
+                Some((min, max)) if value.is_finite() => {
+                    let t = (value - min) / (max - min).max(f64::EPSILON);
+                    ((t * COLOR_BINS as f64) as usize).min(COLOR_BINS - 1)
+                }
+                _ => COLOR_BINS,
+            };
+            bins[bin].push(point);
+        }
+        bins
+    }
+
+    // Valid points of the y axis with the gradient color of each bin
+    fn cached_colored_points(&mut self, color_by: usize) -> Vec<(Rgb, Rc<[[f64; 2]]>)> {
+        self.check_points_cache();
+        if self.points_cache.colored.is_none() {
+            let bins = self.extract_colored_points(self.yaxis, color_by);
+            self.points_cache.colored = Some(bins.into_iter().map(Rc::from).collect());
+        }
+        let bins = self.points_cache.colored.clone().unwrap_or_default();
+        bins.into_iter()
+            .enumerate()
+            .map(|(n, points)| {
+                let rgb = match n < COLOR_BINS {
+                    true => self
+                        .theme
+                        .gradient_color((n as f64 + 0.5) / COLOR_BINS as f64),
+                    false => self.theme.valid,
+                };
+                (rgb, points)
+            })
+            .collect()
+    }
+
+    fn cached_time_segments(&mut self, yaxis: usize) -> Rc<[Vec<[f64; 2]>]> {
+        self.check_points_cache();
+        if !self.points_cache.segments.contains_key(&yaxis) {
+            let segments = self.extract_time_segments(yaxis).into();
+            self.points_cache.segments.insert(yaxis, segments);
+        }
+        Rc::clone(&self.points_cache.segments[&yaxis])
+    }
+
+    // Valid points whose timestamp falls within [start, end)
+    fn extract_period_points(&self, yaxis: usize, (start, end): (f64, f64)) -> Vec<[f64; 2]> {
+        izip!(&self.times, self.convert_points(yaxis))
+            .filter(|(t, p)| **t >= start && **t < end && p[0].is_finite())
+            .map(|(_, p)| p)
+            .collect()
+    }
+
+    fn period_ranges(&self) -> Option<[(f64, f64); 2]> {
+        let [a, b] = &self.periods;
+        Some([
+            parse_date_range(&a[0], &a[1])?,
+            parse_date_range(&b[0], &b[1])?,
+        ])
+    }
+
+    // Valid samples of the y channel against time, broken into the runs
+    // without gaps, missing or excluded values in between
+    fn extract_time_segments(&self, yaxis: usize) -> Vec<Vec<[f64; 2]>> {
+        let shown = self.shown_rows();
+        let points: Vec<Option<[f64; 2]>> =
+            izip!(&self.times, self.timeseries[yaxis].data.iter(), &shown)
+                .map(|(t, y, shown)| match y {
+                    DataPoint::Valid(y_val) if t.is_finite() && *shown => Some([*t, y_val]),
+                    _ => None,
+                })
+                .collect();
+
+        line_segments(&points, self.time_step)
+    }
+
+    // Buffered exclusion periods of all measured channels of every file in the
+    // session, merged per sensor and reason, along with what had to be left out:
+    // the channels whose name gives no mast and sensor, and the timestamps that
+    // can't be read
+    fn exclusion_periods(&self) -> (Vec<ExclusionRow>, Vec<String>) {
+        let (points, skipped) = self.exclusion_points();
+        let periods = points.into_iter().map(|(_, period)| period).collect();
+        (merge_periods(periods), skipped)
+    }
+
+    // The excluded points the periods are merged from
+    fn exclusion_points(&self) -> (Vec<ExclusionPoint>, Vec<String>) {
+        let mut periods = Vec::new();
+        let mut skipped = Vec::new();
+        self.file_periods(
+            &self.timeseries,
+            &self.index,
+            self.time_format,
+            &mut periods,
+            &mut skipped,
+        );
+        for file in &self.other_files {
+            self.file_periods(
+                &file.timeseries,
+                &file.index,
+                file.time_format,
+                &mut periods,
+                &mut skipped,
+            );
+        }
+
+        (periods, skipped)
+    }
+
+    fn file_periods(
+        &self,
+        timeseries: &[TimeSeries],
+        index: &[String],
+        time_format: Option<&str>,
+        periods: &mut Vec<ExclusionPoint>,
+        skipped: &mut Vec<String>,
+    ) {
+        let format = time_format.unwrap_or(TIMESTAMP_FORMAT);
+        for ts in timeseries.iter().filter(|ts| !ts.derived()) {
+            let excluded: Vec<(&String, &Stamp)> = index
+                .iter()
+                .enumerate()
+                .filter_map(|(row, timestamp)| Some((timestamp, ts.data.stamp(row)?)))
+                .collect();
+            if excluded.is_empty() {
+                continue;
+            }
+
+            let (mast, sensor) = match self.mast_sensor(&ts.name) {
+                Ok(names) => names,
+                Err(e) => {
+                    skipped.push(format!("Column '{}': {}", ts.name, e));
+                    continue;
+                }
+            };
+            let mut unreadable = 0;
+            for (timestamp, stamp) in excluded {
+                let Ok(time) = NaiveDateTime::parse_from_str(timestamp.trim(), format) else {
+                    unreadable += 1;
+                    continue;
+                };
+                let period = ExclusionRow {
+                    mast: mast.clone(),
+                    sensor: sensor.clone(),
+                    reason: stamp.reason.clone(),
+                    start: time - Duration::minutes(self.time_buffer as i64),
+                    end: time + Duration::minutes(self.time_buffer as i64),
+                    category: stamp.category,
+                    // Left empty for points excluded before the analyst was
+                    // known, rather than crediting whoever exports
+                    analyst: stamp.analyst.clone(),
+                };
+                periods.push((time, period));
+            }
+            if unreadable > 0 {
+                skipped.push(format!(
+                    "Column '{}': {} excluded timestamps can't be read",
+                    ts.name, unreadable
+                ));
+            }
+        }
+    }
+
+    fn review_status(&self, key: &PointKey) -> ReviewStatus {
+        self.review_states
+            .get(key)
+            .copied()
+            .unwrap_or(ReviewStatus::Proposed)
+    }
+
+    // The merged periods with the state all of their points agree on, or
+    // Proposed when they don't
+    fn reviewed_rows(&self) -> Vec<ReviewedRow> {
+        let (points, _) = self.exclusion_points();
+        let periods: Vec<ExclusionRow> = points.iter().map(|(_, period)| period.clone()).collect();
+        merge_period_groups(&periods)
+            .into_iter()
+            .map(|(row, members)| {
+                let keys: Vec<PointKey> = members.iter().map(|&n| point_key(&points[n])).collect();
+                let mut states = keys.iter().map(|key| self.review_status(key));
+                let first = states.next().unwrap_or(ReviewStatus::Proposed);
+                let status = match states.all(|status| status == first) {
+                    true => first,
+                    false => ReviewStatus::Proposed,
+                };
+                (row, status, keys)
+            })
+            .collect()
+    }
+
+    // The rows to write, and what was left out of them. Only the approved
+    // points make up the rows when asked to, and only those not covered by
+    // the periods already written to the file appended to.
+    fn rows_to_export(
+        &self,
+        written: &[ExclusionRow],
+    ) -> Result<(Vec<ExclusionRow>, Vec<String>), String> {
+        self.check_acknowledged()?;
+        let (mut points, skipped) = self.exclusion_points();
+        if self.export_approved_only {
+            points.retain(|point| self.review_status(&point_key(point)) == ReviewStatus::Approved);
+        }
+        let mut periods: Vec<ExclusionRow> = points.into_iter().map(|(_, period)| period).collect();
+        if !written.is_empty() {
+            let covered = covered(&periods, written);
+            let mut covered = covered.into_iter();
+            periods.retain(|_| !covered.next().unwrap_or(false));
+        }
+        let rows = merge_periods(periods);
+
+        // Refuse to write a file the downstream import would reject
+        let violations = validate_rows(&rows);
+        if !violations.is_empty() {
+            let heading = format!("{} schema violations", violations.len());
+            return Err(listed(heading, &violations));
+        }
+
+        Ok((rows, skipped))
+    }
+
+    // Message of a successful export, listing what was left out
+    fn exported_message(skipped: &[String]) -> String {
+        match skipped.len() {
+            0 => "Exclusions exported successfully".into(),
+            n => listed(
+                format!("Exclusions exported, {} problems left out", n),
+                skipped,
+            ),
+        }
+    }
+
+    // Writes the exclusions in the chosen export format. With `append` only
+    // the periods not in the file yet are added to it, its lines left as they
+    // were.
+    fn export_exclusions(&self, path: PathBuf, append: bool) -> Result<Vec<String>, String> {
+        if append && path.exists() {
+            return self.append_exclusions(&path);
+        }
+        let (rows, skipped) = self.rows_to_export(&[])?;
+        match self.export_format {
+            ExportFormat::Tsv => self.write_exclusions(&path, &rows),
+            ExportFormat::WindFarmer => fs::write(path, windfarmer::write_exclusions(&rows)),
+        }
+        .map_err(|e| format!("File write error: {}", e))?;
+        Ok(skipped)
+    }
+
+    fn append_exclusions(&self, path: &Path) -> Result<Vec<String>, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("File read error: {}", e))?;
+        let written = match self.export_format {
+            ExportFormat::Tsv => read_exclusions(&content)?,
+            ExportFormat::WindFarmer => windfarmer::read_exclusions(&content)?,
+        };
+        let (rows, skipped) = self.rows_to_export(&written)?;
+
+        let appended = match self.export_format {
+            ExportFormat::Tsv => {
+                // New rows follow the file's columns, which without a header
+                // row are the default ones
+                let header = content.lines().find(|line| !line.trim().is_empty());
+                let columns = header
+                    .and_then(header_columns)
+                    .unwrap_or_else(|| ExportLayout::default().written());
+                let mut text = Vec::new();
+                if !content.is_empty() && !content.ends_with('\n') {
+                    text.push(b'\n');
+                }
+                self.write_exclusion_table(&mut text, &rows, &columns, false)
+                    .map_err(|e| format!("File write error: {}", e))?;
+                content + &String::from_utf8_lossy(&text)
+            }
+            ExportFormat::WindFarmer => windfarmer::append_exclusions(&content, &rows)?,
+        };
+        fs::write(path, appended).map_err(|e| format!("File write error: {}", e))?;
+        Ok(skipped)
+    }
+
+    // Writes the exclusions to the auto export path once every interval, so a
+    // crash never costs more than that much work
+    fn auto_export(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.auto_export_path.clone() else {
+            return;
+        };
+        if !self.file_loaded {
+            return;
+        }
+
+        let interval = StdDuration::from_secs(60 * self.auto_export_minutes.max(1) as u64);
+        let elapsed = self.last_auto_export.elapsed();
+        if elapsed < interval {
+            ctx.request_repaint_after(interval - elapsed);
+            return;
+        }
+
+        self.last_auto_export = Instant::now();
+        let mut result = self
+            .timed("export", |app| app.export_exclusions(path.clone(), false))
+            .map(|_| ());
+        if self.auto_export_session && result.is_ok() {
+            // The session goes next to the exclusions, as <name>.project.json
+            result = self.save_project(&path.with_extension("project.json"));
+        }
+        if let Err(e) = result {
+            self.msg = format!("Auto export error: {}", e);
+            self.audit
+                .record(format!("Auto export {}: {}", path.display(), self.msg));
+        }
+        ctx.request_repaint_after(interval);
+    }
+
+    // The measured channels with their excluded values replaced, in the same
+    // tab separated layout as the loaded file, or as nulls in a Parquet file
+    fn export_cleaned_data(&mut self, path: PathBuf) -> Result<(), String> {
+        if self.partial_load {
+            return Err("The file was loaded sub-sampled or with some columns left out. Load it whole to export the cleaned data".into());
+        }
+        let measured: Vec<usize> = (0..self.timeseries.len())
+            .filter(|&n| !self.timeseries[n].derived())
+            .collect();
+        for &n in &measured {
+            self.materialize(n);
+        }
+        self.check_acknowledged()?;
+
+        let channels: Vec<&TimeSeries> = measured.iter().map(|&n| &self.timeseries[n]).collect();
+        let path_name = path.display().to_string();
+        if is_parquet(&path_name) {
+            return write_parquet(&path_name, &self.index_name, &self.index, &channels);
+        }
+        let write = || -> std::io::Result<()> {
+            write_cleaned(
+                &mut BufWriter::new(File::create(&path)?),
+                &self.index_name,
+                &self.index,
+                &channels,
+                &self.cleaned_replacement.text(first_marker(&self.missing)),
+                &first_marker(&self.missing).to_string(),
+            )
+        };
+
+        write().map_err(|e| format!("File write error: {}", e))
+    }
+
+    // Puts the exclusions as exported on the clipboard, with a header row for
+    // pasting into a spreadsheet
+    fn copy_exclusions(&mut self, ctx: &egui::Context) {
+        let result = self.rows_to_export(&[]).and_then(|(rows, _)| {
+            let mut text = Vec::new();
+            self.write_exclusion_table(&mut text, &rows, &self.export_layout.written(), true)
+                .map_err(|e| e.to_string())?;
+            Ok((rows.len(), String::from_utf8_lossy(&text).into_owned()))
+        });
+        match result {
+            Ok((count, text)) => {
+                ctx.output_mut(|output| output.copied_text = text);
+                self.msg = format!("{} exclusion periods copied to the clipboard", count);
+            }
+            Err(e) => self.msg = format!("Copy error: {}", e),
+        }
+    }
+
+    fn export_windfarmer(&self, path: PathBuf) -> Result<Vec<String>, String> {
+        let (rows, skipped) = self.rows_to_export(&[])?;
+        fs::write(path, windfarmer::write_exclusions(&rows))
+            .map_err(|e| format!("File write error: {}", e))?;
+        Ok(skipped)
+    }
+
+    fn import_windfarmer(&mut self, path: PathBuf) -> Result<(), String> {
+        self.import_exclusions(path, "XML import", windfarmer::read_exclusions)
+    }
+
+    // Re-applies the exclusions of a file written by the export, or by another
+    // format through `read`
+    fn import_exclusions(
+        &mut self,
+        path: PathBuf,
+        label: &str,
+        read: fn(&str) -> Result<Vec<ExclusionRow>, String>,
+    ) -> Result<(), String> {
+        if !self.file_loaded {
+            return Err("Load a data file first".into());
+        }
+
+        let content = fs::read_to_string(&path).map_err(|e| format!("File read error: {}", e))?;
+        let rows = read(&content)?;
+
+        self.take_snapshot(&format!("Before {}", label));
+        let (count, unmatched) = self.apply_exclusion_rows(&rows);
+        self.push_action(label, self.selection());
+        self.msg = format!("Imported {} rows, {} points excluded", rows.len(), count);
+        if unmatched > 0 {
+            self.msg += &format!("\n{} rows match no loaded channel", unmatched);
+        }
+        self.audit
+            .record(format!("Import {}: {}", path.display(), self.msg));
+
+        Ok(())
+    }
+
+    // Marks the points covered by each row as excluded with the row's reason.
+    // Returns the number of points marked and of rows matching no channel.
+    fn apply_exclusion_rows(&mut self, rows: &[ExclusionRow]) -> (usize, usize) {
+        let mut count = 0;
+        let mut unmatched = 0;
+        for row in rows {
+            let matching = self.row_channels(row);
+            if matching.is_empty() {
+                unmatched += 1;
+                continue;
+            }
+
+            let covered = covered_rows(&self.times, row);
+            for series in matching {
+                self.materialize(series);
+                count += self.exclude_timeseries_data(series, &covered, &row.reason);
+            }
+            self.register_reason(&row.reason);
+        }
+
+        (count, unmatched)
+    }
+
+    // Measured channels an imported row applies to. Every statistic of the
+    // sensor shares its mast and sensor names, so a row naming a statistic,
+    // as "WS80_Max", applies to that statistic only.
+    fn row_channels(&self, row: &ExclusionRow) -> Vec<usize> {
+        let convention = &self.settings.name_convention;
+        let statistic = convention.split_statistic(&row.sensor);
+        (0..self.timeseries.len())
+            .filter(|&n| {
+                let ts = &self.timeseries[n];
+                let Ok((mast, sensor)) = self.mast_sensor(&ts.name) else {
+                    return false;
+                };
+                let same_statistic = statistic.is_some_and(|(base, statistic)| {
+                    sensor == base
+                        && convention.split_statistic(&ts.name).map(|(_, s)| s) == Some(statistic)
+                });
+                !ts.derived() && mast == row.mast && (sensor == row.sensor || same_statistic)
+            })
+            .collect()
+    }
+
+    fn register_reason(&mut self, reason: &str) {
+        if !self.exclusion_names.iter().any(|name| name == reason) {
+            self.exclusion_names.push(reason.to_owned());
+        }
+
+        if !self.reason_colors.contains_key(reason) {
+            let rgb = self.theme.next_reason_color(&self.reason_colors);
+            self.reason_colors.insert(reason.to_owned(), rgb);
+        }
+    }
+
+    fn write_exclusions(&self, path: &Path, rows: &[ExclusionRow]) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let columns = self.export_layout.written();
+        self.write_exclusion_table(
+            &mut writer,
+            rows,
+            &columns,
+            self.export_layout.writes_header(),
+        )?;
+        writer.flush()
+    }
+
+    // The given columns, as chosen in the export options, in their order
+    fn write_exclusion_table(
+        &self,
+        writer: &mut impl Write,
+        rows: &[ExclusionRow],
+        columns: &[ExportColumn],
+        header: bool,
+    ) -> std::io::Result<()> {
+        if header {
+            let names: Vec<&str> = columns.iter().map(|column| column.label()).collect();
+            writeln!(writer, "{}", names.join("\t"))?;
+        }
+
+        let now = Local::now().naive_local();
+        let fmt = "%Y-%m-%d %H:%M:%S";
+        // Some imports reject periods written with more precision than the
+        // index. Periods stay in ISO order either way, as read back.
+        let seconds = self.time_format.unwrap_or(TIMESTAMP_FORMAT).contains("%S");
+        let period_fmt = match self.export_index_precision && !seconds {
+            true => TIMESTAMP_FORMAT,
+            false => fmt,
+        };
+        for ex in rows.iter() {
+            let fields: Vec<String> = columns
+                .iter()
+                .map(|column| match column {
+                    ExportColumn::Mast => ex.mast.clone(),
+                    ExportColumn::Sensor => ex.sensor.clone(),
+                    ExportColumn::Reason => ex.reason.clone(),
+                    ExportColumn::Start => ex.start.format(period_fmt).to_string(),
+                    ExportColumn::End => ex.end.format(period_fmt).to_string(),
+                    ExportColumn::Exported => now.format(fmt).to_string(),
+                    ExportColumn::Analyst => ex.analyst.clone(),
+                    ExportColumn::Category => category_label(ex.category).to_owned(),
+                    ExportColumn::Comment => self.export_comment.trim().to_owned(),
+                })
+                .collect();
+            writeln!(writer, "{}", fields.join("\t"))?;
+        }
+
+        Ok(())
+    }
+
+    // Columns of the tab separated export, their order and the header row
+    fn show_export_options_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_export_options;
+        egui::Window::new("Export options")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let columns = &mut self.export_layout.columns;
+                let mut swap = None;
+                egui::Grid::new("export_columns")
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        let last = columns.len() - 1;
+                        for (n, (column, written)) in columns.iter_mut().enumerate() {
+                            ui.checkbox(written, column.label());
+                            if ui.add_enabled(n > 0, Button::new("⏶").small()).clicked() {
+                                swap = Some(n - 1);
+                            }
+                            if ui.add_enabled(n < last, Button::new("⏷").small()).clicked() {
+                                swap = Some(n);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(n) = swap {
+                    columns.swap(n, n + 1);
+                }
+                ui.separator();
+
+                // Only the default columns are read back without it
+                let mut header = self.export_layout.writes_header();
+                let forced = header && !self.export_layout.header;
+                let checkbox = ui
+                    .add_enabled(!forced, egui::Checkbox::new(&mut header, "Header row"))
+                    .on_hover_text(
+                        "Files with a header row are read back whatever the order of their columns",
+                    )
+                    .on_disabled_hover_text(
+                        "Written as the columns differ from the default ones, which are the only ones read back without it",
+                    );
+                if checkbox.changed() {
+                    self.export_layout.header = header;
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Comment");
+                    ui.text_edit_singleline(&mut self.export_comment)
+                        .on_hover_text("Written in the Comment column of every period");
+                });
+                if ui.button("Defaults").clicked() {
+                    self.export_layout = ExportLayout::default();
+                }
+            });
+        self.show_export_options = open;
+    }
+
+    fn rule_channels(&self) -> Vec<usize> {
+        match self.rule_scope {
+            RuleScope::Plotted => {
+                let mut series = vec![self.yaxis];
+                series.extend(self.plotted_extra_yaxes());
+                series
+            }
+            RuleScope::All => (0..self.timeseries.len())
+                .filter(|&n| !self.timeseries[n].derived())
+                .collect(),
+            RuleScope::SensorType => (0..self.timeseries.len())
+                .filter(|&n| {
+                    let ts = &self.timeseries[n];
+                    !ts.derived()
+                        && self.channel_type(&ts.name).as_ref() == Some(&self.rule_sensor_type)
+                })
+                .collect(),
+        }
+    }
+
+    fn series_values(&self, series: usize) -> Vec<f64> {
+        self.timeseries[series].values()
+    }
+
+    // Counted again only when the data or the grouping changed
+    fn coverage_table(&mut self) -> Rc<[Coverage]> {
+        let key = (self.revision, self.coverage_monthly);
+        if self.coverage_cache.as_ref().map(|(k, _)| *k) != Some(key) {
+            for n in 0..self.timeseries.len() {
+                self.materialize(n);
+            }
+            let table = coverage_table(&self.timeseries, &self.times, self.coverage_monthly).into();
+            // Parsing the channels above changed the revision
+            self.coverage_cache = Some(((self.revision, self.coverage_monthly), table));
+        }
+        self.coverage_cache.as_ref().unwrap().1.clone()
+    }
+
+    // Rows where both channels of each pair are valid, thinned out to a few
+    // points per plot. Taken again only when the data or the channels changed.
+    fn matrix_cells(&mut self) -> Rc<[MatrixCell]> {
+        let key = (self.revision, self.matrix_channels.clone());
+        if self.matrix_cache.as_ref().map(|(k, _)| k) != Some(&key) {
+            for &n in &key.1 {
+                self.materialize(n);
+            }
+            let step = self.index.len().div_ceil(MAX_MATRIX_POINTS).max(1);
+            let cells = matrix_cells(&self.timeseries, &key.1, step).into();
+            // Parsing the channels above changed the revision
+            self.matrix_cache = Some(((self.revision, key.1), cells));
+        }
+        self.matrix_cache.as_ref().unwrap().1.clone()
+    }
+
+    fn show_matrix_window(&mut self, ctx: &egui::Context) {
+        let names: Vec<String> = self.timeseries.iter().map(|ts| ts.name.clone()).collect();
+        self.matrix_channels.retain(|&n| n < names.len());
+        let cells = self.matrix_cells();
+        let channels = self.matrix_channels.clone();
+
+        let mut open = self.show_matrix;
+        let mut picked = None;
+        egui::Window::new("Scatter matrix")
+            .open(&mut open)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.menu_button(format!("{} channels", channels.len()), |ui| {
+                        egui::ScrollArea::vertical()
+                            .max_height(400.0)
+                            .show(ui, |ui| {
+                                for (n, name) in names.iter().enumerate() {
+                                    let mut selected = self.matrix_channels.contains(&n);
+                                    let enabled = selected
+                                        || self.matrix_channels.len() < MAX_MATRIX_CHANNELS;
+                                    if ui
+                                        .add_enabled(
+                                            enabled,
+                                            egui::Checkbox::new(&mut selected, name),
+                                        )
+                                        .changed()
+                                    {
+                                        if selected {
+                                            self.matrix_channels.push(n);
+                                        } else {
+                                            self.matrix_channels.retain(|&c| c != n);
+                                        }
+                                    }
+                                }
+                            });
+                    })
+                    .response
+                    .on_hover_text(format!(
+                        "Up to {} channels, compared in pairs",
+                        MAX_MATRIX_CHANNELS
+                    ));
+                    ui.label("Click a plot to open its pair in the main view");
+                });
+                ui.separator();
+                if channels.len() < 2 {
+                    ui.label("Pick at least two channels");
+                    return;
+                }
+
+                let theme = &self.theme;
+                ui.visuals_mut().extreme_bg_color = color(theme.background);
+                egui::ScrollArea::both().show(ui, |ui| {
+                    egui::Grid::new("matrix_grid")
+                        .spacing([4.0, 4.0])
+                        .num_columns(channels.len())
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for &x in &channels[..channels.len() - 1] {
+                                ui.small(&names[x]);
+                            }
+                            ui.end_row();
+
+                            // Row n holds the pairs of its channel with the n before it
+                            let mut cells = cells.iter();
+                            for (row, y) in channels.iter().enumerate().skip(1) {
+                                ui.small(&names[*y]);
+                                for ((x, y), points) in cells.by_ref().take(row) {
+                                    let response = Plot::new(("matrix_plot", x, y))
+                                        .width(MATRIX_CELL_SIZE)
+                                        .height(MATRIX_CELL_SIZE)
+                                        .show_axes([false, false])
+                                        .show_x(false)
+                                        .show_y(false)
+                                        .allow_drag(false)
+                                        .allow_zoom(false)
+                                        .allow_scroll(false)
+                                        .allow_boxed_zoom(false)
+                                        .allow_double_click_reset(false)
+                                        .show(ui, |plot_ui| {
+                                            plot_ui.points(
+                                                Points::new(points.clone())
+                                                    .radius(1.0)
+                                                    .color(color(theme.valid)),
+                                            );
+                                        })
+                                        .response;
+                                    let response = response.on_hover_text(format!(
+                                        "{} against {}, {} points shown",
+                                        names[*y],
+                                        names[*x],
+                                        points.len()
+                                    ));
+                                    if response.clicked() {
+                                        picked = Some((*x, *y));
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        self.show_matrix = open;
+
+        if let Some((x, y)) = picked {
+            self.plot_mode = PlotMode::Scatter;
+            self.xaxis = x;
+            self.yaxis = y;
+            self.msg = format!("Showing {} against {}", names[y], names[x]);
+        }
+    }
+
+    // Heatmap of the share of valid records, a row per channel and a column per period
+    fn show_coverage_window(&mut self, ctx: &egui::Context) {
+        let table = self.coverage_table();
+        let periods: BTreeSet<NaiveDate> = table
+            .iter()
+            .flat_map(|(_, counts)| counts.keys().copied())
+            .collect();
+        let label_format = if self.coverage_monthly {
+            "%b %y"
+        } else {
+            "%d %b"
+        };
+        let cell = egui::vec2(if self.coverage_monthly { 40.0 } else { 22.0 }, 16.0);
+
+        let mut open = self.show_coverage;
+        egui::Window::new("Coverage")
+            .open(&mut open)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.coverage_monthly, true, "Monthly");
+                    ui.radio_value(&mut self.coverage_monthly, false, "Daily");
+                    ui.label("Valid records over records in the file, excluded and missing values not counted");
+                });
+                ui.separator();
+                egui::ScrollArea::both().show(ui, |ui| {
+                    egui::Grid::new("coverage_grid")
+                        .spacing([2.0, 2.0])
+                        .num_columns(periods.len() + 1)
+                        .show(ui, |ui| {
+                            ui.label("");
+                            for period in &periods {
+                                ui.small(period.format(label_format).to_string());
+                            }
+                            ui.end_row();
+
+                            for (series, counts) in table.iter() {
+                                ui.label(self.channel_label(*series));
+                                for period in &periods {
+                                    let (rect, response) = ui.allocate_exact_size(cell, egui::Sense::hover());
+                                    let Some(&(valid, total)) = counts.get(period) else {
+                                        continue;
+                                    };
+                                    let share = valid as f64 / total.max(1) as f64;
+                                    let fill = color(self.theme.gradient_color(share));
+                                    ui.painter().rect_filled(rect, 2.0, fill);
+                                    response.on_hover_text(format!(
+                                        "{}: {:.1}% ({} of {} records)",
+                                        period.format(label_format),
+                                        100.0 * share,
+                                        valid,
+                                        total
+                                    ));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+            });
+        self.show_coverage = open;
+    }
+
+    fn export_gaps(path: &Path, gaps: &[Gap]) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("File write error: {}", e))?;
+        write_gaps(&mut BufWriter::new(file), gaps).map_err(|e| format!("File write error: {}", e))
+    }
+
+    // Gaps at the cadence set, or else at the detected one. Found again only
+    // when the data or the cadence changed.
+    fn index_gaps(&mut self) -> IndexGaps {
+        let key = (self.revision, self.gap_cadence);
+        if let Some((_, cached)) = self.gaps_cache.as_ref().filter(|(k, _)| *k == key) {
+            return cached.clone();
+        }
+        let detected = cadence(&self.times);
+        let step = match self.gap_cadence {
+            minutes if minutes > 0.0 => Some(60.0 * minutes),
+            _ => detected,
+        };
+        let found: Rc<[Gap]> = step
+            .map_or_else(Vec::new, |step| gaps(&self.times, step))
+            .into();
+        self.gaps_cache = Some((key, (detected, step, found.clone())));
+        (detected, step, found)
+    }
+
+    // Breaks in the index at the expected cadence, which matter for the
+    // recovery rate as much as the excluded values
+    fn show_gaps_window(&mut self, ctx: &egui::Context) {
+        let (detected, step, found) = self.index_gaps();
+        let missing: usize = found.iter().map(|gap| gap.missing).sum();
+        let irregular = found.iter().filter(|gap| gap.irregular).count();
+
+        let mut open = self.show_gaps;
+        let mut export = false;
+        egui::Window::new("Timestamp gaps")
+            .open(&mut open)
+            .default_width(450.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Cadence (min)");
+                    ui.add(
+                        DragValue::new(&mut self.gap_cadence)
+                            .clamp_range(0.0..=1440.0)
+                            .speed(1.0),
+                    )
+                    .on_hover_text("0 to use the detected cadence");
+                    if let Some(detected) = detected {
+                        ui.label(format!("detected {} min", detected / 60.0));
+                    }
+                });
+                let Some(step) = step else {
+                    ui.label("The index has no readable timestamps");
+                    return;
+                };
+                let expected = self.times.len() + missing;
+                ui.label(format!(
+                    "{} gaps with {} missing records, {} irregular steps. The file has {:.1}% of the records expected every {} min.",
+                    found.len() - irregular,
+                    missing,
+                    irregular,
+                    100.0 * self.times.len() as f64 / expected.max(1) as f64,
+                    step / 60.0
+                ));
+                export = ui
+                    .add_enabled(!found.is_empty(), Button::new("Export gaps"))
+                    .clicked();
+                ui.separator();
+
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                egui::ScrollArea::vertical().max_height(400.0).show_rows(
+                    ui,
+                    row_height,
+                    found.len(),
+                    |ui, rows| {
+                        egui::Grid::new("gaps_grid")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for gap in &found[rows] {
+                                    ui.label(format_time(gap.start));
+                                    ui.label(format_time(gap.end));
+                                    ui.label(format!("{} missing", gap.missing));
+                                    if gap.irregular {
+                                        ui.label("Irregular");
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    },
+                );
+            });
+        self.show_gaps = open;
+
+        if export {
+            if let Some(path) = self
+                .file_dialog()
+                .add_filter("Text", &["tsv", "txt"])
+                .save_file()
+            {
+                self.msg = match Self::export_gaps(&path, &found) {
+                    Ok(()) => format!("{} gaps exported to {}", found.len(), path.display()),
+                    Err(e) => format!("Export error: {}", e),
+                };
+            }
+        }
+    }
+
+    // The main plot as shown, drawn again for a vector image
+    fn plot_drawing(&mut self) -> Option<PlotDrawing> {
+        let ((min, max), _) = self
+            .view
+            .filter(|(_, shown)| *shown == (self.xaxis, self.yaxis, self.plot_mode))?;
+        let time_mode = self.plot_mode == PlotMode::TimeSeries;
+        let theme = self.theme.clone();
+        let rgb = |c: egui::Color32| [c.r(), c.g(), c.b()];
+        let mut layers = Vec::new();
+
+        let mut yaxes = vec![(self.yaxis, theme.valid)];
+        yaxes.extend(
+            self.plotted_extra_yaxes()
+                .into_iter()
+                .enumerate()
+                .map(|(n, yaxis)| (yaxis, rgb(theme.series_color(n)))),
+        );
+        for (yaxis, color) in yaxes {
+            let name = format!("{} (valid)", self.timeseries[yaxis].name);
+            if time_mode {
+                let segments = self.cached_time_segments(yaxis);
+                for segment in self.decimated_lines(&segments) {
+                    layers.push(Layer {
+                        name: String::new(),
+                        color,
+                        mark: Mark::Line(1.0),
+                        points: segment,
+                    });
+                }
+            }
+            let points = self.cached_valid_points(yaxis);
+            let points = self.decimated(&points);
+            layers.push(Layer {
+                name,
+                color,
+                mark: Mark::Points(theme.point_radius),
+                points,
+            });
+            if self.show_excluded {
+                let reasons = self.exclusion_names.clone();
+                for (reason, (color, points)) in
+                    reasons.iter().zip(self.cached_excluded_points(yaxis))
+                {
+                    let points = self.decimated(&points);
+                    layers.push(Layer {
+                        name: format!("Excluded: {}", reason),
+                        color,
+                        mark: Mark::Points(theme.point_radius),
+                        points,
+                    });
+                }
+            }
+        }
+
+        if !self.exclusion_curve.is_empty() {
+            let color = if self.exclusion_curve_is_closed {
+                theme.selection_closed
+            } else {
+                theme.selection_open
+            };
+            for mark in [
+                Mark::Line(theme.line_width),
+                Mark::Points(theme.vertex_radius),
+            ] {
+                layers.push(Layer {
+                    name: "Selection".to_owned(),
+                    color,
+                    mark,
+                    points: self.exclusion_curve.clone(),
+                });
+            }
+        }
+
+        Some(PlotDrawing {
+            min,
+            max,
+            x_label: match time_mode {
+                true => self.index_name.clone(),
+                false => self.axis_label(self.xaxis),
+            },
+            y_label: self.axis_label(self.yaxis),
+            time_axis: time_mode,
+            background: theme.background,
+            layers,
+        })
+    }
+
+    // Saves the main plot as SVG, or as PNG from a screenshot of the next frame
+    fn save_plot_image(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let mut dialog = self.file_dialog().set_file_name("plot.png");
+        for format in ImageFormat::ALL {
+            dialog = dialog.add_filter(format.label(), &[format.extension()]);
+        }
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+
+        match ImageFormat::of(&path) {
+            ImageFormat::Svg => {
+                let Some(drawing) = self.plot_drawing() else {
+                    self.msg = "Nothing plotted to save".to_owned();
+                    return;
+                };
+                self.msg = match write_svg(&path, &drawing) {
+                    Ok(()) => format!("Plot saved to {}", path.display()),
+                    Err(e) => format!("Image error: {}", e),
+                };
+            }
+            ImageFormat::Png => {
+                let Some(rect) = self.plot_rect else {
+                    self.msg = "Nothing plotted to save".to_owned();
+                    return;
+                };
+                let pixels = |pos: egui::Pos2| {
+                    [pos.x, pos.y].map(|v| (v * ctx.pixels_per_point()).round() as usize)
+                };
+                self.pending_plot_image = Some((path, pixels(rect.min), pixels(rect.max)));
+                frame.request_screenshot();
+            }
+        }
+    }
+
+    fn plot_caption(&self) -> String {
+        let y = &self.timeseries[self.yaxis].name;
+        match self.plot_mode {
+            PlotMode::TimeSeries => format!("{} over time", y),
+            PlotMode::Scatter => format!("{} against {}", y, self.timeseries[self.xaxis].name),
+        }
+    }
+
+    fn add_plot_to_report(&mut self) {
+        let Some(drawing) = self.plot_drawing() else {
+            self.msg = "Nothing plotted to add".to_owned();
+            return;
+        };
+        self.report_plots.push((self.plot_caption(), drawing.svg()));
+        self.msg = format!("{} plots in the report", self.report_plots.len());
+    }
+
+    // Recovery of every channel read from the files of the session, the
+    // exclusions and the plots added, or the one shown if none was
+    fn generate_report(&mut self, path: &Path) -> Result<(), String> {
+        let mut plots = self.report_plots.clone();
+        if plots.is_empty() {
+            plots.extend(
+                self.plot_drawing()
+                    .map(|drawing| (self.plot_caption(), drawing.svg())),
+            );
+        }
+
+        let current = (
+            self.file_path.as_str(),
+            self.index.as_slice(),
+            self.timeseries.as_slice(),
+            self.raw.as_ref(),
+        );
+        let others = self.other_files.iter().map(|file| {
+            (
+                file.file_path.as_str(),
+                file.index.as_slice(),
+                file.timeseries.as_slice(),
+                file.raw.as_ref(),
+            )
+        });
+        let mut files = Vec::new();
+        let mut channels = Vec::new();
+        for (file, index, timeseries, raw) in std::iter::once(current).chain(others) {
+            let period = index.first().zip(index.last());
+            files.push((
+                file.to_owned(),
+                period.map(|(first, last)| (first.clone(), last.clone())),
+            ));
+            channels.extend(
+                timeseries
+                    .iter()
+                    .filter(|ts| !ts.derived())
+                    .map(|ts| channel_recovery(file, ts, raw, &self.missing)),
+            );
+        }
+
+        let (exclusions, skipped) = self.exclusion_periods();
+        let report = Report {
+            files,
+            analyst: self.analyst.trim().to_owned(),
+            generated: Local::now().naive_local(),
+            channels,
+            exclusions,
+            skipped,
+            plots,
+        };
+        report.write(path)
+    }
+
+    fn show_stats_window(&mut self, ctx: &egui::Context) {
+        let mut channels = vec![self.yaxis];
+        if self.plot_mode == PlotMode::Scatter {
+            channels.insert(0, self.xaxis);
+        }
+        channels.extend(self.plotted_extra_yaxes());
+        channels.dedup();
+        let stats = self.channel_stats(channels);
+
+        let mut open = self.show_stats;
+        egui::Window::new("Statistics")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("stats_grid")
+                    .striped(true)
+                    .num_columns(9)
+                    .show(ui, |ui| {
+                        for header in [
+                            "Channel", "Valid", "Min", "Max", "Mean", "Std", "Missing", "Excluded",
+                            "Recovery",
+                        ] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+
+                        for &(series, ref stats, missing, excluded, rows) in stats.iter() {
+                            ui.label(self.channel_label(series));
+                            ui.label(stats.count.to_string());
+                            for value in [stats.min, stats.max, stats.mean, stats.std] {
+                                ui.label(format!("{:.3}", value));
+                            }
+                            ui.label(missing.to_string());
+                            ui.label(excluded.to_string());
+                            ui.label(match rows {
+                                0 => "-".to_owned(),
+                                len => format!("{:.1}%", 100.0 * stats.count as f64 / len as f64),
+                            });
+                            ui.end_row();
+                        }
+                    });
+            });
+        self.show_stats = open;
+    }
+
+    // Worked out again only when the data or the channels changed
+    fn channel_stats(&mut self, channels: Vec<usize>) -> Rc<[ChannelStats]> {
+        let key = (channels, self.revision);
+        if let Some((_, stats)) = self.stats_cache.as_ref().filter(|(k, _)| *k == key) {
+            return stats.clone();
+        }
+        let stats: Rc<[ChannelStats]> = key
+            .0
+            .iter()
+            .map(|&series| {
+                let ts = &self.timeseries[series];
+                let (missing, excluded) = ts.counts();
+                (
+                    series,
+                    summary(&ts.values()),
+                    missing,
+                    excluded,
+                    ts.data.len(),
+                )
+            })
+            .collect();
+        self.stats_cache = Some((key, stats.clone()));
+        stats
+    }
+
+    // Rolling mean and ±k·σ band lines of a channel over time, and the points
+    // outside the band
+    fn rolling_overlay(&self, yaxis: usize) -> (Vec<Vec<[f64; 2]>>, Vec<[f64; 2]>) {
+        let values = self.series_values(yaxis);
+        let stats = rolling_mean_std(&values, self.rolling_window);
+
+        let mut lines = Vec::new();
+        for k in [0.0, self.rolling_k, -self.rolling_k] {
+            let points: Vec<Option<[f64; 2]>> = izip!(&self.times, &stats)
+                .map(|(t, (mean, std))| {
+                    let y = mean + k * std;
+                    (t.is_finite() && y.is_finite()).then_some([*t, y])
+                })
+                .collect();
+            lines.extend(line_segments(&points, self.time_step));
+        }
+
+        let outside = izip!(
+            &self.times,
+            &values,
+            out_of_band(&values, &stats, self.rolling_k)
+        )
+        .filter(|(t, _, outside)| *outside && t.is_finite())
+        .map(|(t, v, _)| [*t, *v])
+        .collect();
+
+        (lines, outside)
+    }
+
+    // Excludes the values outside the plausible range of each measured
+    // channel's sensor type
+    fn apply_plausible_ranges(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        let reason = self.range_reason.trim().to_owned();
+        if reason.is_empty() {
+            self.msg = "Write a reason for the range exclusions".to_owned();
+            return;
+        }
+
+        let selection = self.selection();
+        let mut count = 0;
+        for series in 0..self.timeseries.len() {
+            let ts = &self.timeseries[series];
+            if ts.derived() {
+                continue;
+            }
+            let Some(range) = self
+                .channel_type(&ts.name)
+                .and_then(|kind| self.settings.plausible_range(&kind).cloned())
+            else {
+                continue;
+            };
+            self.materialize(series);
+            let rule = Rule::Range {
+                min: range.min,
+                max: range.max,
+            };
+            let flags = rule.evaluate(&self.series_values(series), &GroupStats::default());
+            let excluded = self.exclude_timeseries_data(series, &flags, &reason);
+            if excluded > 0 {
+                self.audit.record(format!(
+                    "Excluded {} points of {} outside [{}, {}]",
+                    excluded, self.timeseries[series].name, range.min, range.max
+                ));
+            }
+            count += excluded;
+        }
+
+        if count > 0 {
+            self.register_reason(&reason);
+            self.record_operation("Plausible ranges", selection);
+        }
+        self.msg = format!("{} points outside the plausible ranges excluded", count);
+    }
+
+    // Points of a channel in the periods detected by the rules, still to review
+    fn detected_points(&mut self, series: usize) -> Rc<[[f64; 2]]> {
+        self.check_points_cache();
+        let periods: Vec<Range<usize>> = self
+            .detections
+            .iter()
+            .filter(|d| d.series == series)
+            .map(|d| d.rows.clone())
+            .collect();
+        if let Some((cached, points)) = self.points_cache.detected.get(&series) {
+            if *cached == periods {
+                return Rc::clone(points);
+            }
+        }
+        let points: Rc<[[f64; 2]]> = match periods.is_empty() {
+            true => Rc::from([]),
+            false => {
+                let points = self.convert_points(series);
+                periods
+                    .iter()
+                    .flat_map(|rows| points[rows.clone()].iter().copied())
+                    .filter(|p| p[0].is_finite())
+                    .collect()
+            }
+        };
+        self.points_cache
+            .detected
+            .insert(series, (periods, Rc::clone(&points)));
+        points
+    }
+
+    // Proposes the points outside the rolling band for exclusion
+    fn flag_out_of_band(&mut self) {
+        let values = self.series_values(self.yaxis);
+        let stats = rolling_mean_std(&values, self.rolling_window);
+        let flags = out_of_band(&values, &stats, self.rolling_k);
+        let runs = flagged_runs(&flags);
+
+        self.msg = format!("{} periods outside the rolling band", runs.len());
+        self.detections
+            .extend(runs.into_iter().map(|rows| Detection {
+                series: self.yaxis,
+                rows,
+                reason: "rolling band".to_owned(),
+            }));
+        self.show_review = true;
+    }
+
+    // Proposes the spikes of the y axis channel, the values jumping away from
+    // the valid values before and after them, for exclusion
+    fn flag_spikes(&mut self) {
+        let rule = Rule::Spike {
+            max_step: self.spike_delta,
+        };
+        let flags = rule.evaluate(&self.series_values(self.yaxis), &GroupStats::default());
+        let runs = flagged_runs(&flags);
+
+        self.msg = format!(
+            "{} spikes over {} in {}",
+            runs.len(),
+            self.spike_delta,
+            self.timeseries[self.yaxis].name
+        );
+        self.detections
+            .extend(runs.into_iter().map(|rows| Detection {
+                series: self.yaxis,
+                rows,
+                reason: "spike".to_owned(),
+            }));
+        self.show_review = true;
+    }
+
+    // Opens the icing check with a temperature and a wind speed standard
+    // deviation channel picked, unless already chosen
+    fn open_icing_window(&mut self) {
+        let find = |app: &Self, kind: &str, statistic: Option<Statistic>| {
+            (0..app.timeseries.len()).find(|&n| {
+                let name = &app.timeseries[n].name;
+                app.channel_type(name).as_deref() == Some(kind)
+                    && statistic.map_or(true, |s| {
+                        app.settings
+                            .name_convention
+                            .split_statistic(name)
+                            .map(|(_, st)| st)
+                            == Some(s)
+                    })
+            })
+        };
+        let (temperature, std) = self.icing_channels;
+        self.icing_channels = (
+            temperature.or_else(|| find(self, "T", None)),
+            std.or_else(|| find(self, "WS", Some(Statistic::Std))),
+        );
+        self.show_icing = true;
+    }
+
+    fn show_icing_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_icing;
+        let mut flag = false;
+        let options: Vec<String> = (0..self.timeseries.len())
+            .map(|n| self.channel_label(n))
+            .collect();
+        egui::Window::new("Icing detection")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("icing_grid").num_columns(2).show(ui, |ui| {
+                    let (temperature, std) = &mut self.icing_channels;
+                    for (label, channel) in [("Temperature", temperature), ("Wind speed std", std)] {
+                        ui.label(label);
+                        ComboBox::new(("icing channel", label), "")
+                            .selected_text(channel.map_or("None", |n| options[n].as_str()))
+                            .show_ui(ui, |ui| {
+                                for (n, option) in options.iter().enumerate() {
+                                    ui.selectable_value(channel, Some(n), option);
+                                }
+                            });
+                        ui.end_row();
+                    }
+
+                    ui.label("Temperature below");
+                    ui.add(DragValue::new(&mut self.icing_max_temperature).speed(0.1).suffix(" °C"));
+                    ui.end_row();
+                    ui.label("Std at most");
+                    ui.add(DragValue::new(&mut self.icing_max_std).clamp_range(0.0..=f64::MAX).speed(0.01));
+                    ui.end_row();
+                });
+                let ready = matches!(self.icing_channels, (Some(_), Some(_)));
+                flag = ui
+                    .add_enabled(ready, Button::new("Flag icing"))
+                    .on_hover_text("Circle the likely icing periods on the plot, to exclude from the review window")
+                    .clicked();
+            });
+
+        if flag {
+            self.flag_icing();
+        }
+        self.show_icing = open;
+    }
+
+    // Proposes the periods of likely icing for exclusion, on the anemometer
+    fn flag_icing(&mut self) {
+        let (Some(temperature), Some(std)) = self.icing_channels else {
+            return;
+        };
+        self.materialize(temperature);
+        self.materialize(std);
+        let flags = icing_flags(
+            &self.series_values(temperature),
+            &self.series_values(std),
+            self.icing_max_temperature,
+            self.icing_max_std,
+        );
+        let runs = flagged_runs(&flags);
+
+        self.msg = format!("{} periods of likely icing", runs.len());
+        self.detections
+            .extend(runs.into_iter().map(|rows| Detection {
+                series: std,
+                rows,
+                reason: "icing".to_owned(),
+            }));
+        self.show_review = true;
+    }
+
+    fn show_sectors_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_sectors;
+        let mut apply = false;
+        let options: Vec<String> = (0..self.timeseries.len())
+            .map(|n| self.channel_label(n))
+            .collect();
+        let directions: Vec<usize> = (0..self.timeseries.len())
+            .filter(|&n| self.is_direction_series(n))
+            .collect();
+
+        let (direction, anemometer) = self.sector_channels;
+        let shares = direction.map(|direction| self.rose_shares(direction, anemometer));
+        let chosen = parse_sectors(&self.sectors);
+        let mut clicked = None;
+
+        egui::Window::new("Direction sectors")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("sectors_grid").num_columns(2).show(ui, |ui| {
+                    let (direction, anemometer) = &mut self.sector_channels;
+                    ui.label("Direction");
+                    ComboBox::new("sector direction", "")
+                        .selected_text(direction.map_or("None", |n| options[n].as_str()))
+                        .show_ui(ui, |ui| {
+                            for &n in &directions {
+                                ui.selectable_value(direction, Some(n), &options[n]);
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Anemometer");
+                    ComboBox::new("sector anemometer", "")
+                        .selected_text(anemometer.map_or("None", |n| options[n].as_str()))
+                        .show_ui(ui, |ui| {
+                            for (n, option) in options.iter().enumerate() {
+                                ui.selectable_value(anemometer, Some(n), option);
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Sectors");
+                    ui.add(TextEdit::singleline(&mut self.sectors).hint_text("140-180, 350-20"));
+                    ui.end_row();
+
+                    ui.label("Reason");
+                    ui.add(TextEdit::singleline(&mut self.sector_reason));
+                    ui.end_row();
+                });
+                let ready = matches!(self.sector_channels, (Some(_), Some(_)));
+                apply = ui
+                    .add_enabled(ready && !self.read_only, Button::new("Exclude"))
+                    .on_hover_text("Exclude the anemometer whenever the direction is within a sector, clockwise from start to end")
+                    .clicked();
+
+                if let Some(shares) = &shares {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Wind rose");
+                        ComboBox::new("rose sectors", "sectors")
+                            .selected_text(self.rose_sectors.to_string())
+                            .show_ui(ui, |ui| {
+                                for n in [8, 12, 16, 36] {
+                                    ui.selectable_value(&mut self.rose_sectors, n, n.to_string());
+                                }
+                            });
+                        ui.add_enabled(
+                            anemometer.is_some(),
+                            egui::Checkbox::new(&mut self.rose_weighted, "Weighted by the anemometer"),
+                        )
+                        .on_hover_text("Share of the wind speed sum in each sector, rather than of the records");
+                    });
+                    let chosen = chosen.as_deref().unwrap_or_default();
+                    clicked = self.draw_rose(ui, shares, chosen);
+                    ui.label("Click a sector of the rose to add it to the sectors, or to remove it");
+                }
+            });
+
+        if let Some(n) = clicked {
+            match chosen {
+                Ok(mut chosen) => {
+                    let sector = rose_sector(n, self.rose_sectors);
+                    match chosen.iter().position(|&s| s == sector) {
+                        Some(i) => {
+                            chosen.remove(i);
+                        }
+                        None => chosen.push(sector),
+                    }
+                    self.sectors = format_sectors(&chosen);
+                }
+                Err(e) => self.msg = format!("Sector error: {}", e),
+            }
+        }
+
+        if apply {
+            if let Err(e) = self.exclude_sectors() {
+                self.msg = format!("Sector error: {}", e);
+            }
+        }
+        self.show_sectors = open;
+    }
+
+    // Worked out again only when the data, the channels or the sectors changed
+    fn rose_shares(&mut self, direction: usize, anemometer: Option<usize>) -> Rc<[f64]> {
+        let weighting = anemometer.filter(|_| self.rose_weighted);
+        for n in std::iter::once(direction).chain(weighting) {
+            self.materialize(n);
+        }
+        let key = (direction, weighting, self.rose_sectors, self.revision);
+        if let Some((_, shares)) = self.rose_cache.as_ref().filter(|(k, _)| *k == key) {
+            return shares.clone();
+        }
+        let weights = weighting.map(|n| self.series_values(n));
+        let shares: Rc<[f64]> = rose(
+            &self.series_values(direction),
+            weights.as_deref(),
+            self.rose_sectors,
+        )
+        .into();
+        self.rose_cache = Some((key, shares.clone()));
+        shares
+    }
+
+    // Draws the share of each sector of the rose, north up, the sectors within
+    // the chosen ones in the excluded color. Returns the sector clicked.
+    fn draw_rose(&self, ui: &mut egui::Ui, shares: &[f64], chosen: &[(f64, f64)]) -> Option<usize> {
+        let size = 280.0;
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::click());
+        let painter = ui.painter_at(rect);
+        let center = rect.center();
+        let radius = size / 2.0 - 16.0;
+        let at = |degrees: f64, r: f32| {
+            let angle = degrees.to_radians() as f32;
+            center + egui::vec2(r * angle.sin(), -r * angle.cos())
+        };
+
+        painter.rect_filled(rect, 4.0, color(self.theme.background));
+        let grid = ui.visuals().widgets.noninteractive.bg_stroke;
+        for ring in 1..=4 {
+            painter.circle_stroke(center, radius * ring as f32 / 4.0, grid);
+        }
+
+        // The longest wedge reaches the outer ring
+        let largest = shares.iter().copied().fold(0.0, f64::max);
+        let width = 360.0 / shares.len() as f64;
+        for (n, share) in shares.iter().enumerate() {
+            if largest <= 0.0 || *share <= 0.0 {
+                continue;
+            }
+            let r = radius * (share / largest) as f32;
+            let middle = n as f64 * width;
+            let mut wedge = vec![center];
+            wedge.extend(
+                (0..=8).map(|step| at(middle - width / 2.0 + width * step as f64 / 8.0, r)),
+            );
+            let fill = match chosen.iter().any(|&sector| in_sector(middle, sector)) {
+                true => self.theme.excluded,
+                false => self.theme.valid,
+            };
+            painter.add(egui::Shape::convex_polygon(wedge, color(fill), grid));
+        }
+
+        for (degrees, label) in [(0.0, "N"), (90.0, "E"), (180.0, "S"), (270.0, "W")] {
+            painter.text(
+                at(degrees, radius + 8.0),
+                egui::Align2::CENTER_CENTER,
+                label,
+                egui::FontId::default(),
+                ui.visuals().text_color(),
+            );
+        }
+
+        let hover = response.hover_pos().map(|pos| {
+            let offset = pos - center;
+            let direction = offset.x.atan2(-offset.y).to_degrees() as f64;
+            rose_index(direction, shares.len())
+        });
+        let response = match hover {
+            Some(n) => {
+                let (start, end) = rose_sector(n, shares.len());
+                response.on_hover_text(format!("{}° to {}°: {:.1}%", start, end, 100.0 * shares[n]))
+            }
+            None => response,
+        };
+        hover.filter(|_| response.clicked())
+    }
+
+    // Excludes the anemometer, and its sensor group, at the rows where the
+    // direction lies within any of the sectors
+    fn exclude_sectors(&mut self) -> Result<(), String> {
+        let (Some(direction), Some(anemometer)) = self.sector_channels else {
+            return Err("Pick a direction channel and an anemometer".into());
+        };
+        let sectors = parse_sectors(&self.sectors)?;
+        let reason = self.sector_reason.trim().to_owned();
+        if sectors.is_empty() || reason.is_empty() {
+            return Err("Write the sectors and a reason".into());
+        }
+        if !self.check_editable() {
+            return Ok(());
+        }
+
+        self.materialize(direction);
+        let rows: Vec<bool> = self
+            .series_values(direction)
+            .into_iter()
+            .map(|d| sectors.iter().any(|&sector| in_sector(d, sector)))
+            .collect();
+        let selection = self.selection();
+        self.exclude_with_group(anemometer, &rows, &reason);
+        self.register_reason(&reason);
+        self.record_operation("Sector exclusion", selection);
+        self.msg = format!(
+            "{} timestamps with {} within {}",
+            rows.iter().filter(|&&r| r).count(),
+            self.timeseries[direction].name,
+            self.sectors.trim()
+        );
+        Ok(())
+    }
+
+    // Runs the enabled rules on the channels in scope, one after the other,
+    // in the background
+    fn run_rules(&mut self) {
+        let rules: Vec<Rule> = self
+            .rules
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, rule)| rule.clone())
+            .collect();
+        if rules.is_empty() {
+            self.msg = "Enable at least one rule".to_owned();
+            return;
+        }
+
+        let series = self.rule_channels();
+        if series.is_empty() {
+            self.msg = "No channels to run the rules on".to_owned();
+            return;
+        }
+
+        // Group checks run once per sensor group, on its first channel in scope
+        let mut checked_groups = Vec::new();
+        let channels = series
+            .iter()
+            .map(|&n| {
+                self.materialize(n);
+                let members = self.sensor_group(n);
+                let mut group = GroupStats::default();
+                if members.len() > 1 && !checked_groups.contains(&members) {
+                    for &member in &members {
+                        self.materialize(member);
+                        let values = Some(self.series_values(member));
+                        match self
+                            .settings
+                            .name_convention
+                            .split_statistic(&self.timeseries[member].name)
+                        {
+                            Some((_, Statistic::Mean)) => group.mean = values,
+                            Some((_, Statistic::Min)) => group.min = values,
+                            Some((_, Statistic::Max)) => group.max = values,
+                            _ => (),
+                        }
+                    }
+                    checked_groups.push(members);
+                }
+                (n, self.series_values(n), group)
+            })
+            .collect();
+
+        self.detections.clear();
+        self.rule_job = Some(RuleJob::spawn(rules, channels));
+        self.show_review = true;
+    }
+
+    fn poll_rules(&mut self, ctx: &egui::Context) {
+        let Some(job) = self.rule_job.as_mut() else {
+            return;
+        };
+
+        self.detections.extend(job.poll());
+        if job.is_finished() {
+            self.rule_job = None;
+            self.msg = format!("Rules flagged {} periods", self.detections.len());
+        } else {
+            ctx.request_repaint();
+        }
+    }
+
+    // Excludes the rows of a detection with the rule's reason
+    fn accept_detection(&mut self, n: usize) {
+        if !self.check_editable() {
+            return;
+        }
+
+        let detection = self.detections.remove(n);
+        let mut rows = vec![false; self.index.len()];
+        rows[detection.rows].fill(true);
+        self.exclude_with_group(detection.series, &rows, &detection.reason);
+        self.register_reason(&detection.reason);
+        self.record_operation("Rule detection", self.selection());
+    }
+
+    fn show_review_window(&mut self, ctx: &egui::Context) {
+        let rows = self.reviewed_rows();
+        let mut open = self.show_review;
+        let mut jump = None;
+        let mut delete = None;
+        let mut copy = false;
+        egui::Window::new("Review exclusions")
+            .open(&mut open)
+            .default_width(700.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for status in [
+                        ReviewStatus::Proposed,
+                        ReviewStatus::Approved,
+                        ReviewStatus::Rejected,
+                    ] {
+                        let count = rows.iter().filter(|(_, s, _)| *s == status).count();
+                        ui.label(format!("{}: {}", status.label(), count));
+                    }
+                    copy = ui
+                        .button("Copy to clipboard")
+                        .on_hover_text("The exclusions as exported, tab separated, to paste into a spreadsheet or an email")
+                        .clicked();
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("review_grid")
+                        .striped(true)
+                        .num_columns(7)
+                        .show(ui, |ui| {
+                            for header in ["Mast", "Sensor", "Reason", "Start", "End", "Status"] {
+                                ui.strong(header);
+                            }
+                            ui.end_row();
+
+                            // Rows come sorted by sensor, which is only named on its first row
+                            let fmt = "%Y-%m-%d %H:%M";
+                            let mut previous: Option<&ExclusionRow> = None;
+                            for (row, row_status, keys) in &rows {
+                                let same_sensor = previous.is_some_and(|p| {
+                                    (&p.mast, &p.sensor) == (&row.mast, &row.sensor)
+                                });
+                                previous = Some(row);
+                                if same_sensor {
+                                    ui.label("");
+                                    ui.label("");
+                                } else {
+                                    ui.strong(&row.mast);
+                                    ui.strong(&row.sensor);
+                                }
+                                ui.label(&row.reason);
+                                ui.label(row.start.format(fmt).to_string());
+                                ui.label(row.end.format(fmt).to_string());
+                                ui.label(row_status.label());
+                                ui.horizontal(|ui| {
+                                    for status in [ReviewStatus::Approved, ReviewStatus::Rejected] {
+                                        if ui.small_button(status.label()).clicked() {
+                                            for key in keys {
+                                                self.review_states.insert(key.clone(), status);
+                                            }
+                                            self.audit.record(format!(
+                                                "{} exclusion {} {} '{}' {} - {}",
+                                                status.label(),
+                                                row.mast,
+                                                row.sensor,
+                                                row.reason,
+                                                row.start.format(fmt),
+                                                row.end.format(fmt)
+                                            ));
+                                        }
+                                    }
+                                    if ui
+                                        .small_button("Jump")
+                                        .on_hover_text("Show the period in the plot")
+                                        .clicked()
+                                    {
+                                        jump = Some(row.clone());
+                                    }
+                                    if ui
+                                        .add_enabled(!self.read_only, Button::new("Delete").small())
+                                        .clicked()
+                                    {
+                                        delete = Some(row.clone());
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                        });
+
+                    if self.rule_job.is_some() || !self.detections.is_empty() {
+                        ui.separator();
+                        ui.strong("Detected by rules");
+                        let mut accept = Vec::new();
+                        let mut dismiss = Vec::new();
+
+                        // Grouped by sensor, in the order the channels were scanned
+                        let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+                        for (n, detection) in self.detections.iter().enumerate() {
+                            match groups.last_mut() {
+                                Some((series, members)) if *series == detection.series => {
+                                    members.push(n)
+                                }
+                                _ => groups.push((detection.series, vec![n])),
+                            }
+                        }
+
+                        for (series, members) in groups {
+                            let name = &self.timeseries[series].name;
+                            ui.collapsing(format!("{} ({})", name, members.len()), |ui| {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("Exclude all").clicked() {
+                                        accept.extend(&members);
+                                    }
+                                    if ui.small_button("Dismiss all").clicked() {
+                                        dismiss.extend(&members);
+                                    }
+                                });
+                                egui::Grid::new(("detections_grid", series))
+                                    .striped(true)
+                                    .num_columns(4)
+                                    .show(ui, |ui| {
+                                        for &n in &members {
+                                            let detection = &self.detections[n];
+                                            ui.label(&detection.reason);
+                                            ui.label(&self.index[detection.rows.start]);
+                                            ui.label(&self.index[detection.rows.end - 1]);
+                                            ui.horizontal(|ui| {
+                                                if ui.small_button("Exclude").clicked() {
+                                                    accept.push(n);
+                                                }
+                                                if ui.small_button("Dismiss").clicked() {
+                                                    dismiss.push(n);
+                                                }
+                                            });
+                                            ui.end_row();
+                                        }
+                                    });
+                            });
+                        }
+
+                        // Highest first, so the remaining indices stay valid
+                        accept.sort_unstable();
+                        dismiss.sort_unstable();
+                        if !accept.is_empty() {
+                            for n in accept.into_iter().rev() {
+                                self.accept_detection(n);
+                            }
+                        } else {
+                            for n in dismiss.into_iter().rev() {
+                                self.detections.remove(n);
+                            }
+                        }
+                    }
+                });
+            });
+        self.show_review = open;
+
+        if let Some(row) = jump {
+            self.jump_to_exclusion(&row);
+        }
+        if let Some(row) = delete {
+            self.delete_exclusion(&row);
+        }
+        if copy {
+            self.copy_exclusions(ctx);
+        }
+    }
+
+    // Measured channels of the shown file belonging to the mast and sensor of a row
+    fn exclusion_channels(&self, row: &ExclusionRow) -> Vec<usize> {
+        (0..self.timeseries.len())
+            .filter(|&n| !self.timeseries[n].derived())
+            .filter(|&n| {
+                self.mast_sensor(&self.timeseries[n].name)
+                    .is_ok_and(|(mast, sensor)| mast == row.mast && sensor == row.sensor)
+            })
+            .collect()
+    }
+
+    // Plots the channel of an exclusion over time, zoomed to its period
+    fn jump_to_exclusion(&mut self, row: &ExclusionRow) {
+        let Some(&series) = self.exclusion_channels(row).first() else {
+            self.msg = format!("{} {} is not in the file shown", row.mast, row.sensor);
+            return;
+        };
+        self.materialize(series);
+        self.plot_mode = PlotMode::TimeSeries;
+        self.yaxis = series;
+
+        let covered = covered_rows(&self.times, row);
+        let values: Vec<f64> = izip!(self.timeseries[series].data.iter(), &covered)
+            .filter_map(|(val, covered)| match (val, covered) {
+                (DataPoint::Valid(v) | DataPoint::Excluded(v, _), true) => Some(v),
+                _ => None,
+            })
+            .collect();
+        let (min, max) = values
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                (lo.min(*v), hi.max(*v))
+            });
+        let (start, end) = (
+            row.start.and_utc().timestamp() as f64,
+            row.end.and_utc().timestamp() as f64,
+        );
+        // Some context around the period on both axes
+        let margin_x = (end - start).max(3600.0);
+        let margin_y = ((max - min) * 0.2).max(1.0);
+        if min <= max {
+            self.jump_to = Some([
+                [start - margin_x, min - margin_y],
+                [end + margin_x, max + margin_y],
+            ]);
+        }
+        self.msg = format!("Showing {} {} '{}'", row.mast, row.sensor, row.reason);
+    }
+
+    // Restores the points of an exclusion row in the shown file, as an undoable operation
+    fn delete_exclusion(&mut self, row: &ExclusionRow) {
+        if !self.check_editable() {
+            return;
+        }
+        let channels = self.exclusion_channels(row);
+        let covered = covered_rows(&self.times, row);
+        for series in channels {
+            self.materialize(series);
+            let rows: Vec<bool> = izip!(self.timeseries[series].data.iter(), &covered)
+                .map(|(val, covered)| {
+                    *covered
+                        && matches!(val, DataPoint::Excluded(_, reason) if reason == row.reason)
+                })
+                .collect();
+            self.restore_timeseries_data(series, &rows);
+        }
+
+        let count = self.pending_restorations.len();
+        if count == 0 {
+            self.msg = format!(
+                "No points of {} {} to restore in the file shown",
+                row.mast, row.sensor
+            );
+            return;
+        }
+        self.msg = format!("Exclusion deleted, {} points restored", count);
+        self.audit.record(format!(
+            "Deleted exclusion {} {} '{}' {} - {}",
+            row.mast, row.sensor, row.reason, row.start, row.end
+        ));
+        self.record_operation("Delete exclusion", self.selection());
+    }
+
+    // Rows inside the selection drawn on the main plot, once it has an area
+    fn brushed_rows(&mut self) -> Option<Vec<bool>> {
+        if self.exclusion_curve.len() < 3 {
+            return None;
+        }
+        let key = (
+            self.selection(),
+            self.revision,
+            (self.xaxis, self.yaxis, self.plot_mode),
+        );
+        if let Some((cached, rows)) = &self.brush_cache {
+            if *cached == key {
+                return Some(rows.clone());
+            }
+        }
+
+        let mut yaxes = vec![self.yaxis];
+        if self.exclude_extra_y {
+            yaxes.extend(self.plotted_extra_yaxes());
+        }
+        let offsets = seam_offsets(self.direction_axes());
+        let mut rows = vec![false; self.index.len()];
+        for yaxis in yaxes {
+            let data = self.convert_points(yaxis);
+            let inside =
+                check_inside_curve_with_offsets(self.exclusion_curve.clone(), data, &offsets);
+            for (row, inside) in rows.iter_mut().zip(inside) {
+                *row |= inside;
+            }
+        }
+        self.brush_cache = Some((key, rows.clone()));
+        Some(rows)
+    }
+
+    // Rows inside the selection drawn on the linked plot, once it has an area
+    fn window_brushed_rows(&mut self) -> Option<Vec<bool>> {
+        if self.window_curve.len() < 3 {
+            return None;
+        }
+        let key = (
+            self.window_curve.clone(),
+            self.revision,
+            (self.xaxis, self.window_yaxis, self.plot_mode),
+            self.window_over_time,
+        );
+        if let Some((cached, rows)) = &self.window_brush_cache {
+            if *cached == key {
+                return Some(rows.clone());
+            }
+        }
+
+        let data = match self.window_over_time {
+            true => izip!(&self.times, self.timeseries[self.window_yaxis].data.iter())
+                .map(|(t, val)| match val {
+                    DataPoint::Valid(v) => [*t, v],
+                    _ => [f64::NAN, f64::NAN],
+                })
+                .collect(),
+            false => self.convert_points(self.window_yaxis),
+        };
+        let rows = check_inside_curve_with_offsets(self.window_curve.clone(), data, &[[0.0, 0.0]]);
+        self.window_brush_cache = Some((key, rows.clone()));
+        Some(rows)
+    }
+
+    // Valid points of the main plot in the rows selected on the linked plot
+    fn window_brushed_points(&mut self) -> Vec<[f64; 2]> {
+        let Some(rows) = self.window_brushed_rows() else {
+            return Vec::new();
+        };
+        izip!(self.convert_points(self.yaxis), rows)
+            .filter(|(p, brushed)| *brushed && p[0].is_finite())
+            .map(|(p, _)| p)
+            .collect()
+    }
+
+    // Points of the linked plot: valid, excluded by reason color and those of
+    // the brushed rows
+    fn linked_points(&mut self) -> LinkedPoints {
+        let series = self.window_yaxis;
+        let brushed = self.brushed_rows().unwrap_or_default();
+        let is_brushed = |row: usize| brushed.get(row) == Some(&true);
+        if !self.window_over_time {
+            let highlighted = self
+                .convert_points(series)
+                .into_iter()
+                .enumerate()
+                .filter(|(row, p)| is_brushed(*row) && p[0].is_finite())
+                .map(|(_, p)| p)
+                .collect();
+            let valid = self.cached_valid_points(series);
+            let excluded = self.cached_excluded_points(series);
+            return (valid, excluded, highlighted);
+        }
+
+        let mut valid = Vec::new();
+        let mut excluded: BTreeMap<String, Vec<[f64; 2]>> = BTreeMap::new();
+        let mut highlighted = Vec::new();
+        for (row, (t, val)) in self
+            .times
+            .iter()
+            .zip(self.timeseries[series].data.iter())
+            .enumerate()
+        {
+            match val {
+                DataPoint::Valid(v) if is_brushed(row) => highlighted.push([*t, v]),
+                DataPoint::Valid(v) => valid.push([*t, v]),
+                DataPoint::Excluded(v, reason) => {
+                    excluded.entry(reason.to_owned()).or_default().push([*t, v])
+                }
+                DataPoint::NaN => (),
+            }
+        }
+        let excluded = excluded
+            .into_iter()
+            .map(|(reason, points)| (self.reason_color(&reason), points.into()))
+            .collect();
+        (valid.into(), excluded, highlighted)
+    }
+
+    // Second plot sharing the x axis and cursor with the main one, so another
+    // channel can be watched while panning and zooming the main plot. A
+    // selection drawn on either highlights its rows on the other. It floats
+    // inside the application window rather than in its own OS window, which
+    // eframe 0.22 has no way to open: to spread the plots over two monitors,
+    // stretch the application window across both.
+    fn show_plot_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_plot_window;
+        egui::Window::new("Linked plot")
+            .open(&mut open)
+            .default_size([500.0, 400.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                let shown = (self.window_yaxis, self.window_over_time);
+                ui.horizontal(|ui| {
+                    ComboBox::new("Select linked y axis", "Y-axis")
+                        .selected_text(&self.timeseries[self.window_yaxis].name)
+                        .show_ui(ui, |ui| {
+                            for (i, ts) in self.timeseries.iter().enumerate() {
+                                ui.selectable_value(&mut self.window_yaxis, i, &ts.name);
+                            }
+                        });
+                    ui.checkbox(&mut self.window_over_time, "Over time")
+                        .on_hover_text("Plot the channel over time, e.g. to see when a cluster selected in the scatter plot happened");
+                    let clear = ui.add_enabled(!self.window_curve.is_empty(), Button::new("Clear selection"));
+                    if clear.on_hover_text("E+click on this plot to select samples and see them on the main plot").clicked() {
+                        self.window_curve.clear();
+                    }
+                });
+                // A selection drawn against other axes no longer applies
+                if shown != (self.window_yaxis, self.window_over_time) {
+                    self.window_curve.clear();
+                }
+
+                let (points_valid, points_excluded, points_brushed) = self.linked_points();
+                let theme = &self.theme;
+                let over_time = self.window_over_time || self.plot_mode == PlotMode::TimeSeries;
+
+                ui.visuals_mut().extreme_bg_color = color(theme.background);
+                let mut plot = Plot::new("linked_plot");
+                if !self.window_over_time || self.plot_mode == PlotMode::TimeSeries {
+                    plot = plot
+                        .link_axis(LINKED_PLOTS, true, false)
+                        .link_cursor(LINKED_PLOTS, true, false);
+                }
+                if over_time {
+                    plot = plot
+                        .x_axis_formatter(|x, _range| format_time(x))
+                        .label_formatter(|_name, value| {
+                            format!("{}\n{:.3}", format_time(value.x), value.y)
+                        });
+                }
+
+                plot.show(ui, |plot_ui| {
+                    plot_ui.points(
+                        Points::new(points_valid.to_vec())
+                            .radius(theme.point_radius)
+                            .color(color(theme.valid)),
+                    );
+                    // The samples inside the selection of the main plot, before excluding them
+                    plot_ui.points(
+                        Points::new(points_brushed)
+                            .radius(theme.point_radius * 1.5)
+                            .color(color(theme.selection_open)),
+                    );
+                    if self.show_excluded {
+                        for (rgb, points) in points_excluded {
+                            plot_ui.points(
+                                Points::new(points.to_vec())
+                                    .radius(theme.point_radius)
+                                    .color(color(rgb)),
+                            );
+                        }
+                    }
+
+                    // E+click draws a selection here too, closed once it has
+                    // three vertices, and Escape drops it
+                    let curve_color = color(theme.selection_closed);
+                    plot_ui.points(
+                        Points::new(self.window_curve.clone())
+                            .radius(theme.vertex_radius)
+                            .color(curve_color),
+                    );
+                    let mut closed = self.window_curve.clone();
+                    closed.extend(self.window_curve.first().filter(|_| closed.len() > 2));
+                    plot_ui.line(Line::new(closed).width(theme.line_width).color(curve_color));
+                    let input = plot_ui.ctx().input(|i| i.clone());
+                    if !self.presentation && plot_ui.plot_hovered() {
+                        if input.key_down(egui::Key::E) && input.pointer.primary_clicked() {
+                            if let Some(pointer) = plot_ui.pointer_coordinate() {
+                                self.window_curve.push([pointer.x, pointer.y]);
+                            }
+                        }
+                        if input.key_pressed(egui::Key::Escape) {
+                            self.window_curve.clear();
+                        }
+                    }
+                });
+            });
+        self.show_plot_window = open;
+    }
+
+    // Measured channels with a known height: those of the profile sensor type
+    // and the direction ones
+    fn profile_channels(&self) -> (ProfileChannels, ProfileChannels) {
+        let mut speeds = Vec::new();
+        let mut directions = Vec::new();
+        for (n, ts) in self
+            .timeseries
+            .iter()
+            .enumerate()
+            .filter(|(_, ts)| !ts.derived())
+        {
+            let Some(height) = self.channel_height(&ts.name) else {
+                continue;
+            };
+            if self.is_direction_series(n) {
+                directions.push((n, height));
+            } else if self.channel_type(&ts.name).as_deref() == Some(self.profile_type.as_str()) {
+                speeds.push((n, height));
+            }
+        }
+        (speeds, directions)
+    }
+
+    // Values of the channels at one row against their height, as valid points
+    // and excluded ones
+    fn profile_points(
+        &self,
+        channels: &[(usize, f64)],
+        row: usize,
+    ) -> (Vec<[f64; 2]>, Vec<[f64; 2]>) {
+        let mut valid = Vec::new();
+        let mut excluded = Vec::new();
+        for &(series, height) in channels {
+            match self.timeseries[series].data.get(row) {
+                Some(DataPoint::Valid(v)) => valid.push([v, height]),
+                Some(DataPoint::Excluded(v, _)) => excluded.push([v, height]),
+                _ => (),
+            }
+        }
+        valid.sort_by(|a, b| a[1].total_cmp(&b[1]));
+        (valid, excluded)
+    }
+
+    // Excludes one timestamp in every channel of the profile
+    fn exclude_profile_row(&mut self, row: usize) {
+        if !self.check_editable() {
+            return;
+        }
+        if self.reason.is_empty() {
+            self.msg = "Write a reason for exclusion".to_owned();
+            return;
+        }
+
+        let (speeds, directions) = self.profile_channels();
+        let mut rows = vec![false; self.index.len()];
+        rows[row] = true;
+        let reason = self.reason.clone();
+        self.register_reason(&reason);
+        for (series, _) in speeds.into_iter().chain(directions) {
+            self.exclude_with_group(series, &rows, &reason);
+        }
+        self.msg = format!(
+            "{} excluded across heights by '{}' reason",
+            self.index[row], reason
+        );
+        self.record_operation("Profile exclusion", self.selection());
+    }
+
+    // Valid and excluded values of a channel, over the rows shown
+    fn histogram_values(&self, series: usize) -> (Vec<f64>, Vec<f64>) {
+        let mut valid = Vec::new();
+        let mut excluded = Vec::new();
+        for (val, shown) in izip!(self.timeseries[series].data.iter(), self.shown_rows()) {
+            match (val, shown) {
+                (DataPoint::Valid(v), true) => valid.push(v),
+                (DataPoint::Excluded(v, _), true) => excluded.push(v),
+                _ => {}
+            }
+        }
+        (valid, excluded)
+    }
+
+    fn show_histogram_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_histogram;
+        let (valid, excluded) = self.histogram_values(self.yaxis);
+        egui::Window::new("Histogram")
+            .open(&mut open)
+            .default_size([500.0, 300.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(self.channel_label(self.yaxis));
+                    ui.label("Bin width");
+                    ui.add(
+                        DragValue::new(&mut self.histogram_bin)
+                            .speed(0.1)
+                            .clamp_range(0.001..=f64::MAX),
+                    );
+                    ui.checkbox(&mut self.histogram_excluded, "Show excluded");
+                });
+
+                let width = self.histogram_bin;
+                let bars = |values: &[f64]| -> Vec<Bar> {
+                    histogram(values, width)
+                        .into_iter()
+                        .map(|(n, count)| {
+                            Bar::new((n as f64 + 0.5) * width, count as f64).width(width)
+                        })
+                        .collect()
+                };
+                let valid_bars = bars(&valid);
+                let excluded_bars = if self.histogram_excluded {
+                    bars(&excluded)
+                } else {
+                    Vec::new()
+                };
+                if valid_bars.len() + excluded_bars.len() > MAX_HISTOGRAM_BINS {
+                    ui.label("Too many bins, use a wider bin");
+                    return;
+                }
+
+                let theme = &self.theme;
+                ui.visuals_mut().extreme_bg_color = color(theme.background);
+                Plot::new("histogram_plot")
+                    .legend(Legend::default())
+                    .label_formatter(|_name, value| {
+                        format!("{:.2}\n{:.0} points", value.x, value.y)
+                    })
+                    .show(ui, |plot_ui| {
+                        plot_ui.bar_chart(
+                            BarChart::new(valid_bars)
+                                .color(color(theme.valid))
+                                .name("Valid"),
+                        );
+                        if !excluded_bars.is_empty() {
+                            plot_ui.bar_chart(
+                                BarChart::new(excluded_bars)
+                                    .color(color(theme.excluded).gamma_multiply(0.7))
+                                    .name("Excluded"),
+                            );
+                        }
+                    });
+            });
+        self.show_histogram = open;
+    }
+
+    // Wind speed and direction against height at the pinned timestamp, or at
+    // the one under the pointer
+    fn show_profile_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_profile;
+        let (speeds, directions) = self.profile_channels();
+        let row = self
+            .pinned_row
+            .or(self.hovered_row)
+            .filter(|&row| row < self.index.len());
+        let mut exclude = false;
+        egui::Window::new("Vertical profile")
+            .open(&mut open)
+            .default_size([500.0, 400.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Sensor type");
+                    ui.add_sized([60., 20.], TextEdit::singleline(&mut self.profile_type));
+                    match row {
+                        Some(row) => ui.label(&self.index[row]),
+                        None => ui.label("Hover the time series plot"),
+                    };
+                    if self.pinned_row.is_some() {
+                        if ui.button("Unpin").clicked() {
+                            self.pinned_row = None;
+                        }
+                    } else if ui.add_enabled(row.is_some(), Button::new("Pin")).on_hover_text("Or press P over the plot").clicked() {
+                        self.pinned_row = row;
+                    }
+                    exclude = ui
+                        .add_enabled(row.is_some(), Button::new("Exclude across heights"))
+                        .on_hover_text("Exclude this timestamp in every channel of the profile, with the current reason")
+                        .clicked();
+                });
+
+                if speeds.is_empty() && directions.is_empty() {
+                    ui.label("No channel has a height in the sensor configuration or its name");
+                    return;
+                }
+
+                let Some(row) = row else { return };
+                let theme = &self.theme;
+                ui.visuals_mut().extreme_bg_color = color(theme.background);
+                ui.columns(if directions.is_empty() { 1 } else { 2 }, |columns| {
+                    for (n, channels) in [&speeds, &directions].into_iter().filter(|c| !c.is_empty()).enumerate() {
+                        let (valid, excluded) = self.profile_points(channels, row);
+                        Plot::new(("profile_plot", n))
+                            .label_formatter(|_name, value| format!("{:.2}\n{:.1} m", value.x, value.y))
+                            .show(&mut columns[n], |plot_ui| {
+                                plot_ui.line(Line::new(valid.clone()).width(theme.line_width).color(color(theme.valid)));
+                                plot_ui.points(Points::new(valid).radius(2.0 * theme.point_radius).color(color(theme.valid)));
+                                plot_ui.points(Points::new(excluded).radius(2.0 * theme.point_radius).color(color(theme.excluded)));
+                            });
+                    }
+                });
+            });
+
+        if exclude {
+            if let Some(row) = row {
+                self.exclude_profile_row(row);
+            }
+        }
+        self.show_profile = open;
+    }
+
+    // Mean speed channels of the profile sensor type with a height, by mast
+    fn shear_channels(&self) -> BTreeMap<String, ProfileChannels> {
+        let mut masts: BTreeMap<String, ProfileChannels> = BTreeMap::new();
+        for (n, ts) in self
+            .timeseries
+            .iter()
+            .enumerate()
+            .filter(|(_, ts)| !ts.derived())
+        {
+            let mean = self
+                .settings
+                .name_convention
+                .split_statistic(&ts.name)
+                .map_or(true, |(_, s)| s == Statistic::Mean);
+            if !mean || self.channel_type(&ts.name).as_deref() != Some(self.profile_type.as_str()) {
+                continue;
+            }
+            if let (Some(height), Ok((mast, _))) =
+                (self.channel_height(&ts.name), self.mast_sensor(&ts.name))
+            {
+                masts.entry(mast).or_default().push((n, height));
+            }
+        }
+        masts
+    }
+
+    // Mean speed against height of the channels, over every timestamp or per
+    // direction sector, as named lines. Only the rows where every height is
+    // valid count, so that no height is averaged over other periods. Worked
+    // out again only when the data or the options changed.
+    fn shear_lines(&mut self, channels: &[(usize, f64)]) -> Vec<ShearLine> {
+        for &(n, _) in channels {
+            self.materialize(n);
+        }
+        if let Some(n) = self.shear_direction {
+            self.materialize(n);
+        }
+        let period = match &self.view {
+            Some(((min, max), (_, _, PlotMode::TimeSeries))) if self.shear_shown_period => {
+                Some((min[0], max[0]))
+            }
+            _ => None,
+        };
+        let key = (
+            channels.to_vec(),
+            self.revision,
+            self.shear_direction,
+            self.shear_sectors,
+            period,
+        );
+        if let Some((cached, lines)) = &self.shear_cache {
+            if *cached == key {
+                return lines.clone();
+            }
+        }
+
+        let directions = self.shear_direction.map(|n| self.series_values(n));
+        let sectors = match directions {
+            Some(_) => self.shear_sectors.max(1),
+            None => 1,
+        };
+        let values: Vec<Vec<f64>> = channels
+            .iter()
+            .map(|&(n, _)| self.series_values(n))
+            .collect();
+
+        let groups: Vec<Option<usize>> = self
+            .times
+            .iter()
+            .enumerate()
+            .map(|(row, time)| {
+                if period.is_some_and(|(start, end)| *time < start || *time > end) {
+                    return None;
+                }
+                if values.iter().any(|values| !values[row].is_finite()) {
+                    return None;
+                }
+                match &directions {
+                    Some(directions) if sectors > 1 => {
+                        let direction = directions[row];
+                        direction
+                            .is_finite()
+                            .then(|| rose_index(direction, sectors))
+                    }
+                    _ => Some(0),
+                }
+            })
+            .collect();
+        let means: Vec<Vec<Option<f64>>> = values
+            .iter()
+            .map(|values| group_means(values, &groups, sectors))
+            .collect();
+
+        let lines: Vec<ShearLine> = (0..sectors)
+            .map(|group| {
+                let mut points: Vec<[f64; 2]> = izip!(channels, &means)
+                    .filter_map(|(&(_, height), means)| Some([means[group]?, height]))
+                    .collect();
+                points.sort_by(|a, b| a[1].total_cmp(&b[1]));
+                let name = match sectors {
+                    1 => "All".to_owned(),
+                    _ => {
+                        let (start, end) = rose_sector(group, sectors);
+                        format!("{}°-{}°", start, end)
+                    }
+                };
+                (name, points)
+            })
+            .filter(|(_, points)| !points.is_empty())
+            .collect();
+        self.shear_cache = Some((key, lines.clone()));
+        lines
+    }
+
+    fn show_shear_window(&mut self, ctx: &egui::Context) {
+        let masts = self.shear_channels();
+        if !masts.contains_key(&self.shear_mast) {
+            self.shear_mast = masts.keys().next().cloned().unwrap_or_default();
+        }
+        let channels = masts.get(&self.shear_mast).cloned().unwrap_or_default();
+        let lines = self.shear_lines(&channels);
+        let options: Vec<String> = (0..self.timeseries.len())
+            .map(|n| self.channel_label(n))
+            .collect();
+        let directions: Vec<usize> = (0..self.timeseries.len())
+            .filter(|&n| self.is_direction_series(n))
+            .collect();
+
+        let mut open = self.show_shear;
+        egui::Window::new("Shear profile")
+            .open(&mut open)
+            .default_size([500.0, 450.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Mast");
+                    ComboBox::new("shear mast", "")
+                        .selected_text(&self.shear_mast)
+                        .show_ui(ui, |ui| {
+                            for mast in masts.keys() {
+                                ui.selectable_value(&mut self.shear_mast, mast.clone(), mast);
+                            }
+                        });
+                    ui.label("Sensor type");
+                    ui.add_sized([60., 20.], TextEdit::singleline(&mut self.profile_type));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Direction");
+                    ComboBox::new("shear direction", "")
+                        .selected_text(self.shear_direction.map_or("None", |n| options[n].as_str()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.shear_direction, None, "None");
+                            for &n in &directions {
+                                ui.selectable_value(&mut self.shear_direction, Some(n), &options[n]);
+                            }
+                        });
+                    ComboBox::new("shear sectors", "sectors")
+                        .selected_text(self.shear_sectors.to_string())
+                        .show_ui(ui, |ui| {
+                            for n in [1, 4, 8, 12] {
+                                ui.selectable_value(&mut self.shear_sectors, n, n.to_string());
+                            }
+                        });
+                    ui.checkbox(&mut self.shear_shown_period, "Shown period only")
+                        .on_hover_text("Only the timestamps within the time axis of the main plot, in time series mode");
+                });
+
+                if channels.len() < 2 {
+                    ui.label("The mast needs two heights or more of the sensor type, from the sensor configuration or the channel names");
+                    return;
+                }
+                ui.label("Mean of the valid values at each height. An anemometer off the curve of the others may be miscalibrated.");
+
+                let theme = &self.theme;
+                ui.visuals_mut().extreme_bg_color = color(theme.background);
+                Plot::new("shear_plot")
+                    .legend(Legend::default())
+                    .label_formatter(|_name, value| format!("{:.2}\n{:.1} m", value.x, value.y))
+                    .show(ui, |plot_ui| {
+                        for (n, (name, points)) in lines.into_iter().enumerate() {
+                            let name = match shear_exponent(&points) {
+                                Some(alpha) => format!("{} (α {:.3})", name, alpha),
+                                None => name,
+                            };
+                            let line_color = theme.series_color(n);
+                            plot_ui.line(
+                                Line::new(points.clone())
+                                    .width(theme.line_width)
+                                    .color(line_color)
+                                    .name(&name),
+                            );
+                            plot_ui.points(
+                                Points::new(points)
+                                    .radius(2.0 * theme.point_radius)
+                                    .color(line_color)
+                                    .name(&name),
+                            );
+                        }
+                    });
+            });
+        self.show_shear = open;
+    }
+
+    // Additional y channels to plot, besides the main y axis
+    fn plotted_extra_yaxes(&self) -> Vec<usize> {
+        self.extra_yaxes
+            .iter()
+            .copied()
+            .filter(|&y| y != self.yaxis && y < self.timeseries.len())
+            .collect()
+    }
+
+    // Mast and sensor of a channel, from the sensor configuration when it lists
+    // them, or else from the naming convention of the settings
+    fn mast_sensor(&self, name: &str) -> Result<(String, String), String> {
+        match self.sensor_config.get(name) {
+            Some(info) if !info.mast.is_empty() && !info.sensor.is_empty() => {
+                Ok((info.mast.clone(), info.sensor.clone()))
+            }
+            _ => self.settings.name_convention.split(name),
+        }
+    }
+
+    // Height of a channel, from the sensor configuration when it lists one, or
+    // else from the number in its sensor name
+    fn channel_height(&self, name: &str) -> Option<f64> {
+        match self.sensor_config.get(name).and_then(|info| info.height) {
+            Some(height) => Some(height),
+            None => sensor_height(&self.mast_sensor(name).ok()?.1),
+        }
+    }
+
+    fn channel_type(&self, name: &str) -> Option<String> {
+        match self.sensor_config.get(name) {
+            Some(info) if !info.sensor_type.is_empty() => Some(info.sensor_type.clone()),
+            _ => sensor_kind(&self.mast_sensor(name).ok()?.1),
+        }
+    }
+
+    // Sets the window visuals when the chosen theme, or the system one, changes.
+    // Plot colors left at the built-in ones follow along.
+    fn apply_ui_theme(&mut self, ctx: &egui::Context, system_dark: Option<bool>) {
+        let dark = self.settings.ui_theme.is_dark(system_dark);
+        if self.dark_visuals == Some(dark) {
+            return;
+        }
+        ctx.set_visuals(match dark {
+            true => egui::Visuals::dark(),
+            false => egui::Visuals::light(),
+        });
+        if self.theme == PlotTheme::preset(!dark) {
+            self.theme = PlotTheme::preset(dark);
+        }
+        self.dark_visuals = Some(dark);
+    }
+
+    fn license_expired(&self) -> bool {
+        matches!(self.license, License::Expired(_))
+    }
+
+    // Warning shown over the whole window during the grace period
+    fn show_license_banner(&mut self, ctx: &egui::Context) {
+        let License::Grace(_, days) = self.license else {
+            return;
+        };
+        egui::TopBottomPanel::top("license_banner").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(
+                    egui::RichText::new(format!("License expired: {} days left", days))
+                        .color(egui::Color32::from_rgb(255, 200, 100)),
+                )
+                .on_hover_text(self.license.message());
+                if ui.button("Enter key").clicked() {
+                    self.show_license = true;
+                }
+            });
+        });
+    }
+
+    // Row to type a new license key. True when it should be saved.
+    fn license_key_row(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.horizontal(|ui| {
+            ui.label("Key");
+            let key = ui.add(
+                TextEdit::singleline(&mut self.license_key)
+                    .hint_text("Paste the key")
+                    .desired_width(180.0),
+            );
+            ui.button("Save key").clicked()
+                || (key.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)))
+        })
+        .inner
+    }
+
+    fn save_license_key(&mut self) {
+        match expiration::save_key(&self.license_key) {
+            Ok(_) => {
+                self.license = License::current();
+                self.msg = self.license.message();
+                self.license_key.clear();
+                self.show_license = false;
+            }
+            Err(e) => self.msg = e,
+        }
+    }
+
+    fn show_license_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_license;
+        let mut save = false;
+        egui::Window::new("License")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(self.license.message());
+                save = self.license_key_row(ui);
+            });
+        self.show_license = open;
+
+        if save {
+            self.save_license_key();
+        }
+    }
+
+    // Shown instead of the whole app once the license is expired, until a
+    // new key is entered
+    fn show_expired_screen(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(ui.available_height() / 3.0);
+                ui.heading("Manual Data Cleaner has expired");
+                ui.add_space(10.0);
+                ui.label(self.license.message());
+                if ui.button("Copy contact").clicked() {
+                    ui.output_mut(|o| o.copied_text = expiration::CONTACT.to_owned());
+                    self.msg = "Contact copied to the clipboard".into();
+                }
+                ui.add_space(10.0);
+                if self.license_key_row(ui) {
+                    self.save_license_key();
+                }
+                ui.label(&self.msg);
+                if ui.button("Close").clicked() {
+                    frame.close();
+                }
+            });
+        });
+    }
+
+    fn show_appearance_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_appearance;
+        egui::Window::new("Appearance")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("appearance_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Window theme");
+                        ComboBox::new("ui theme", "")
+                            .selected_text(self.settings.ui_theme.label())
+                            .show_ui(ui, |ui| {
+                                for theme in UiTheme::ALL {
+                                    ui.selectable_value(
+                                        &mut self.settings.ui_theme,
+                                        theme,
+                                        theme.label(),
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        let theme = &mut self.theme;
+                        for (label, rgb) in [
+                            ("Plot background", &mut theme.background),
+                            ("Valid points", &mut theme.valid),
+                            ("Excluded points", &mut theme.excluded),
+                            ("Open selection", &mut theme.selection_open),
+                            ("Closed selection", &mut theme.selection_closed),
+                        ] {
+                            ui.label(label);
+                            ui.color_edit_button_srgb(rgb);
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Dark colors").clicked() {
+                        self.theme = PlotTheme::default();
+                    }
+                    if ui.button("Light colors").clicked() {
+                        self.theme = PlotTheme::light();
+                    }
+                });
+            });
+        self.show_appearance = open;
+    }
+
+    // Editor of the naming convention, with how it splits the first channels
+    fn show_naming_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_naming;
+        egui::Window::new("Channel names")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let convention = &mut self.settings.name_convention;
+                let mut pattern = convention.pattern().to_owned();
+                egui::Grid::new("naming_grid").num_columns(2).show(ui, |ui| {
+                    ui.label("Separator");
+                    ui.add(TextEdit::singleline(&mut convention.separator).desired_width(40.0));
+                    ui.end_row();
+
+                    ui.label("Mast part");
+                    ui.add(DragValue::new(&mut convention.mast_part).clamp_range(0..=20))
+                        .on_hover_text("Position of the mast among the parts, counted from 0");
+                    ui.end_row();
+
+                    ui.label("Sensor part");
+                    ui.add(DragValue::new(&mut convention.sensor_part).clamp_range(0..=20));
+                    ui.end_row();
+
+                    ui.label("Pattern");
+                    ui.add(TextEdit::singleline(&mut pattern).hint_text("(?P<mast>M\\d+)_(?P<sensor>.+)"))
+                        .on_hover_text("Regular expression with mast and sensor groups, used instead of the separator when set");
+                    ui.end_row();
+
+                    ui.label("Units");
+                    ui.checkbox(&mut self.settings.units_from_names, "From the third ~ part")
+                        .on_hover_text("As in M1~WS80~m/s, for the channels without configured units");
+                    ui.end_row();
+                });
+                if pattern != convention.pattern() {
+                    convention.set_pattern(pattern);
+                }
+
+                ui.separator();
+                for ts in self.timeseries.iter().filter(|ts| !ts.derived()).take(5) {
+                    let split = match self.settings.name_convention.split(&ts.name) {
+                        Ok((mast, sensor)) => format!("mast {}, sensor {}", mast, sensor),
+                        Err(e) => e,
+                    };
+                    ui.label(format!("{} → {}", ts.name, split));
+                }
+            });
+        self.show_naming = open;
+    }
+
+    // Overlay listing the shortcuts, and the keys used while drawing
+    fn show_shortcuts_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_shortcuts;
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                let shortcuts = &self.settings.shortcuts;
+                let plot_keys = [
+                    ("E+click", "Add a selection vertex"),
+                    ("T+click", "Start or end a time range"),
+                    ("Ctrl+click", "Exclude the point under the pointer"),
+                    ("P", "Pin the timestamp under the pointer"),
+                    ("Escape", "Drop the selection being drawn"),
+                ];
+                egui::Grid::new("shortcuts_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for action in Action::ALL {
+                            ui.strong(shortcuts.label(action));
+                            ui.label(action.label());
+                            ui.end_row();
+                        }
+                        ui.separator();
+                        ui.separator();
+                        ui.end_row();
+                        for (keys, label) in plot_keys {
+                            ui.strong(keys);
+                            ui.label(label);
+                            ui.end_row();
+                        }
+                    });
+                if ui.button("Change shortcuts").clicked() {
+                    self.show_shortcut_settings = true;
+                }
+            });
+        self.show_shortcuts = open;
+    }
+
+    // Remaps a shortcut to the next key pressed after clicking it
+    fn show_shortcut_settings_window(&mut self, ctx: &egui::Context) {
+        if let Some(action) = self.remapping {
+            if let Some(shortcut) = ctx.input(|i| Shortcut::captured(&i.events)) {
+                self.remapping = None;
+                match self.settings.shortcuts.conflict(action, shortcut) {
+                    Some(other) => {
+                        self.msg = format!(
+                            "{} is already used to {}",
+                            shortcut.label(),
+                            other.to_lowercase()
+                        )
+                    }
+                    None => self.settings.shortcuts.set(action, shortcut),
+                }
+            }
+        }
+
+        let mut open = self.show_shortcut_settings;
+        egui::Window::new("Shortcut settings")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcut_settings_grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for action in Action::ALL {
+                            ui.label(action.label());
+                            let text = match self.remapping == Some(action) {
+                                true => "Press a key...".to_owned(),
+                                false => self.settings.shortcuts.label(action),
+                            };
+                            if ui.add_sized([120., 20.], Button::new(text)).clicked() {
+                                self.remapping = Some(action);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Defaults").clicked() {
+                        self.settings.shortcuts.reset();
+                    }
+                    if ui
+                        .add_enabled(self.remapping.is_some(), Button::new("Cancel"))
+                        .clicked()
+                    {
+                        self.remapping = None;
+                    }
+                });
+            });
+        if !open {
+            self.remapping = None;
+        }
+        self.show_shortcut_settings = open;
+    }
+
+    fn channel_units(&self, series: usize) -> String {
+        let name = &self.timeseries[series].name;
+        match self.sensor_config.get(name) {
+            Some(info) if !info.units.is_empty() => info.units.clone(),
+            _ if self.settings.units_from_names => self
+                .settings
+                .name_convention
+                .name_units(name)
+                .unwrap_or_default()
+                .to_owned(),
+            _ => String::new(),
+        }
+    }
+
+    // Channel name with its units, for the plot axes
+    fn axis_label(&self, series: usize) -> String {
+        let name = &self.timeseries[series].name;
+        match self.channel_units(series) {
+            units if units.is_empty() => name.clone(),
+            units => format!("{} [{}]", name, units),
+        }
+    }
+
+    // Channel name for the selection lists, with its configured metadata
+    fn channel_label(&self, series: usize) -> String {
+        let name = &self.timeseries[series].name;
+        match self.sensor_config.get(name).map(|info| info.summary()) {
+            Some(summary) if !summary.is_empty() => format!("{} ({})", name, summary),
+            _ => name.clone(),
+        }
+    }
+
+    fn calibration_label(&self, series: usize) -> Option<String> {
+        let info = self.sensor_config.get(&self.timeseries[series].name)?;
+        match (info.slope, info.offset) {
+            (None, None) => None,
+            (slope, offset) => Some(format!(
+                "Calibration: slope {}, offset {}",
+                slope.map_or("-".to_owned(), |s| s.to_string()),
+                offset.map_or("-".to_owned(), |o| o.to_string())
+            )),
+        }
+    }
+
+    fn is_direction_series(&self, series: usize) -> bool {
+        let ts = &self.timeseries[series];
+        !ts.derived() && is_direction(&ts.name, &self.settings.direction_tokens)
+    }
+
+    fn direction_axes(&self) -> [bool; 2] {
+        [
+            self.plot_mode == PlotMode::Scatter && self.is_direction_series(self.xaxis),
+            self.is_direction_series(self.yaxis),
+        ]
+    }
+
+    fn add_angular_difference(&mut self) {
+        let (a, b) = (self.difference_a, self.difference_b);
+        if a == b || !self.is_direction_series(a) || !self.is_direction_series(b) {
+            self.msg = "Select two different direction channels".to_owned();
+            return;
+        }
+
+        let name = format!(
+            "{} - {} (circular)",
+            self.timeseries[a].name, self.timeseries[b].name
+        );
+        if self.timeseries.iter().any(|ts| ts.name == name) {
+            self.msg = format!("Channel '{}' already exists", name);
+            return;
+        }
+
+        self.add_derived(name, vec![a, b], |v| angular_difference(v[0], v[1]));
+    }
+
+    fn add_anemometer_comparison(&mut self) {
+        let (a, b) = self.comparison;
+        if a == b || a >= self.timeseries.len() || b >= self.timeseries.len() {
+            self.msg = "Select two different anemometers".to_owned();
+            return;
+        }
+
+        let operator = if self.comparison_ratio { "/" } else { "-" };
+        let name = format!(
+            "{} {} {}",
+            self.timeseries[a].name, operator, self.timeseries[b].name
+        );
+        if self.timeseries.iter().any(|ts| ts.name == name) {
+            self.msg = format!("Channel '{}' already exists", name);
+            return;
+        }
+
+        match self.comparison_ratio {
+            true => self.add_derived(name, vec![a, b], |v| v[0] / v[1]),
+            false => self.add_derived(name, vec![a, b], |v| v[0] - v[1]),
+        }
+    }
+
+    // A channel written in an expression: by its full name, or by its sensor
+    // when only one channel, or one mean, has it
+    fn find_channel(&self, name: &str) -> Result<usize, String> {
+        if let Some(n) = self.timeseries.iter().position(|ts| ts.name == name) {
+            return Ok(n);
+        }
+        let matches: Vec<usize> = (0..self.timeseries.len())
+            .filter(|&n| {
+                self.mast_sensor(&self.timeseries[n].name)
+                    .is_ok_and(|(_, sensor)| sensor == name)
+            })
+            .collect();
+        let means: Vec<usize> = matches
+            .iter()
+            .copied()
+            .filter(|&n| {
+                self.settings
+                    .name_convention
+                    .split_statistic(&self.timeseries[n].name)
+                    .map_or(true, |(_, s)| s == Statistic::Mean)
+            })
+            .collect();
+        match (matches.as_slice(), means.as_slice()) {
+            ([n], _) | (_, [n]) => Ok(*n),
+            ([], _) => Err(format!("No channel '{}'", name)),
+            _ => Err(format!(
+                "'{}' matches several channels, write its full name",
+                name
+            )),
+        }
+    }
+
+    fn add_expression_channel(&mut self) {
+        let expression = match Expression::parse(&self.expression) {
+            Ok(expression) => expression,
+            Err(e) => {
+                self.msg = format!("Expression error: {}", e);
+                return;
+            }
+        };
+        let sources = match expression
+            .variables
+            .iter()
+            .map(|name| self.find_channel(name))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(sources) => sources,
+            Err(e) => {
+                self.msg = format!("Expression error: {}", e);
+                return;
+            }
+        };
+        let name = match &expression.name {
+            Some(name) => name.clone(),
+            None => self.expression.trim().to_owned(),
+        };
+        if self.timeseries.iter().any(|ts| ts.name == name) {
+            self.msg = format!("Channel '{}' already exists", name);
+            return;
+        }
+
+        self.add_derived(name, sources, move |values| expression.eval(values));
+    }
+
+    // Adds a channel computed row by row from others, given their values in
+    // order. Values that can't be computed, like ratios to zero, are missing.
+    fn add_derived(&mut self, name: String, sources: Vec<usize>, f: impl Fn(&[f64]) -> f64) {
+        for &n in &sources {
+            self.materialize(n);
+        }
+        let mut values = vec![0.0; sources.len()];
+        // Rows already excluded in any source start out excluded
+        let data = (0..self.index.len())
+            .map(|row| {
+                let mut reason = None;
+                for (value, &n) in values.iter_mut().zip(&sources) {
+                    match self.timeseries[n].data.get(row) {
+                        Some(DataPoint::Valid(v)) => *value = v,
+                        Some(DataPoint::Excluded(v, excluded)) => {
+                            *value = v;
+                            reason = reason.or(Some(excluded));
+                        }
+                        _ => return DataPoint::NaN,
+                    }
+                }
+                match (f(&values), reason) {
+                    (v, _) if !v.is_finite() => DataPoint::NaN,
+                    (v, Some(reason)) => DataPoint::Excluded(v, reason),
+                    (v, None) => DataPoint::Valid(v),
+                }
+            })
+            .collect();
+
+        self.timeseries.push(TimeSeries {
+            name: name.clone(),
+            data,
+            sources,
+            column: None,
+        });
+        self.msg = format!("Channel '{}' added", name);
+        self.audit.record(format!("Added channel '{}'", name));
+    }
+
+    fn check_editable(&mut self) -> bool {
+        if self.read_only {
+            self.msg = "Read-only review mode: exclusions can't be modified".to_owned();
+        }
+        !self.read_only
+    }
+
+    fn take_snapshot(&mut self, label: &str) {
+        let exclusions = self
+            .timeseries
+            .iter()
+            .enumerate()
+            .flat_map(|(series, ts)| {
+                ts.data
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(row, val)| match val {
+                        DataPoint::Excluded(..) => Some((series, row, ts.data.stamp(row)?.clone())),
+                        _ => None,
+                    })
+            })
+            .collect();
+
+        self.snapshots.push(Snapshot {
+            label: label.to_owned(),
+            taken_at: Local::now().naive_local(),
+            exclusions,
+        });
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.remove(0);
+        }
+        self.operations_since_snapshot = 0;
+    }
+
+    fn selection(&self) -> Selection {
+        (self.exclusion_curve.clone(), self.exclusion_curve_is_closed)
+    }
+
+    // Counts an operation on the exclusions, snapshotting every `snapshot_every`
+    // of them, and adds it to the undo history
+    fn record_operation(&mut self, label: &str, selection_before: Selection) {
+        self.push_action(label, selection_before);
+        self.operations_since_snapshot += 1;
+        if self.snapshot_every > 0 && self.operations_since_snapshot >= self.snapshot_every {
+            let label = format!("After {} operations", self.operations_since_snapshot);
+            self.take_snapshot(&label);
+        }
+    }
+
+    // Adds the points excluded since the last action, along with the selection
+    // change, to the undo history
+    fn push_action(&mut self, label: &str, selection_before: Selection) {
+        let exclusions = std::mem::take(&mut self.pending_exclusions);
+        let restorations = std::mem::take(&mut self.pending_restorations);
+        let selection = [selection_before, self.selection()];
+        if exclusions.is_empty() && restorations.is_empty() && selection[0] == selection[1] {
+            return;
+        }
+
+        self.unsaved_changes |= !exclusions.is_empty() || !restorations.is_empty();
+        self.undo_stack.push(ExclusionAction {
+            label: label.to_owned(),
+            exclusions,
+            restorations,
+            selection,
+        });
+        if self.undo_stack.len() > MAX_UNDO {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+    }
+
+    fn clear_history(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.pending_exclusions.clear();
+        self.pending_restorations.clear();
+    }
+
+    // Reverts the last action, or reapplies the last undone one
+    fn undo(&mut self, redo: bool) {
+        if !self.check_editable() {
+            return;
+        }
+        let (from, to) = match redo {
+            false => (&mut self.undo_stack, &mut self.redo_stack),
+            true => (&mut self.redo_stack, &mut self.undo_stack),
+        };
+        let Some(action) = from.pop() else {
+            self.msg = format!("Nothing to {}", if redo { "redo" } else { "undo" });
+            return;
+        };
+
+        self.revision += 1;
+        self.unsaved_changes = true;
+        // Restorations are exclusions undone
+        let changes = action
+            .exclusions
+            .iter()
+            .map(|change| (change, redo))
+            .chain(action.restorations.iter().map(|change| (change, !redo)));
+        for ((series, row, stamp), exclude) in changes {
+            let Some(ts) = self.timeseries.get_mut(*series) else {
+                continue;
+            };
+            match exclude {
+                true => ts.data.exclude(*row, stamp),
+                false => ts.data.restore(*row),
+            };
+        }
+        (self.exclusion_curve, self.exclusion_curve_is_closed) =
+            action.selection[redo as usize].clone();
+
+        self.msg = format!(
+            "{} '{}'",
+            if redo { "Redone" } else { "Undone" },
+            action.label
+        );
+        self.audit.record(self.msg.clone());
+        to.push(action);
+    }
+
+    fn clear_selection(&mut self) {
+        let selection = self.selection();
+        self.time_range_start = None;
+        self.exclusion_curve.clear();
+        self.exclusion_curve_is_closed = false;
+        self.push_action("Clear selection", selection);
+    }
+
+    fn run_shortcut(&mut self, action: Action) {
+        let channels = self.timeseries.len();
+        match action {
+            Action::Load => self.pick_data_file(),
+            Action::Exclude | Action::RepeatReason if !self.file_loaded => {}
+            Action::Exclude => self.timed("exclusion", |app| app.exclude_data()),
+            Action::RepeatReason => self.timed("exclusion", |app| app.exclude_with_last_reason()),
+            Action::ClearSelection => self.clear_selection(),
+            Action::ToggleExcluded => self.show_excluded = !self.show_excluded,
+            Action::SwapAxes if self.plot_mode == PlotMode::Scatter => {
+                std::mem::swap(&mut self.xaxis, &mut self.yaxis)
+            }
+            Action::SwapAxes => self.msg = "Axes can only be swapped in scatter mode".into(),
+            Action::NextChannel | Action::PreviousChannel if channels == 0 => {}
+            Action::NextChannel => self.yaxis = (self.yaxis + 1) % channels,
+            Action::PreviousChannel => self.yaxis = (self.yaxis + channels - 1) % channels,
+            Action::Undo => self.undo(false),
+            Action::Redo => self.undo(true),
+            Action::Help => self.show_shortcuts = !self.show_shortcuts,
+        }
+    }
+
+    fn restore_snapshot(&mut self, n: usize) {
+        if !self.check_editable() {
+            return;
+        }
+
+        self.revision += 1;
+        for ts in self.timeseries.iter_mut() {
+            for row in 0..ts.data.len() {
+                ts.data.restore(row);
+            }
+        }
+
+        // The history doesn't apply on top of a different state
+        self.clear_history();
+
+        let snapshot = &self.snapshots[n];
+        for (series, row, stamp) in &snapshot.exclusions {
+            if let Some(ts) = self.timeseries.get_mut(*series) {
+                ts.data.exclude(*row, stamp);
+            }
+        }
+
+        self.msg = format!(
+            "Restored snapshot '{}' from {}",
+            snapshot.label,
+            snapshot.taken_at.format("%H:%M:%S")
+        );
+        self.audit.record(self.msg.clone());
+    }
+
+    // The mean, std, min and max channels of the same sensor as the given one,
+    // itself included
+    fn sensor_group(&self, series: usize) -> Vec<usize> {
+        let ts = &self.timeseries[series];
+        let Some((base, _)) = self
+            .settings
+            .name_convention
+            .split_statistic(&ts.name)
+            .filter(|_| !ts.derived())
+        else {
+            return vec![series];
+        };
+
+        (0..self.timeseries.len())
+            .filter(|&n| {
+                let other = &self.timeseries[n];
+                !other.derived()
+                    && self
+                        .settings
+                        .name_convention
+                        .split_statistic(&other.name)
+                        .is_some_and(|(b, _)| b == base)
+            })
+            .collect()
+    }
+
+    fn sensor_group_label(&self, series: usize) -> String {
+        let statistics: Vec<&str> = self
+            .sensor_group(series)
+            .into_iter()
+            .filter_map(|n| {
+                self.settings
+                    .name_convention
+                    .split_statistic(&self.timeseries[n].name)
+            })
+            .map(|(_, statistic)| statistic.label())
+            .collect();
+        match statistics.len() {
+            0 | 1 => "No mean/std/min/max group".to_owned(),
+            _ => format!("Sensor group: {}", statistics.join(", ")),
+        }
+    }
+
+    // Excludes rows of a channel and, unless disabled, of the rest of its
+    // sensor group, so no statistic of an excluded period stays valid
+    fn exclude_with_group(&mut self, axis: usize, rows: &[bool], reason: &str) {
+        let members = if self.exclude_sensor_group {
+            self.sensor_group(axis)
+        } else {
+            vec![axis]
+        };
+
+        for member in members {
+            self.materialize(member);
+            let count = self.exclude_timeseries_data(member, rows, reason);
+            self.audit.record(format!(
+                "Excluded {} points of {} by '{}'",
+                count, self.timeseries[member].name, reason
+            ));
+        }
+    }
+
+    fn exclude_timeseries_data(
+        &mut self,
+        axis: usize,
+        is_inside_curve: &[bool],
+        reason: &str,
+    ) -> usize {
+        self.revision += 1;
+        let stamp = Stamp {
+            reason: reason.to_owned(),
+            category: self.category,
+            analyst: self.analyst.trim().to_owned(),
+        };
+        let changed = exclude_rows(&mut self.timeseries[axis].data, is_inside_curve, &stamp);
+        let count = changed.len();
+        self.pending_exclusions
+            .extend(changed.into_iter().map(|row| (axis, row, stamp.clone())));
+
+        // Keep the channels computed from this one consistent with it
+        let dependents: Vec<usize> = (0..self.timeseries.len())
+            .filter(|&n| self.timeseries[n].sources.contains(&axis))
+            .collect();
+        for dependent in dependents {
+            self.exclude_timeseries_data(dependent, is_inside_curve, reason);
+        }
+
+        count
+    }
+
+    // Turns the excluded points of the given rows back into valid ones, in the
+    // channels computed from this one too. A derived point stays excluded
+    // while any of its sources is.
+    fn restore_timeseries_data(&mut self, axis: usize, rows: &[bool]) -> usize {
+        let sources = &self.timeseries[axis].sources;
+        let rows: Vec<bool> = rows
+            .iter()
+            .enumerate()
+            .map(|(row, &selected)| {
+                selected
+                    && sources.iter().all(|&source| {
+                        !matches!(
+                            self.timeseries[source].data.get(row),
+                            Some(DataPoint::Excluded(..))
+                        )
+                    })
+            })
+            .collect();
+
+        let mut count = 0;
+        self.revision += 1;
+        let data = &mut self.timeseries[axis].data;
+        for row in (0..data.len()).filter(|&row| rows.get(row) == Some(&true)) {
+            if let Some(stamp) = data.stamp(row) {
+                self.pending_restorations.push((axis, row, stamp.clone()));
+                data.restore(row);
+                count += 1;
+            }
+        }
+
+        let dependents: Vec<usize> = (0..self.timeseries.len())
+            .filter(|&n| self.timeseries[n].sources.contains(&axis))
+            .collect();
+        for dependent in dependents {
+            self.restore_timeseries_data(dependent, &rows);
+        }
+
+        count
+    }
+
+    fn restore_with_group(&mut self, axis: usize, rows: &[bool]) {
+        let members = if self.exclude_sensor_group {
+            self.sensor_group(axis)
+        } else {
+            vec![axis]
+        };
+
+        for member in members {
+            self.materialize(member);
+            let count = self.restore_timeseries_data(member, rows);
+            self.audit.record(format!(
+                "Restored {} points of {}",
+                count, self.timeseries[member].name
+            ));
+        }
+    }
+
+    // Restores the excluded points inside the closed selection, in the same
+    // channels an exclusion would apply to
+    fn restore_data(&mut self) {
+        if !self.check_editable() {
+            return;
+        }
+        if !self.exclusion_curve_is_closed || self.exclusion_curve.len() < 3 {
+            self.msg = "Close a selection around the points to restore".to_owned();
+            return;
+        }
+
+        let selection = self.selection();
+        let mut yaxes = vec![self.yaxis];
+        if self.exclude_extra_y {
+            yaxes.extend(self.plotted_extra_yaxes());
+        }
+
+        // Excluded points are placed where they were before their exclusion
+        let offsets = seam_offsets(self.direction_axes());
+        let mut inside_any = vec![false; self.index.len()];
+        let mut inside_per_yaxis = Vec::new();
+        for yaxis in yaxes {
+            let data = self.process_points(yaxis, |x, y| match (x, y) {
+                (
+                    DataPoint::Valid(x) | DataPoint::Excluded(x, _),
+                    DataPoint::Valid(y) | DataPoint::Excluded(y, _),
+                ) => Some([x, y]),
+                _ => Some([f64::NAN, f64::NAN]),
+            });
+            let is_inside =
+                check_inside_curve_with_offsets(self.exclusion_curve.clone(), data, &offsets);
+            for (any, inside) in inside_any.iter_mut().zip(&is_inside) {
+                *any |= inside;
+            }
+            inside_per_yaxis.push((yaxis, is_inside));
+        }
+
+        self.restore_selected(inside_per_yaxis, &inside_any);
+
+        let count = self.pending_restorations.len();
+        self.exclusion_curve.clear();
+        self.exclusion_curve_is_closed = false;
+        self.msg = format!("{} excluded points restored", count);
+        self.record_operation("Restore", selection);
+    }
+
+    // Restores the rows selected in the same channels `exclude_selected` would
+    // exclude them from. Sources go before the derived channels, which are
+    // only restored where their sources are valid again.
+    fn restore_selected(&mut self, inside_per_yaxis: Vec<(usize, Vec<bool>)>, inside_any: &[bool]) {
+        if self.excludex && self.plot_mode == PlotMode::Scatter {
+            self.restore_with_group(self.xaxis, inside_any);
+        }
+
+        if self.excludey {
+            for (yaxis, is_inside) in inside_per_yaxis {
+                let sources = self.timeseries[yaxis].sources.clone();
+                for source in sources {
+                    if self.exclude_sources.contains(&source) {
+                        self.restore_with_group(source, &is_inside);
+                    }
+                }
+                self.restore_with_group(yaxis, &is_inside);
+            }
+        }
+
+        for target in self.other_targets() {
+            self.restore_with_group(target, inside_any);
+        }
+    }
+
+    // The other channels chosen to follow the selected timestamps
+    fn other_targets(&self) -> Vec<usize> {
+        let mut targets: Vec<usize> = self
+            .exclude_targets
+            .iter()
+            .copied()
+            .filter(|&n| n < self.timeseries.len())
+            .collect();
+        if self.exclude_same_height {
+            targets.extend(self.same_height_channels(self.yaxis));
+        }
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+
+    // Measured channels on the same mast and at the same configured height as
+    // the given one, itself excluded
+    fn same_height_channels(&self, series: usize) -> Vec<usize> {
+        let name = &self.timeseries[series].name;
+        let (Ok((mast, _)), Some(height)) = (
+            self.mast_sensor(name),
+            self.sensor_config.get(name).and_then(|info| info.height),
+        ) else {
+            return Vec::new();
+        };
+
+        (0..self.timeseries.len())
+            .filter(|&n| n != series && !self.timeseries[n].derived())
+            .filter(|&n| {
+                let other = &self.timeseries[n].name;
+                self.mast_sensor(other).is_ok_and(|(m, _)| m == mast)
+                    && self.sensor_config.get(other).and_then(|info| info.height) == Some(height)
+            })
+            .collect()
+    }
+
+    fn exclude_data(&mut self) {
+        self.exclude_data_with(self.reason.clone());
+    }
+
+    // Applies the closed selection with the reason used last, leaving the
+    // reason field as it is
+    fn exclude_with_last_reason(&mut self) {
+        if self.last_reason.is_empty() {
+            self.msg = "No exclusion reason used yet".to_owned();
+        } else {
+            self.exclude_data_with(self.last_reason.clone());
+        }
+    }
+
+    // Whether the selection was excluded
+    fn exclude_data_with(&mut self, reason: String) -> bool {
+        if !self.check_editable() {
+            return false;
+        }
+
+        if reason.is_empty() {
+            self.msg = "Write a reason for exclusion".to_owned();
+        } else if self.exclusion_curve.len() < 3 {
+            self.msg = "At least 3 points are needed to define an exclusion area".to_owned();
+        } else if !self.exclusion_curve_is_closed {
+            self.msg = "The exclusion area must be closed".to_owned();
+        } else {
+            let selection = self.selection();
+            let mut yaxes = vec![self.yaxis];
+            if self.exclude_extra_y {
+                yaxes.extend(self.plotted_extra_yaxes());
+            }
+
+            let offsets = seam_offsets(self.direction_axes());
+            let inside_per_yaxis = yaxes
+                .into_iter()
+                .map(|yaxis| {
+                    let curve = self.exclusion_curve.clone();
+                    let data = self.convert_points(yaxis);
+                    (
+                        yaxis,
+                        check_inside_curve_with_offsets(curve, data, &offsets),
+                    )
+                })
+                .collect();
+            self.exclude_selected(inside_per_yaxis, &reason);
+
+            self.exclusion_curve.clear();
+            self.exclusion_curve_is_closed = false;
+            self.msg = format!("Data excluded by '{}' reason", reason).to_owned();
+            self.record_operation(&format!("Exclusion by '{}'", reason), selection);
+            self.last_reason = reason;
+            return true;
+        }
+        false
+    }
+
+    // Excludes the rows selected in each y channel, and as the options say, in
+    // the x channel, the sources of derived channels and the other targets
+    fn exclude_selected(&mut self, inside_per_yaxis: Vec<(usize, Vec<bool>)>, reason: &str) {
+        let mut inside_any = vec![false; self.index.len()];
+        for (_, is_inside) in &inside_per_yaxis {
+            for (any, inside) in inside_any.iter_mut().zip(is_inside) {
+                *any |= inside;
+            }
+        }
+
+        self.register_reason(reason);
+
+        if self.excludex && self.plot_mode == PlotMode::Scatter {
+            self.exclude_with_group(self.xaxis, &inside_any, reason);
+        }
+
+        if self.excludey {
+            for (yaxis, is_inside) in inside_per_yaxis {
+                self.exclude_with_group(yaxis, &is_inside, reason);
+                // A derived channel passes them on to the chosen sources
+                let sources = self.timeseries[yaxis].sources.clone();
+                for source in sources {
+                    if self.exclude_sources.contains(&source) {
+                        self.exclude_with_group(source, &is_inside, reason);
+                    }
+                }
+            }
+        }
+
+        // The same timestamps in the other chosen channels
+        for target in self.other_targets() {
+            self.exclude_with_group(target, &inside_any, reason);
+        }
+    }
+
+    // Excludes the single plotted point nearest to where Ctrl+click landed,
+    // when it's within reach
+    fn exclude_picked(&mut self, target: [f64; 2], scale: [f64; 2]) {
+        if !self.check_editable() {
+            return;
+        }
+        let reason = self.reason.clone();
+        if reason.is_empty() {
+            self.msg = "Write a reason for exclusion".to_owned();
+            return;
+        }
+
+        let mut yaxes = vec![self.yaxis];
+        yaxes.extend(self.plotted_extra_yaxes());
+        let nearest = yaxes
+            .into_iter()
+            .filter_map(|yaxis| {
+                let points = self.convert_points(yaxis);
+                let row = nearest_index(&points, &target, scale)?;
+                Some((yaxis, row, scaled_distance(&points[row], &target, scale)))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+        let Some((yaxis, row, _)) = nearest.filter(|(_, _, d)| *d < PICK_DISTANCE_PIXELS) else {
+            self.msg = "No point close enough to pick".to_owned();
+            return;
+        };
+
+        let selection = self.selection();
+        let mut picked = vec![false; self.index.len()];
+        picked[row] = true;
+        self.exclude_selected(vec![(yaxis, picked)], &reason);
+
+        self.msg = format!(
+            "Point of '{}' at {} excluded by '{}' reason",
+            self.timeseries[yaxis].name, self.index[row], reason
+        );
+        self.record_operation(&format!("Point exclusion by '{}'", reason), selection);
+        self.last_reason = reason;
+    }
+}
+
+impl eframe::App for ManualDataCleanerApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, REASON_COLORS_KEY, &self.reason_colors);
+        eframe::set_value(storage, ANALYST_KEY, &self.analyst);
+        eframe::set_value(storage, SETTINGS_KEY, &self.settings);
+        eframe::set_value(storage, THEME_KEY, &self.theme);
+        eframe::set_value(storage, MISSING_KEY, &self.missing);
+        eframe::set_value(storage, TIME_BUFFER_KEY, &self.time_buffer);
+        eframe::set_value(storage, CHOOSE_COLUMNS_KEY, &self.choose_columns);
+        eframe::set_value(storage, EXPORT_LAYOUT_KEY, &self.export_layout);
+        eframe::set_value(storage, LAST_DIR_KEY, &self.last_dir);
+        self.remember_axes();
+        eframe::set_value(storage, AXES_KEY, &self.saved_axes);
+    }
+
+    fn post_rendering(&mut self, _window_size_px: [u32; 2], frame: &eframe::Frame) {
+        let Some((path, min, max)) = self.pending_plot_image.take() else {
+            return;
+        };
+        self.msg = match frame.screenshot() {
+            Some(image) => match write_png(&path, &image, min, max) {
+                Ok(()) => format!("Plot saved to {}", path.display()),
+                Err(e) => format!("Image error: {}", e),
+            },
+            None => "The screenshot of the plot failed".to_owned(),
+        };
+    }
+
+    fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
+        let system_dark = frame.info().system_theme.map(|t| t == eframe::Theme::Dark);
+        self.apply_ui_theme(ctx, system_dark);
+        if self.license_expired() {
+            self.show_expired_screen(ctx, frame);
+            return;
+        }
+        self.poll_load(ctx);
+        self.show_license_banner(ctx);
+
+        // A data file dropped on the window loads as if picked with Load File,
+        // once confirmed when there are unsaved exclusions
+        let dropped = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .first()
+                .and_then(|file| file.path.clone())
+        });
+        if let (Some(path), false) = (dropped, self.presentation) {
+            let path = path.display().to_string();
+            match self.unsaved_changes {
+                true => self.pending_drop = Some(path),
+                false => self.open_data_file(path),
+            }
+        }
+
+        eframe::egui::SidePanel::left("left_panel")
+            .show_separator_line(true)
+            .show_animated(ctx, self.show_left_panel, |ui| {
+                eframe::egui::Grid::new("left_grid")
+                    .striped(false)
+                    .num_columns(3)
+                    .spacing([10.0, 10.0])
+                    .show(ui, |ui| {
+                        ui.end_row();
+
+                        ui.label("Presentation");
+                        let presentation = ui.checkbox(&mut self.presentation, "Presentation mode");
+                        if presentation.changed() {
+                            set_presentation_fonts(ctx, self.presentation);
+                        }
+                        ui.checkbox(&mut self.show_hud, "Performance HUD");
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        ui.checkbox(&mut self.level_of_detail, "Level of detail").on_hover_text(
+                            "Draw large datasets decimated to the plot resolution; selections still use every point",
+                        );
+                        ui.end_row();
+
+                        if !self.presentation {
+                            ui.label("Review mode");
+                            ui.add_enabled(
+                                !self.read_only_locked,
+                                egui::Checkbox::new(&mut self.read_only, "Read-only"),
+                            );
+                            if self.read_only {
+                                ui.label(
+                                    egui::RichText::new("Exclusions locked")
+                                        .color(egui::Color32::from_rgb(255, 200, 100)),
+                                );
+                            }
+                            ui.end_row();
+
+                            ui.label("Missing values");
+                            let missing = ui
+                                .add_sized(
+                                    [100., 20.],
+                                    TextEdit::singleline(&mut self.missing_text).hint_text("99999, -999"),
+                                )
+                                .on_hover_text("Values read as missing, besides blank and NaN cells");
+                            if missing.changed() {
+                                match parse_markers(&self.missing_text) {
+                                    Ok(markers) => self.missing = markers,
+                                    Err(e) => self.msg = e,
+                                }
+                            }
+                            let load_button = ui
+                                .add_sized([100., 20.], Button::new("Load File"))
+                                .on_hover_text(self.settings.shortcuts.label(Action::Load));
+                            if load_button.clicked() {
+                                self.pick_data_file();
+                            }
+
+                            ui.end_row();
+
+                            ui.label(""); // dummy row
+                            ui.checkbox(&mut self.choose_columns, "Choose columns")
+                                .on_hover_text("Pick the columns to load from each text file opened");
+                            ui.end_row();
+
+                            ui.label("Repeated times");
+                            ComboBox::new("duplicate policy", "")
+                                .selected_text(self.duplicate_policy.label())
+                                .show_ui(ui, |ui| {
+                                    for policy in DuplicatePolicy::ALL {
+                                        ui.selectable_value(&mut self.duplicate_policy, policy, policy.label());
+                                    }
+                                })
+                                .response
+                                .on_hover_text("What is kept of the rows sharing a timestamp when a file is loaded");
+                            ui.end_row();
+
+                            ui.label(""); // dummy row
+                            ui.checkbox(&mut self.skip_malformed, "Skip malformed rows").on_hover_text(
+                                "Leave out the rows with cells that are not numbers, instead of reading those cells as missing. The whole file is checked at load, otherwise each column is checked when first used.",
+                            );
+                            let report_button = ui.add_enabled(
+                                self.load_report.as_ref().is_some_and(LoadReport::has_findings),
+                                Button::new("Load report"),
+                            );
+                            if report_button.clicked() {
+                                self.show_load_report = true;
+                            }
+                            ui.end_row();
+
+                            ui.label("Session");
+                            let open_button = ui.add_sized([100., 20.], Button::new("Open session"));
+                            if open_button.clicked() {
+                                if let Some(path) = self.file_dialog()
+                                    .add_filter("Project", &["json"])
+                                    .pick_file()
+                                {
+                                    self.msg = match self.timed("load", |app| app.open_project(&path)) {
+                                        Ok(()) => "Session restored successfully".into(),
+                                        Err(e) => format!("Session error: {}", e),
+                                    };
+                                    self.audit.record(format!("Open session {}: {}", path.display(), self.msg));
+                                } else {
+                                    self.msg = "No file selected.".into();
+                                }
+                            }
+                            let save_button = ui.add_enabled(
+                                self.file_loaded,
+                                Button::new("Save session").min_size([100., 20.].into()),
+                            );
+                            if save_button.clicked() {
+                                if let Some(path) = self.file_dialog()
+                                    .add_filter("Project", &["json"])
+                                    .save_file()
+                                {
+                                    self.audit.record(format!("Save session {}", path.display()));
+                                    self.msg = match self.timed("export", |app| app.save_project(&path)) {
+                                        Ok(()) => {
+                                            self.unsaved_changes = false;
+                                            "Session saved successfully".into()
+                                        }
+                                        Err(e) => format!("Session error: {}", e),
+                                    };
+                                } else {
+                                    self.msg = "No file selected.".into();
+                                }
+                            }
+                            ui.end_row();
+                        }
 
                         ui.label("Loaded file");
-                        ui.label(if self.file_path.is_empty() {
-                            "No file selected"
+                        let current = match self.file_path.is_empty() {
+                            true => "No file selected".to_owned(),
+                            false => file_label(&self.file_path),
+                        };
+                        if self.other_files.is_empty() {
+                            ui.label(current).on_hover_text("Or drop a data file on the window");
                         } else {
-                            self.file_path
-                                .split("\\")
-                                .last()
-                                .unwrap_or("No file selected")
+                            let mut switch_to = None;
+                            ComboBox::new("Select file", "")
+                                .selected_text(current)
+                                .show_ui(ui, |ui| {
+                                    for (n, file) in self.other_files.iter().enumerate() {
+                                        if ui.selectable_label(false, file_label(&file.file_path)).clicked() {
+                                            switch_to = Some(n);
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Switch to another file of the session");
+                            if let Some(n) = switch_to {
+                                self.switch_file(n);
+                            }
+                        }
+                        let add_button = ui
+                            .add_enabled(self.file_loaded && !self.presentation, Button::new("Add file").min_size([100., 20.].into()))
+                            .on_hover_text("Load another file, e.g. of another mast, keeping this one. Exports cover every file.");
+                        if add_button.clicked() {
+                            if let Some(path) = self.file_dialog().pick_file() {
+                                self.add_data_file(path.display().to_string());
+                            } else {
+                                self.msg = "No file selected.".into();
+                            }
+                        }
+                        ui.end_row();
+                        ui.end_row();
+
+                        let mut options: Vec<String> = (0..self.timeseries.len())
+                            .map(|n| self.channel_label(n))
+                            .collect();
+
+                        if options.is_empty() {
+                            options.push("".to_string());
+                            options.push("".to_string());
+                        } else if options.len() > 1 && !self.file_loaded {
+                            self.xaxis = 0;
+                            self.yaxis = 1;
+                        }
+
+                        ui.label("Plot mode");
+                        ComboBox::new("Select plot mode", "")
+                            .selected_text(match self.plot_mode {
+                                PlotMode::Scatter => "Scatter",
+                                PlotMode::TimeSeries => "Time series",
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut self.plot_mode,
+                                    PlotMode::Scatter,
+                                    "Scatter",
+                                );
+                                ui.selectable_value(
+                                    &mut self.plot_mode,
+                                    PlotMode::TimeSeries,
+                                    "Time series",
+                                );
+                            })
+                            .response
+                            .on_hover_text("In time series mode, T+click the start and end of a time range to select it");
+                        let plot_window = ui.add_enabled(
+                            self.file_loaded,
+                            Button::new("Linked plot").min_size([100., 20.].into()),
+                        );
+                        let plot_window = plot_window.on_hover_text(
+                            "A second plot linked to this one, in a panel that can be moved anywhere in the application window",
+                        );
+                        if plot_window.clicked() {
+                            self.window_yaxis = self.yaxis;
+                            self.show_plot_window = true;
+                        }
+                        ui.end_row();
+
+                        let scatter = self.plot_mode == PlotMode::Scatter;
+                        ui.label("X-axis");
+                        ui.add_enabled_ui(scatter, |ui| {
+                            ComboBox::new("Select x axis", "")
+                                .selected_text(&options[self.xaxis])
+                                .show_ui(ui, |ui| {
+                                    for (index, option) in options.iter().enumerate() {
+                                        if ui
+                                            .selectable_value(&mut self.xaxis, index, option)
+                                            .clicked()
+                                        {
+                                            self.xaxis = index;
+                                        }
+                                    }
+                                });
+                        });
+
+                        ui.add_enabled(
+                            scatter,
+                            egui::Checkbox::new(&mut self.excludex, "Exclude x axis"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Y-axis");
+                        let group_label = match self.file_loaded {
+                            true => {
+                                let mut label = self.sensor_group_label(self.yaxis);
+                                if let Some(calibration) = self.calibration_label(self.yaxis) {
+                                    label = format!("{}\n{}", label, calibration);
+                                }
+                                label
+                            }
+                            false => String::new(),
+                        };
+                        ComboBox::new("Select y axis", "")
+                            .selected_text(&options[self.yaxis])
+                            .show_ui(ui, |ui| {
+                                for (index, option) in options.iter().enumerate() {
+                                    if ui
+                                        .selectable_value(&mut self.yaxis, index, option)
+                                        .clicked()
+                                    {
+                                        self.yaxis = index;
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text(group_label);
+
+                        ui.checkbox(&mut self.excludey, "Exclude y axis");
+                        ui.end_row();
+
+                        ui.label("Extra Y-axes");
+                        ui.menu_button(format!("{} selected", self.extra_yaxes.len()), |ui| {
+                            for (index, option) in options.iter().enumerate() {
+                                let mut selected = self.extra_yaxes.contains(&index);
+                                if ui.checkbox(&mut selected, option).changed() {
+                                    if selected {
+                                        self.extra_yaxes.push(index);
+                                    } else {
+                                        self.extra_yaxes.retain(|&y| y != index);
+                                    }
+                                }
+                            }
+                        });
+                        ui.checkbox(&mut self.exclude_extra_y, "Exclude extra y axes");
+                        ui.end_row();
+
+                        ui.label("Color by");
+                        ComboBox::new("Select color by", "")
+                            .selected_text(self.color_by.map_or("None", |n| options[n].as_str()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.color_by, None, "None");
+                                for (index, option) in options.iter().enumerate() {
+                                    ui.selectable_value(&mut self.color_by, Some(index), option);
+                                }
+                            })
+                            .response
+                            .on_hover_text("Color the y axis points along a gradient of this channel's value");
+                        if let Some(n) = self.color_by {
+                            if let Some((min, max)) = self.color_range(n) {
+                                ui.label(format!("{:.1} to {:.1} {}", min, max, self.channel_units(n)));
+                            }
+                        }
+                        ui.end_row();
+
+                        ui.label("Also exclude");
+                        ui.menu_button(format!("{} selected", self.exclude_targets.len()), |ui| {
+                            for (index, option) in options.iter().enumerate() {
+                                let mut selected = self.exclude_targets.contains(&index);
+                                if ui.checkbox(&mut selected, option).changed() {
+                                    if selected {
+                                        self.exclude_targets.push(index);
+                                    } else {
+                                        self.exclude_targets.retain(|&t| t != index);
+                                    }
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("Channels excluded at the same timestamps as the selection");
+                        ui.checkbox(&mut self.exclude_same_height, "Same mast and height")
+                            .on_hover_text("Also exclude every channel of the y axis mast at its height, from the sensor configuration");
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        ui.checkbox(&mut self.show_stats, "Statistics summary")
+                            .on_hover_text("Statistics and recovery of the plotted channels as cleaned so far");
+                        let coverage_button = ui
+                            .add_enabled(self.file_loaded, Button::new("Coverage").min_size([100., 20.].into()))
+                            .on_hover_text("Share of valid records of every channel per month or day");
+                        if coverage_button.clicked() {
+                            self.show_coverage = true;
+                        }
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        let gaps_button = ui
+                            .add_enabled(self.file_loaded, Button::new("Timestamp gaps").min_size([100., 20.].into()))
+                            .on_hover_text("Periods missing from the index, and steps off its cadence");
+                        if gaps_button.clicked() {
+                            self.show_gaps = true;
+                        }
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        let report_button = ui
+                            .add_enabled(self.file_loaded, Button::new("Generate report").min_size([100., 20.].into()))
+                            .on_hover_text(format!(
+                                "HTML summary of the session: recovery per channel, exclusions and {} plots added",
+                                self.report_plots.len()
+                            ));
+                        if report_button.clicked() {
+                            let file_name = format!(
+                                "{}_report.html",
+                                Path::new(&self.file_path).file_stem().unwrap_or_default().to_string_lossy()
+                            );
+                            if let Some(path) = self.file_dialog().set_file_name(file_name).add_filter("HTML", &["html"]).save_file() {
+                                self.msg = match self.generate_report(&path) {
+                                    Ok(()) => format!("Report written to {}", path.display()),
+                                    Err(e) => format!("Report error: {}", e),
+                                };
+                                self.audit.record(self.msg.clone());
+                            }
+                        }
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        let matrix_button = ui
+                            .add_enabled(self.file_loaded, Button::new("Scatter matrix").min_size([100., 20.].into()))
+                            .on_hover_text("Small scatter plots of every pair of the chosen channels, to spot the pairs worth a closer look");
+                        if matrix_button.clicked() {
+                            self.show_matrix = true;
+                        }
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        ui.checkbox(&mut self.concurrent_only, "Only concurrent data")
+                            .on_hover_text("Plot only the timestamps where every plotted channel is valid");
+                        if self.file_loaded && !self.index.is_empty() {
+                            let concurrent = self.concurrent_rows().iter().filter(|&&c| c).count();
+                            ui.label(format!(
+                                "Concurrent recovery {:.1}%",
+                                100.0 * concurrent as f64 / self.index.len() as f64
+                            ));
+                        }
+                        ui.end_row();
+
+                        ui.label("Vertical profile");
+                        ui.label(match self.pinned_row.filter(|&row| row < self.index.len()) {
+                            Some(row) => format!("Pinned at {}", self.index[row]),
+                            None => String::new(),
+                        });
+                        let profile_button = ui.add_enabled(
+                            self.file_loaded,
+                            Button::new("Show profile").min_size([100., 20.].into()),
+                        );
+                        if profile_button.clicked() {
+                            self.show_profile = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Shear profile");
+                        ui.label("");
+                        let shear_button = ui
+                            .add_enabled(self.file_loaded, Button::new("Show shear").min_size([100., 20.].into()))
+                            .on_hover_text("Mean wind speed against height of a mast, to spot a miscalibrated anemometer");
+                        if shear_button.clicked() {
+                            self.show_shear = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Histogram");
+                        ui.label("");
+                        let histogram_button = ui.add_enabled(
+                            self.file_loaded,
+                            Button::new("Show histogram").min_size([100., 20.].into()),
+                        );
+                        if histogram_button.clicked() {
+                            self.show_histogram = true;
+                        }
+                        ui.end_row();
+
+                        let time_mode = self.plot_mode == PlotMode::TimeSeries;
+                        ui.label("Rolling band");
+                        ui.add_enabled(
+                            time_mode,
+                            egui::Checkbox::new(&mut self.show_rolling, "Mean ± k·σ"),
+                        );
+                        ui.add_enabled_ui(time_mode, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    DragValue::new(&mut self.rolling_window)
+                                        .clamp_range(3..=100_000)
+                                        .suffix(" rows"),
+                                );
+                                ui.add(
+                                    DragValue::new(&mut self.rolling_k)
+                                        .clamp_range(0.5..=10.0)
+                                        .speed(0.1)
+                                        .prefix("k = "),
+                                );
+                            });
+                        });
+                        ui.end_row();
+
+                        if !self.presentation {
+                            ui.label(""); // dummy row
+                            ui.label("");
+                            let flag_button = ui.add_enabled(
+                                time_mode && self.show_rolling && self.file_loaded,
+                                Button::new("Flag out-of-band").min_size([100., 20.].into()),
+                            );
+                            if flag_button.clicked() {
+                                self.flag_out_of_band();
+                            }
+                            ui.end_row();
+
+                            ui.label("Spike filter");
+                            ui.add(
+                                DragValue::new(&mut self.spike_delta)
+                                    .clamp_range(0.0..=f64::MAX)
+                                    .speed(0.1)
+                                    .prefix("jump > "),
+                            )
+                            .on_hover_text("Largest change of the y axis from the valid values before and after a point");
+                            let spike_button = ui
+                                .add_enabled(self.file_loaded, Button::new("Flag spikes").min_size([100., 20.].into()))
+                                .on_hover_text("Circle the spikes on the plot, to exclude from the review window");
+                            if spike_button.clicked() {
+                                self.flag_spikes();
+                            }
+                            ui.end_row();
+                        }
+                        ui.end_row();
+
+                        if !self.presentation {
+                            ui.label("Exclusion reason");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    TextEdit::singleline(&mut self.reason)
+                                        .hint_text("Write the reason for exclusion")
+                                        .desired_width(270.0),
+                                );
+                                // Presets and the reasons used so far, to keep them consistent
+                                let choices = self.settings.reason_choices(&self.exclusion_names);
+                                ui.menu_button("⏷", |ui| {
+                                    for choice in choices {
+                                        if ui.button(&choice).clicked() {
+                                            self.reason = choice;
+                                            ui.close_menu();
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Pick a preset or a reason used before");
+                            });
+
+                            let exclude_button = ui
+                                .add_enabled_ui(!self.read_only, |ui| {
+                                    ui.add_sized([100., 20.], Button::new("Exclude"))
+                                })
+                                .inner;
+                            let exclude_button = match self.last_reason.is_empty() {
+                                true => exclude_button,
+                                false => exclude_button.on_hover_text(format!(
+                                    "Press {} to exclude with the last reason, '{}'",
+                                    self.settings.shortcuts.label(Action::RepeatReason),
+                                    self.last_reason
+                                )),
+                            };
+                            if exclude_button.clicked() {
+                                self.timed("exclusion", |app| app.exclude_data());
+                            }
+
+                            ui.end_row();
+
+                            ui.label("Category");
+                            ComboBox::from_id_source("category")
+                                .selected_text(match self.category {
+                                    Some(category) => category.label(),
+                                    None => "None",
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.category, None, "None");
+                                    for category in Category::ALL {
+                                        ui.selectable_value(
+                                            &mut self.category,
+                                            Some(category),
+                                            category.label(),
+                                        );
+                                    }
+                                })
+                                .response
+                                .on_hover_text("Exported in its own column, for tools filtering on it");
+                            ui.end_row();
+
+                            // One click sets the reason and excludes the selection
+                            ui.label("Quick reasons");
+                            let mut quick_reason = None;
+                            ui.horizontal_wrapped(|ui| {
+                                ui.set_max_width(300.0);
+                                for reason in &self.settings.quick_reasons {
+                                    if ui.add_enabled(!self.read_only, Button::new(reason)).clicked() {
+                                        quick_reason = Some(reason.clone());
+                                    }
+                                }
+                            });
+                            // The reason field only takes the quick reason once it was used
+                            if let Some(reason) = quick_reason {
+                                if self.timed("exclusion", |app| app.exclude_data_with(reason.clone())) {
+                                    self.reason = reason;
+                                }
+                            }
+                            let settings_button = ui
+                                .vertical(|ui| {
+                                    if ui.add_sized([100., 20.], Button::new("Channel names")).clicked() {
+                                        self.show_naming = true;
+                                    }
+                                    if ui.add_sized([100., 20.], Button::new("Shortcuts")).clicked() {
+                                        self.show_shortcut_settings = true;
+                                    }
+                                    ui.add_sized([100., 20.], Button::new("Load settings"))
+                                })
+                                .inner;
+                            if settings_button.clicked() {
+                                if let Some(path) = self.file_dialog()
+                                    .add_filter("Settings", &["json", "toml"])
+                                    .pick_file()
+                                {
+                                    match Settings::load(&path) {
+                                        Ok(settings) => {
+                                            self.settings = settings;
+                                            self.msg = "Settings loaded".into();
+                                        }
+                                        Err(e) => self.msg = format!("Settings error: {}", e),
+                                    }
+                                } else {
+                                    self.msg = "No file selected.".into();
+                                }
+                            }
+                            ui.end_row();
+                        }
+
+                        ui.label(""); // dummy row
+                        ui.checkbox(&mut self.show_excluded, "Show excluded data");
+                        let clear_button =
+                            ui.add_sized([100., 20.], Button::new("Clear selection"));
+                        if clear_button.clicked() {
+                            self.clear_selection();
+                        }
+                        ui.end_row();
+
+                        // Only editing controls follow, hidden while presenting
+                        if self.presentation {
+                            return;
+                        }
+
+                        ui.label(""); // dummy row
+                        let undo_key = self.settings.shortcuts.label(Action::Undo);
+                        let redo_key = self.settings.shortcuts.label(Action::Redo);
+                        ui.horizontal(|ui| {
+                            let undo = ui
+                                .add_enabled(!self.undo_stack.is_empty(), Button::new("Undo"))
+                                .on_hover_text(match self.undo_stack.last() {
+                                    Some(action) => format!("Undo '{}' ({})", action.label, undo_key),
+                                    None => undo_key,
+                                });
+                            if undo.clicked() {
+                                self.undo(false);
+                            }
+                            let redo = ui
+                                .add_enabled(!self.redo_stack.is_empty(), Button::new("Redo"))
+                                .on_hover_text(match self.redo_stack.last() {
+                                    Some(action) => format!("Redo '{}' ({})", action.label, redo_key),
+                                    None => redo_key,
+                                });
+                            if redo.clicked() {
+                                self.undo(true);
+                            }
+                        });
+                        let restore_button = ui
+                            .add_enabled_ui(!self.read_only, |ui| {
+                                ui.add_sized([100., 20.], Button::new("Restore"))
+                            })
+                            .inner
+                            .on_hover_text("Turn the excluded points inside the selection back into valid data");
+                        if restore_button.clicked() {
+                            self.timed("restore", |app| app.restore_data());
+                        }
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        ui.checkbox(&mut self.snap_to_data, "Snap vertices to data");
+                        ui.checkbox(&mut self.compass_labels, "Compass labels");
+                        ui.end_row();
+
+                        ui.toggle_value(&mut self.draw_mode, "Draw exclusion area")
+                            .on_hover_text("Click to add vertices without holding E, Escape to cancel");
+                        ui.checkbox(&mut self.touch_drawing, "Touch drawing")
+                            .on_hover_text(
+                                "Tap to add a vertex and long-press to close the selection",
+                            );
+                        ui.checkbox(&mut self.exclude_sensor_group, "Exclude sensor group")
+                            .on_hover_text(
+                                "Also exclude the mean, std, min and max channels of the same sensor",
+                            );
+                        ui.end_row();
+                        ui.end_row();
+
+                        ui.label("Analyst");
+                        ui.add_sized(
+                            [100., 20.],
+                            TextEdit::singleline(&mut self.analyst).hint_text("Name or initials"),
+                        )
+                        .on_hover_text(
+                            "Stamped on the exclusions made from now on and written with them",
+                        );
+                        ui.checkbox(&mut self.export_index_precision, "Index time precision")
+                            .on_hover_text(
+                                "Export periods without seconds when the loaded index has none",
+                            );
+                        ui.end_row();
+
+                        ui.label("Export format");
+                        ComboBox::new("export format", "")
+                            .selected_text(self.export_format.label())
+                            .show_ui(ui, |ui| {
+                                for format in ExportFormat::ALL {
+                                    ui.selectable_value(&mut self.export_format, format, format.label());
+                                }
+                            })
+                            .response
+                            .on_hover_text("Layout of the exported periods, the auto export's too");
+                        let options_button = ui.add_enabled(
+                            self.export_format == ExportFormat::Tsv,
+                            Button::new("Columns..."),
+                        );
+                        if options_button.clicked() {
+                            self.show_export_options = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Time buffer");
+                        ui.add_sized(
+                            [100., 20.],
+                            DragValue::new(&mut self.time_buffer).suffix(" min"),
+                        );
+                        let export_button = ui.add_sized([100., 20.], Button::new("Export"));
+                        if export_button.clicked() {
+                            let format = self.export_format;
+                            if let Some(path) = self
+                                .file_dialog()
+                                .add_filter(format.label(), &[format.extension()])
+                                .save_file()
+                            {
+                                let path_name = path.display().to_string();
+                                let append = self.export_append;
+                                match self.timed("export", |app| app.export_exclusions(path, append)) {
+                                    Ok(skipped) => {
+                                        self.unsaved_changes = false;
+                                        self.take_snapshot("After export");
+                                        self.msg = Self::exported_message(&skipped);
+                                        if append {
+                                            self.msg += " (only the periods not in the file yet were added)";
+                                        }
+                                    }
+                                    Err(e) => self.msg = format!("Export error: {}", e),
+                                };
+                                self.audit
+                                    .record(format!("Export {}: {}", path_name, self.msg));
+                            } else {
+                                self.msg = "No file selected.".into();
+                            }
+                        }
+                        ui.end_row();
+
+                        ui.label("Auto export");
+                        ui.horizontal(|ui| {
+                            ui.label("Every");
+                            ui.add(
+                                DragValue::new(&mut self.auto_export_minutes)
+                                    .clamp_range(1..=240)
+                                    .suffix(" min"),
+                            );
+                            ui.checkbox(&mut self.auto_export_session, "Session too");
+                            if let Some(path) = &self.auto_export_path {
+                                ui.label(path.file_name().unwrap_or_default().to_string_lossy())
+                                    .on_hover_text(path.display().to_string());
+                            }
+                        });
+                        if self.auto_export_path.is_some() {
+                            if ui.add_sized([100., 20.], Button::new("Stop")).clicked() {
+                                self.auto_export_path = None;
+                                self.msg = "Auto export stopped".into();
+                            }
+                        } else if ui.add_sized([100., 20.], Button::new("Auto export")).clicked() {
+                            if let Some(path) = self.file_dialog().save_file() {
+                                self.msg = format!(
+                                    "Exclusions will be exported to {} every {} min",
+                                    path.display(),
+                                    self.auto_export_minutes
+                                );
+                                self.auto_export_path = Some(path);
+                                self.last_auto_export = Instant::now();
+                            } else {
+                                self.msg = "No file selected.".into();
+                            }
+                        }
+                        ui.end_row();
+
+                        ui.label("Exported exclusions");
+                        ui.label("");
+                        let import_button = ui.add_enabled(
+                            !self.read_only,
+                            Button::new("Import").min_size([100., 20.].into()),
+                        );
+                        if import_button.clicked() {
+                            if let Some(path) = self.file_dialog().pick_file() {
+                                if let Err(e) = self.timed("import", |app| {
+                                    app.import_exclusions(path, "exclusions import", read_exclusions)
+                                }) {
+                                    self.msg = format!("Import error: {}", e);
+                                }
+                            } else {
+                                self.msg = "No file selected.".into();
+                            }
+                        }
+                        ui.end_row();
+
+                        ui.label(""); // dummy row
+                        ui.vertical(|ui| {
+                            ui.checkbox(&mut self.export_approved_only, "Export approved only");
+                            ui.checkbox(&mut self.export_append, "Append to file").on_hover_text(
+                                "Merge the exclusions into the periods of the file exported to, instead of overwriting it",
+                            );
+                        });
+                        let review_button = ui.add_sized([100., 20.], Button::new("Review"));
+                        if review_button.clicked() {
+                            self.show_review = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Excluded values as");
+                        ui.horizontal(|ui| {
+                            ComboBox::new("Select excluded replacement", "")
+                                .selected_text(self.cleaned_replacement.label())
+                                .show_ui(ui, |ui| {
+                                    for replacement in [
+                                        Replacement::Sentinel,
+                                        Replacement::Empty,
+                                        Replacement::NaN,
+                                        Replacement::Custom(String::new()),
+                                    ] {
+                                        let label = replacement.label();
+                                        let selected = self.cleaned_replacement.label() == label;
+                                        if ui.selectable_label(selected, label).clicked() && !selected {
+                                            self.cleaned_replacement = replacement;
+                                        }
+                                    }
+                                });
+                            if let Replacement::Custom(code) = &mut self.cleaned_replacement {
+                                ui.add(TextEdit::singleline(code).hint_text("Code").desired_width(60.0));
+                            }
                         });
+                        let data_button = ui
+                            .add_enabled_ui(!self.partial_load, |ui| ui.add_sized([100., 20.], Button::new("Export data")))
+                            .inner
+                            .on_disabled_hover_text("The file was loaded sub-sampled or with some columns left out");
+                        if data_button.clicked() && self.file_loaded {
+                            // Next to the loaded file by default, as <name>_cleaned.<ext>,
+                            // and as text for workbooks
+                            let loaded = Path::new(&self.file_path);
+                            let extension = match is_workbook(&self.file_path) {
+                                true => "txt".into(),
+                                false => loaded.extension().map_or("txt".into(), |ext| ext.to_string_lossy()),
+                            };
+                            let file_name = format!(
+                                "{}_cleaned.{}",
+                                loaded.file_stem().unwrap_or_default().to_string_lossy(),
+                                extension
+                            );
+                            let mut dialog = self.file_dialog().set_file_name(file_name);
+                            if let Some(directory) = loaded.parent() {
+                                dialog = dialog.set_directory(directory);
+                            }
+                            if let Some(path) = dialog.save_file() {
+                                let path_name = path.display().to_string();
+                                self.msg = match self.timed("export", |app| app.export_cleaned_data(path)) {
+                                    Ok(()) => format!(
+                                        "Cleaned data exported successfully: {} rows, {} channels",
+                                        self.index.len(),
+                                        self.timeseries.iter().filter(|ts| !ts.derived()).count()
+                                    ),
+                                    Err(e) => format!("Export error: {}", e),
+                                };
+                                self.audit
+                                    .record(format!("Export {}: {}", path_name, self.msg));
+                            }
+                        }
+                        ui.end_row();
+
+                        ui.label("WindFarmer XML");
+                        let import_button = ui.add_enabled(
+                            !self.read_only,
+                            Button::new("Import XML").min_size([100., 20.].into()),
+                        );
+                        if import_button.clicked() {
+                            if let Some(path) = self.file_dialog()
+                                .add_filter("WindFarmer XML", &["xml"])
+                                .pick_file()
+                            {
+                                if let Err(e) =
+                                    self.timed("import", |app| app.import_windfarmer(path))
+                                {
+                                    self.msg = format!("Import error: {}", e);
+                                }
+                            }
+                        }
+                        let export_xml = ui.add_sized([100., 20.], Button::new("Export XML"));
+                        if export_xml.clicked() {
+                            if let Some(path) = self.file_dialog()
+                                .add_filter("WindFarmer XML", &["xml"])
+                                .save_file()
+                            {
+                                let path_name = path.display().to_string();
+                                self.msg = match self
+                                    .timed("export", |app| app.export_windfarmer(path))
+                                {
+                                    Ok(skipped) => Self::exported_message(&skipped),
+                                    Err(e) => format!("Export error: {}", e),
+                                };
+                                self.audit
+                                    .record(format!("Export {}: {}", path_name, self.msg));
+                            }
+                        }
                         ui.end_row();
                         ui.end_row();
 
-                        let mut options: Vec<String> =
-                            self.timeseries.iter().map(|ts| ts.name.clone()).collect();
+                        let directions: Vec<usize> = (0..self.timeseries.len())
+                            .filter(|&i| self.is_direction_series(i))
+                            .collect();
 
-                        if options.is_empty() {
-                            options.push("".to_string());
-                            options.push("".to_string());
-                        } else if options.len() > 1 && !self.file_loaded {
-                            self.xaxis = 0;
-                            self.yaxis = 1;
+                        for (n, period) in self.periods.iter_mut().enumerate() {
+                            ui.label(format!("Period {}", ["A", "B"][n]));
+                            for (day, hint) in period.iter_mut().zip(["First day", "Last day"]) {
+                                ui.add(
+                                    TextEdit::singleline(day)
+                                        .hint_text(hint)
+                                        .desired_width(100.0),
+                                );
+                            }
+                            ui.end_row();
                         }
 
-                        ui.label("X-axis");
-                        ComboBox::new("Select x axis", "")
-                            .selected_text(&options[self.xaxis])
-                            .show_ui(ui, |ui| {
-                                for (index, option) in options.iter().enumerate() {
-                                    if ui
-                                        .selectable_value(&mut self.xaxis, index, option)
-                                        .clicked()
-                                    {
-                                        self.xaxis = index;
+                        ui.label(""); // dummy row
+                        let compare = ui.checkbox(&mut self.compare_periods, "Compare periods");
+                        if compare.changed()
+                            && self.compare_periods
+                            && self.period_ranges().is_none()
+                        {
+                            self.msg = "Write both periods as YYYY-MM-DD dates".to_owned();
+                        }
+                        ui.end_row();
+                        ui.end_row();
+
+                        ui.label("Direction difference");
+                        for (id, selected) in [
+                            ("Select first direction", &mut self.difference_a),
+                            ("Select second direction", &mut self.difference_b),
+                        ] {
+                            ComboBox::new(id, "")
+                                .selected_text(options.get(*selected).map_or("", |s| s.as_str()))
+                                .show_ui(ui, |ui| {
+                                    for &index in &directions {
+                                        ui.selectable_value(selected, index, &options[index]);
                                     }
-                                }
-                            });
+                                });
+                        }
+                        ui.end_row();
 
-                        ui.checkbox(&mut self.excludex, "Exclude x axis");
+                        ui.label(""); // dummy row
+                        ui.label("");
+                        let difference_button =
+                            ui.add_sized([100., 20.], Button::new("Add channel"));
+                        if difference_button.clicked() {
+                            self.add_angular_difference();
+                        }
                         ui.end_row();
 
-                        ui.label("Y-axis");
-                        ComboBox::new("Select y axis", "")
-                            .selected_text(&options[self.yaxis])
-                            .show_ui(ui, |ui| {
-                                for (index, option) in options.iter().enumerate() {
-                                    if ui
-                                        .selectable_value(&mut self.yaxis, index, option)
-                                        .clicked()
-                                    {
-                                        self.yaxis = index;
+                        ui.label("Anemometer comparison");
+                        let (first, second) = &mut self.comparison;
+                        for (id, selected) in [
+                            ("Select first anemometer", first),
+                            ("Select second anemometer", second),
+                        ] {
+                            ComboBox::new(id, "")
+                                .selected_text(options.get(*selected).map_or("", |s| s.as_str()))
+                                .show_ui(ui, |ui| {
+                                    for (index, option) in options.iter().enumerate() {
+                                        if !directions.contains(&index) {
+                                            ui.selectable_value(selected, index, option);
+                                        }
                                     }
-                                }
-                            });
-
-                        ui.checkbox(&mut self.excludey, "Exclude y axis");
+                                });
+                        }
                         ui.end_row();
+
+                        ui.label(""); // dummy row
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut self.comparison_ratio, true, "Ratio");
+                            ui.selectable_value(&mut self.comparison_ratio, false, "Difference");
+                        });
+                        let comparison_button =
+                            ui.add_sized([100., 20.], Button::new("Add channel"));
+                        if comparison_button.clicked() {
+                            self.add_anemometer_comparison();
+                        }
                         ui.end_row();
 
-                        ui.label("Exclusion reason");
+                        ui.label("Expression");
                         ui.add(
-                            TextEdit::singleline(&mut self.reason)
-                                .hint_text("Write the reason for exclusion")
-                                .desired_width(300.0),
+                            TextEdit::singleline(&mut self.expression)
+                                .hint_text("TI = M1~WSSD80 / M1~WS80"),
                         );
-
-                        let exclude_button = ui.add_sized([100., 20.], Button::new("Exclude"));
-                        if exclude_button.clicked() {
-                            self.exclude_data();
+                        let expression_button = ui
+                            .add_sized([100., 20.], Button::new("Add channel"))
+                            .on_hover_text("Adds a channel computed from others with + - * / ^, parentheses and abs, sqrt, ln or exp. Channels go by full name or sensor, between square brackets when they have spaces.");
+                        if expression_button.clicked() {
+                            self.add_expression_channel();
                         }
-
                         ui.end_row();
 
-                        ui.label(""); // dummy row
-                        ui.checkbox(&mut self.show_excluded, "Show excluded data");
-                        let clear_button =
-                            ui.add_sized([100., 20.], Button::new("Clear selection"));
-                        if clear_button.clicked() {
-                            self.exclusion_curve.clear();
-                            self.exclusion_curve_is_closed = false;
-                        }
+                        ui.label("Derived y axis");
+                        let sources = self.timeseries.get(self.yaxis).map_or_else(Vec::new, |ts| ts.sources.clone());
+                        ui.vertical(|ui| {
+                            if sources.is_empty() {
+                                ui.label("Not derived");
+                            }
+                            for source in sources {
+                                let mut selected = self.exclude_sources.contains(&source);
+                                let label = format!("Exclude {}", self.timeseries[source].name);
+                                let checkbox = ui
+                                    .checkbox(&mut selected, label)
+                                    .on_hover_text("Exclusions of the derived y axis also apply to this source");
+                                if checkbox.changed() {
+                                    if selected {
+                                        self.exclude_sources.push(source);
+                                    } else {
+                                        self.exclude_sources.retain(|&s| s != source);
+                                    }
+                                }
+                            }
+                        });
                         ui.end_row();
                         ui.end_row();
 
-                        ui.label("Time buffer");
-                        ui.add_sized(
-                            [100., 20.],
-                            DragValue::new(&mut self.time_buffer).suffix(" min"),
-                        );
-                        let export_button = ui.add_sized([100., 20.], Button::new("Export"));
-                        if export_button.clicked() {
-                            if let Some(path) = rfd::FileDialog::new().save_file() {
-                                match self.export_exclusions(path) {
-                                    Ok(()) => self.msg = "Exclusions exported successfully".into(),
-                                    Err(e) => self.msg = format!("Export error: {}", e),
-                                };
+                        ui.label("Plot theme");
+                        ui.horizontal(|ui| {
+                            ui.label(&self.theme.name);
+                            if ui.small_button("Edit").on_hover_text("Window theme and plot colors").clicked() {
+                                self.show_appearance = true;
+                            }
+                        });
+                        let theme_button = ui.add_sized([100., 20.], Button::new("Load theme"));
+                        if theme_button.clicked() {
+                            if let Some(path) = self.file_dialog()
+                                .add_filter("Theme", &["json", "toml"])
+                                .pick_file()
+                            {
+                                match PlotTheme::load(&path) {
+                                    Ok(theme) => {
+                                        self.msg = format!("Theme '{}' loaded", theme.name);
+                                        self.theme = theme;
+                                    }
+                                    Err(e) => self.msg = format!("Theme error: {}", e),
+                                }
                             } else {
                                 self.msg = "No file selected.".into();
                             }
                         }
                         ui.end_row();
+
+                        ui.label("Sensor config");
+                        ui.label(format!("{} channels", self.sensor_config.channels.len()));
+                        let config_button = ui.add_sized([100., 20.], Button::new("Load config"));
+                        if config_button.clicked() {
+                            if let Some(path) = self.file_dialog()
+                                .add_filter("Sensor configuration", &["json", "toml"])
+                                .pick_file()
+                            {
+                                match SensorConfig::load(&path) {
+                                    Ok(config) => {
+                                        self.msg = format!(
+                                            "Sensor configuration with {} channels loaded",
+                                            config.channels.len()
+                                        );
+                                        self.sensor_config = config;
+                                    }
+                                    Err(e) => self.msg = format!("Sensor config error: {}", e),
+                                }
+                            } else {
+                                self.msg = "No file selected.".into();
+                            }
+                        }
                         ui.end_row();
                         ui.end_row();
                     });
+
+                if self.file_loaded && !self.presentation {
+                    ui.collapsing("Snapshots", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Every");
+                            ui.add(
+                                DragValue::new(&mut self.snapshot_every)
+                                    .clamp_range(0..=1000)
+                                    .suffix(" operations"),
+                            );
+                            if ui.button("Take snapshot").clicked() {
+                                self.take_snapshot("Manual");
+                            }
+                        });
+
+                        let mut restore = None;
+                        for (n, snapshot) in self.snapshots.iter().enumerate().rev() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!(
+                                    "{}  {} ({} points)",
+                                    snapshot.taken_at.format("%H:%M:%S"),
+                                    snapshot.label,
+                                    snapshot.exclusions.len()
+                                ));
+                                if ui.small_button("Restore").clicked() {
+                                    restore = Some(n);
+                                }
+                            });
+                        }
+                        if let Some(n) = restore {
+                            self.restore_snapshot(n);
+                        }
+                    });
+                }
+
+                if self.file_loaded && !self.presentation {
+                    ui.collapsing("Rules", |ui| {
+                        for (enabled, rule) in self.rules.iter_mut() {
+                            ui.horizontal(|ui| {
+                                ui.checkbox(enabled, rule.reason());
+                                match rule {
+                                    Rule::Range { min, max } => {
+                                        ui.add(DragValue::new(min).prefix("min "));
+                                        ui.add(DragValue::new(max).prefix("max "));
+                                    }
+                                    Rule::Spike { max_step } => {
+                                        ui.add(DragValue::new(max_step).prefix("step > "));
+                                    }
+                                    Rule::MinMaxConsistency => (),
+                                    Rule::FlatLine {
+                                        min_length,
+                                        tolerance,
+                                    } => {
+                                        ui.add(
+                                            DragValue::new(min_length)
+                                                .clamp_range(2..=10000)
+                                                .suffix(" values"),
+                                        )
+                                        .on_hover_text("Consecutive timestamps reporting the same value, within the tolerance");
+                                        ui.add(DragValue::new(tolerance).speed(0.01).prefix("± "));
+                                    }
+                                }
+                            });
+                        }
+
+                        ui.collapsing("Plausible ranges by sensor type", |ui| {
+                            let mut remove = None;
+                            egui::Grid::new("plausible_ranges_grid").num_columns(4).show(ui, |ui| {
+                                for (n, range) in self.settings.plausible_ranges.iter_mut().enumerate() {
+                                    ui.add(TextEdit::singleline(&mut range.sensor_type).desired_width(40.0));
+                                    ui.add(DragValue::new(&mut range.min).prefix("min "));
+                                    ui.add(DragValue::new(&mut range.max).prefix("max "));
+                                    if ui.small_button("Remove").clicked() {
+                                        remove = Some(n);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                            if let Some(n) = remove {
+                                self.settings.plausible_ranges.remove(n);
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.button("Add range").clicked() {
+                                    self.settings.plausible_ranges.push(PlausibleRange {
+                                        sensor_type: String::new(),
+                                        min: 0.0,
+                                        max: 100.0,
+                                    });
+                                }
+                                ui.add(TextEdit::singleline(&mut self.range_reason).hint_text("Reason").desired_width(100.0));
+                                let apply = ui
+                                    .add_enabled(!self.read_only, Button::new("Apply ranges"))
+                                    .on_hover_text("Exclude the values outside the range of their sensor type in every channel");
+                                if apply.clicked() {
+                                    self.timed("exclusion", |app| app.apply_plausible_ranges());
+                                }
+                            });
+                        });
+
+                        match &self.rule_job {
+                            Some(job) => {
+                                let progress = job.progress;
+                                ui.horizontal(|ui| {
+                                    ui.add(egui::ProgressBar::new(progress).desired_width(200.0));
+                                    if ui.button("Cancel").clicked() {
+                                        self.rule_job = None;
+                                        self.msg = format!(
+                                            "Rules cancelled after flagging {} periods",
+                                            self.detections.len()
+                                        );
+                                    }
+                                });
+                            }
+                            None => {
+                                let types: BTreeSet<String> = self
+                                    .timeseries
+                                    .iter()
+                                    .filter_map(|ts| self.channel_type(&ts.name))
+                                    .collect();
+                                ui.horizontal(|ui| {
+                                    ui.selectable_value(
+                                        &mut self.rule_scope,
+                                        RuleScope::Plotted,
+                                        "Plotted",
+                                    );
+                                    ui.selectable_value(
+                                        &mut self.rule_scope,
+                                        RuleScope::All,
+                                        "All channels",
+                                    );
+                                    ui.selectable_value(
+                                        &mut self.rule_scope,
+                                        RuleScope::SensorType,
+                                        "Type",
+                                    );
+                                    ComboBox::new("Select rule sensor type", "")
+                                        .selected_text(&self.rule_sensor_type)
+                                        .show_ui(ui, |ui| {
+                                            for kind in types {
+                                                let label = kind.clone();
+                                                ui.selectable_value(
+                                                    &mut self.rule_sensor_type,
+                                                    kind,
+                                                    label,
+                                                );
+                                            }
+                                        });
+                                });
+                                ui.horizontal(|ui| {
+                                    if ui.button("Run rules").clicked() {
+                                        self.run_rules();
+                                    }
+                                    if ui.button("Icing detection").clicked() {
+                                        self.open_icing_window();
+                                    }
+                                    if ui.button("Direction sectors").clicked() {
+                                        self.show_sectors = true;
+                                    }
+                                });
+                            }
+                        }
+                    });
+                }
+
+                if !self.presentation {
+                    ui.collapsing("Audit trail", |ui| {
+                        if ui.button("Export audit trail").clicked() {
+                            if let Some(path) = self.file_dialog().save_file() {
+                                self.msg = match File::create(&path)
+                                    .and_then(|file| self.audit.write(&mut BufWriter::new(file)))
+                                {
+                                    Ok(()) => "Audit trail exported successfully".into(),
+                                    Err(e) => format!("Export error: {}", e),
+                                };
+                            }
+                        }
+
+                        egui::ScrollArea::vertical()
+                            .max_height(150.0)
+                            .show(ui, |ui| {
+                                for entry in self.audit.entries.iter().rev() {
+                                    ui.label(format!(
+                                        "{}  {}",
+                                        entry.time.format("%H:%M:%S"),
+                                        entry.action
+                                    ));
+                                }
+                            });
+                    });
+                }
+
                 ui.add_space(50.0);
                 ui.label(
                     egui::RichText::new(&self.msg).color(egui::Color32::from_rgb(255, 200, 200)),
                 );
             });
 
+        // Text fields keep their keys, e.g. their own undo
+        if !ctx.wants_keyboard_input() && !self.presentation && self.remapping.is_none() {
+            let shortcuts = &self.settings.shortcuts;
+            for action in ctx.input_mut(|i| shortcuts.pressed(i)) {
+                self.run_shortcut(action);
+            }
+        }
+
+        // After the axis selection, so newly selected channels are plotted right away
+        self.materialize_plotted();
+        self.poll_rules(ctx);
+        self.auto_export(ctx);
+
+        if self.show_review {
+            self.show_review_window(ctx);
+        }
+
+        if self.show_icing && self.file_loaded {
+            self.show_icing_window(ctx);
+        }
+
+        if self.show_sectors && self.file_loaded {
+            self.show_sectors_window(ctx);
+        }
+
+        if self.show_naming {
+            self.show_naming_window(ctx);
+        }
+
+        if self.show_appearance {
+            self.show_appearance_window(ctx);
+        }
+
+        if self.show_shortcuts {
+            self.show_shortcuts_window(ctx);
+        }
+
+        if self.show_shortcut_settings {
+            self.show_shortcut_settings_window(ctx);
+        }
+
+        if self.show_export_options {
+            self.show_export_options_window(ctx);
+        }
+
+        if self.show_stats && self.file_loaded {
+            self.show_stats_window(ctx);
+        }
+
+        if self.show_coverage && self.file_loaded {
+            self.show_coverage_window(ctx);
+        }
+
+        if self.show_gaps && self.file_loaded {
+            self.show_gaps_window(ctx);
+        }
+
+        if self.show_matrix && self.file_loaded {
+            self.show_matrix_window(ctx);
+        }
+
+        if self.show_hud {
+            self.show_hud(ctx);
+        }
+
+        if self.show_license {
+            self.show_license_window(ctx);
+        }
+
+        self.show_pending_drop_window(ctx);
+        self.show_pending_load_window(ctx);
+        self.show_pending_sheet_window(ctx);
+
+        if self.show_load_report {
+            self.show_load_report_window(ctx);
+        }
+
+        if self.show_plot_window && self.window_yaxis < self.timeseries.len() {
+            self.show_plot_window(ctx);
+        }
+
+        if self.show_profile && self.file_loaded {
+            self.show_profile_window(ctx);
+        }
+
+        if self.show_shear && self.file_loaded {
+            self.show_shear_window(ctx);
+        }
+
+        if self.show_histogram && self.yaxis < self.timeseries.len() {
+            self.show_histogram_window(ctx);
+        }
+
         eframe::egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.toggle_value(&mut self.show_left_panel, "Controls")
+                    .on_hover_text("Show or hide the left panel");
+                ui.checkbox(&mut self.square_plot, "Square plot")
+                    .on_hover_text("Keep the plot square, or stretch it over the whole panel");
+                ui.checkbox(&mut self.show_legend, "Legend")
+                    .on_hover_text("Name the channels, the excluded points and the selection, and label the axes");
+                if ui.add_enabled(self.file_loaded, Button::new("Save plot image")).on_hover_text("Save the plot as PNG, as shown, or as SVG").clicked() {
+                    self.save_plot_image(ui.ctx(), frame);
+                }
+                if ui.add_enabled(self.file_loaded, Button::new("Add to report")).on_hover_text("Include the plot as shown in the cleaning report").clicked() {
+                    self.add_plot_to_report();
+                }
+
+                let mut cancel = false;
+                if let Some(loading) = &self.loading {
+                    ui.separator();
+                    ui.add(
+                        egui::ProgressBar::new(loading.job.progress)
+                            .desired_width(200.0)
+                            .show_percentage(),
+                    );
+                    cancel = ui.button("Cancel").clicked();
+                }
+                if cancel {
+                    self.loading = None;
+                    self.msg = "Load cancelled".into();
+                }
+            });
+
             if self.file_loaded {
-                let points_valid = self.extract_valid_points();
-                let points_excluded = self.extract_excluded_points();
+                let points_valid = self.cached_valid_points(self.yaxis);
+                let points_excluded = self.cached_excluded_points(self.yaxis);
+                let snap_points = if self.snap_to_data { Rc::clone(&points_valid) } else { Rc::from([]) };
                 if !points_valid.is_empty() {
-                Plot::new("data_plot")
-                    .view_aspect(1.0)
-                    .width(700.0)
-                    .height(700.0)
+                let points_valid = self.decimated(&points_valid);
+                let points_excluded: Vec<_> = points_excluded.into_iter().map(|(rgb, points)| (rgb, self.decimated(&points))).collect();
+                let points_colored = match self.color_by {
+                    Some(n) => self.cached_colored_points(n),
+                    None => Vec::new(),
+                };
+                let points_colored: Vec<_> = points_colored.into_iter().map(|(rgb, points)| (rgb, self.decimated(&points))).collect();
+                ui.visuals_mut().extreme_bg_color = color(self.theme.background);
+                let available = ui.available_size();
+                let (width, height) = match self.square_plot {
+                    true => (available.min_elem(), available.min_elem()),
+                    false => (available.x, available.y),
+                };
+                let mut plot = Plot::new("data_plot")
+                    .width(width)
+                    .height(height)
                     .auto_bounds_x()
                     .auto_bounds_y()
-                    .show(ui, |plot_ui| {
-                        plot_ui.points(Points::new(points_valid).radius(2.0).color(Rgba::from_rgb(0.9, 0.9, 0.9)));
-
-                        if self.show_excluded {
-                            plot_ui.points(Points::new(points_excluded).radius(2.0).color(Rgba::from_rgb(0.9, 0.2, 0.2)));
-                        }
-
-                        let color = if self.exclusion_curve_is_closed {Rgba::GREEN} else {Rgba::RED};
-                        plot_ui.points(Points::new(self.exclusion_curve.clone()).radius(5.0).color(color));
-                        plot_ui.line(Line::new(self.exclusion_curve.clone())
-                            .width(2.0)
-                            .color(color));
-                        
-                        let ctx = plot_ui.ctx();
-                        let input = ctx.input(|i| i.clone());
-                        
-                        if input.pointer.primary_clicked() && input.key_down(egui::Key::E) {
-
-                            if let Some(click_pos) = input.pointer.interact_pos() {
-
-                                let min_bounds = plot_ui.plot_bounds().min();
-                                let max_bounds = plot_ui.plot_bounds().max();
-                                let minx = min_bounds[0] as f64;
-                                let miny = min_bounds[1] as f64;
-                                let maxx = max_bounds[0] as f64;
-                                let maxy = max_bounds[1] as f64;
-
-                                let mut data_pos = plot_ui.transform().value_from_position(click_pos);
-                                if data_pos.x > minx && data_pos.x < maxx && data_pos.y > miny && data_pos.y < maxy {
-                                    if self.exclusion_curve.len() > 2 {
-                                        let first_point = self.exclusion_curve.first().unwrap();
-                                        let dist = ((data_pos.x - first_point[0]).powi(2) + (data_pos.y - first_point[1]).powi(2)).sqrt();
-                                        if dist < 0.3 {
-                                            self.exclusion_curve_is_closed = true;
-                                            data_pos.x = first_point[0];
-                                            data_pos.y = first_point[1];
-                                        }
+                    .link_axis(LINKED_PLOTS, true, false)
+                    .link_cursor(LINKED_PLOTS, true, false)
+                    .allow_drag(!self.draw_mode);
+                let time_mode = self.plot_mode == PlotMode::TimeSeries;
+                let (x_units, y_units) = (self.channel_units(self.xaxis), self.channel_units(self.yaxis));
+                if time_mode {
+                    plot = plot
+                        .x_axis_formatter(|x, _range| format_time(x))
+                        .label_formatter(move |_name, value| format!("{}\n{:.3} {}", format_time(value.x), value.y, y_units));
+                } else if !x_units.is_empty() || !y_units.is_empty() {
+                    plot = plot.label_formatter(move |_name, value| format!("x = {:.3} {}\ny = {:.3} {}", value.x, x_units, value.y, y_units));
+                }
+                let time_segments = if time_mode { self.cached_time_segments(self.yaxis) } else { Rc::from([]) };
+                let time_segments = self.decimated_lines(&time_segments);
+                // Excluded points that show their reason on hover, with their row, by series
+                let excluded_hover: Vec<(usize, RowPoints)> = match self.show_excluded {
+                    true => std::iter::once(self.yaxis)
+                        .chain(self.plotted_extra_yaxes())
+                        .map(|n| (n, self.cached_excluded_rows(n)))
+                        .collect(),
+                    false => Vec::new(),
+                };
+                // Values a time range selection has to span
+                let value_range = std::iter::once(self.yaxis)
+                    .chain(self.plotted_extra_yaxes())
+                    .filter_map(|n| self.cached_value_range(n))
+                    .reduce(|(min, max), (lo, hi)| (min.min(lo), max.max(hi)))
+                    .map_or((-1.0, 1.0), |(min, max)| (min - 1.0, max + 1.0));
+                if !time_mode {
+                    self.time_range_start = None;
+                }
+                let (rolling_lines, out_of_band_points) = if time_mode && self.show_rolling { self.rolling_overlay(self.yaxis) } else { Default::default() };
+
+                let extra_yaxes: Vec<_> = self
+                    .plotted_extra_yaxes()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(n, yaxis)| {
+                        let segments = if time_mode { self.cached_time_segments(yaxis) } else { Rc::from([]) };
+                        let excluded = self.cached_excluded_points(yaxis);
+                        let valid = self.cached_valid_points(yaxis);
+                        (
+                            self.timeseries[yaxis].name.clone(),
+                            self.theme.series_color(n),
+                            self.decimated(&valid),
+                            self.decimated_lines(&segments),
+                            excluded.into_iter().map(|(rgb, points)| (rgb, self.decimated(&points))).collect::<Vec<_>>(),
+                        )
+                    })
+                    .collect();
+                let periods = match (self.compare_periods, self.period_ranges()) {
+                    (true, Some(ranges)) => ranges.map(|range| self.extract_period_points(self.yaxis, range)),
+                    _ => Default::default(),
+                };
+                let comparing = periods.iter().any(|points| !points.is_empty());
+                let window_brushed = match self.show_plot_window {
+                    true => self.window_brushed_points(),
+                    false => Vec::new(),
+                };
+                let detected: Vec<[f64; 2]> = std::iter::once(self.yaxis)
+                    .chain(self.plotted_extra_yaxes())
+                    .flat_map(|n| self.detected_points(n).to_vec())
+                    .collect();
+
+                if self.show_legend {
+                    plot = plot.legend(Legend::default());
+                }
+                let x_label = match time_mode {
+                    true => self.index_name.clone(),
+                    false => self.axis_label(self.xaxis),
+                };
+                let y_label = self.axis_label(self.yaxis);
+                let reasons = self.exclusion_names.clone();
+
+                let direction_axes = self.direction_axes();
+                for (axis, _) in direction_axes.iter().enumerate().filter(|(_, &dir)| dir) {
+                    plot = direction_axis(plot, axis, self.compass_labels);
+                }
+                let excluded_points: usize = points_excluded
+                    .iter()
+                    .chain(extra_yaxes.iter().flat_map(|series| &series.4))
+                    .map(|(_, points)| points.len())
+                    .sum();
+                self.plotted_points = points_valid.len()
+                    + periods.iter().map(Vec::len).sum::<usize>()
+                    + extra_yaxes.iter().map(|series| series.2.len()).sum::<usize>()
+                    + if self.show_excluded { excluded_points } else { 0 };
+
+                let theme = if self.presentation {
+                    self.theme.scaled(PRESENTATION_POINT_SCALE)
+                } else {
+                    self.theme.clone()
+                };
+                let response = plot.show(ui, |plot_ui| {
+                    let theme = &theme;
+
+                    if let (Some([min, max]), PlotMode::TimeSeries) = (self.jump_to.take(), self.plot_mode) {
+                        plot_ui.set_plot_bounds(PlotBounds::from_min_max(min, max));
+                    }
+
+                    // Repeat the data a full turn away so selections can cross the 0°/360° seam
+                    let bounds = plot_ui.plot_bounds();
+                    self.view = Some(((bounds.min(), bounds.max()), (self.xaxis, self.yaxis, self.plot_mode)));
+                    for offset in seam_offsets(direction_axes).into_iter().skip(1) {
+                        let wrapped: Vec<[f64; 2]> = points_valid
+                            .iter()
+                            .map(|p| [p[0] + offset[0], p[1] + offset[1]])
+                            .filter(|p| (0..2).all(|i| p[i] >= bounds.min()[i] && p[i] <= bounds.max()[i]))
+                            .collect();
+                        if !wrapped.is_empty() {
+                            plot_ui.points(Points::new(wrapped).radius(theme.point_radius).color(color(theme.valid).gamma_multiply(0.4)));
+                        }
+                    }
+
+                    for segment in time_segments {
+                        plot_ui.line(Line::new(segment).width(1.0).color(color(theme.valid)));
+                    }
+
+                    for segment in rolling_lines {
+                        plot_ui.line(Line::new(segment).width(theme.line_width).color(color(theme.selection_closed).gamma_multiply(0.7)));
+                    }
+                    plot_ui.points(Points::new(out_of_band_points).radius(2.0 * theme.point_radius).color(color(theme.selection_open)));
+
+                    // Dim the rest of the data while comparing periods
+                    let dim = if comparing { 0.25 } else { 1.0 };
+                    let valid_name = format!("{} (valid)", self.timeseries[self.yaxis].name);
+                    if points_colored.is_empty() {
+                        plot_ui.points(Points::new(points_valid).radius(theme.point_radius).color(color(theme.valid).gamma_multiply(dim)).name(&valid_name));
+                    }
+                    for (rgb, points) in points_colored {
+                        plot_ui.points(Points::new(points).radius(theme.point_radius).color(color(rgb).gamma_multiply(dim)).name(&valid_name));
+                    }
+
+                    for (n, points) in periods.into_iter().enumerate() {
+                        plot_ui.points(Points::new(points).radius(theme.point_radius).color(color(theme.period_colors[n])).name(format!("Period {}", ["A", "B"][n])));
+                    }
+
+                    let mut points_excluded = points_excluded;
+                    for (name, series_color, points, segments, excluded) in extra_yaxes {
+                        for segment in segments {
+                            plot_ui.line(Line::new(segment).width(1.0).color(series_color));
+                        }
+                        plot_ui.points(Points::new(points).radius(theme.point_radius).color(series_color).name(format!("{} (valid)", name)));
+                        points_excluded.extend(excluded);
+                    }
+
+                    // The samples inside the selection of the linked plot
+                    if !window_brushed.is_empty() {
+                        plot_ui.points(Points::new(window_brushed).radius(1.5 * theme.point_radius).color(color(theme.selection_open)).name("Selected on the linked plot"));
+                    }
+
+                    // Circled until accepted or dismissed in the review window
+                    if !detected.is_empty() {
+                        plot_ui.points(Points::new(detected).radius(2.5 * theme.point_radius).filled(false).color(color(theme.selection_open)).name("Detected by rules"));
+                    }
+
+                    // One group per reason for each plotted channel, in the same order
+                    if self.show_excluded {
+                        for (reason, (rgb, points)) in reasons.iter().cycle().zip(points_excluded).filter(|(_, (_, points))| !points.is_empty()) {
+                            plot_ui.points(Points::new(points).radius(theme.point_radius).color(color(rgb)).name(format!("Excluded: {}", reason)));
+                        }
+                    }
+
+                    let curve_color = color(if self.exclusion_curve_is_closed {theme.selection_closed} else {theme.selection_open});
+                    // Unnamed items stay out of the legend
+                    let curve_name = if self.exclusion_curve.is_empty() { "" } else { "Selection" };
+                    plot_ui.points(Points::new(self.exclusion_curve.clone()).radius(theme.vertex_radius).color(curve_color).name(curve_name));
+                    plot_ui.line(Line::new(self.exclusion_curve.clone())
+                        .width(theme.line_width)
+                        .color(curve_color)
+                        .name(curve_name));
+                    if let Some(start) = self.time_range_start {
+                        plot_ui.vline(VLine::new(start).width(theme.line_width).color(color(theme.selection_open)));
+                    }
+                    
+                    let ctx = plot_ui.ctx();
+                    let input = ctx.input(|i| i.clone());
+
+                    let editing = !self.presentation;
+
+                    // Timestamp, value and reason of the excluded point under the pointer
+                    if let Some(pointer) = plot_ui.pointer_coordinate() {
+                        let scale = plot_ui.transform().dpos_dvalue();
+                        let distance = |p: &[f64; 2]| scaled_distance(p, &[pointer.x, pointer.y], scale);
+                        let hovered = excluded_hover
+                            .iter()
+                            .flat_map(|(series, rows)| rows.iter().map(move |&(row, point)| (*series, row, point)))
+                            .min_by(|a, b| distance(&a.2).total_cmp(&distance(&b.2)))
+                            .filter(|(_, _, point)| distance(point) < 8.0);
+                        if let Some((series, row, point)) = hovered {
+                            if let Some(DataPoint::Excluded(_, reason)) = self.timeseries[series].data.get(row) {
+                                let text = format!(
+                                    "{}\n{}: {:.3} {}\nExcluded: {}",
+                                    self.index[row],
+                                    self.timeseries[series].name,
+                                    point[1],
+                                    self.channel_units(series),
+                                    reason
+                                );
+                                egui::show_tooltip_at_pointer(ctx, egui::Id::new("excluded_point"), |ui| ui.label(text));
+                            }
+                        }
+                    }
+
+                    // Timestamp under the pointer, for the vertical profile
+                    if time_mode {
+                        if let Some(pointer) = plot_ui.pointer_coordinate() {
+                            self.hovered_row = nearest_time(&self.times, pointer.x, self.time_step);
+                            if input.key_pressed(egui::Key::P) && !ctx.wants_keyboard_input() {
+                                self.pinned_row = self.hovered_row;
+                            }
+                        }
+                    }
+                    
+                    // T+click marks the start and then the end of a time range, selected
+                    // across every value
+                    if time_mode && editing && input.key_down(egui::Key::T) && input.pointer.primary_clicked() {
+                        if let Some(pointer) = plot_ui.pointer_coordinate() {
+                            match self.time_range_start.take() {
+                                None => {
+                                    self.time_range_start = Some(pointer.x);
+                                    self.exclusion_curve.clear();
+                                    self.exclusion_curve_is_closed = false;
+                                }
+                                Some(start) => {
+                                    let (t0, t1) = (start.min(pointer.x), start.max(pointer.x));
+                                    let (y0, y1) = value_range;
+                                    self.exclusion_curve = vec![[t0, y0], [t1, y0], [t1, y1], [t0, y1], [t0, y0]];
+                                    self.exclusion_curve_is_closed = true;
+                                }
+                            }
+                        }
+                    }
+
+                    // With touch drawing a long press closes the selection, as there's no E key
+                    let held_still = match (input.pointer.press_origin(), input.pointer.hover_pos()) {
+                        (Some(origin), Some(pos)) => plot_ui.transform().frame().contains(origin) && origin.distance(pos) < 6.0,
+                        _ => false,
+                    };
+                    if editing && self.touch_drawing && input.pointer.primary_down() && held_still {
+                        ctx.request_repaint();
+                        let pressed_for = input.pointer.press_start_time().map_or(0.0, |t| input.time - t);
+                        if pressed_for > LONG_PRESS_SECONDS && self.exclusion_curve.len() > 2 && !self.exclusion_curve_is_closed {
+                            let first_point = self.exclusion_curve[0];
+                            self.exclusion_curve.push(first_point);
+                            self.exclusion_curve_is_closed = true;
+                        }
+                    }
+
+                    // Escape drops the selection being drawn
+                    let drawing = !self.exclusion_curve.is_empty() || self.time_range_start.is_some();
+                    if editing && drawing && input.key_pressed(egui::Key::Escape) && !ctx.wants_keyboard_input() {
+                        self.clear_selection();
+                    }
+
+                    // Ctrl+click excludes just the point under the pointer
+                    let pick = input.modifiers.command && !self.touch_drawing;
+                    if input.pointer.primary_clicked() && pick && editing {
+                        if let Some(pointer) = plot_ui.pointer_coordinate() {
+                            let scale = plot_ui.transform().dpos_dvalue();
+                            self.timed("exclusion", |app| app.exclude_picked([pointer.x, pointer.y], scale));
+                        }
+                    }
+
+                    let add_vertex = input.key_down(egui::Key::E) || self.touch_drawing || self.draw_mode;
+                    if input.pointer.primary_clicked() && add_vertex && !pick && editing && plot_ui.plot_hovered() {
+
+                        if let Some(click_pos) = input.pointer.interact_pos() {
+
+                            let min_bounds = plot_ui.plot_bounds().min();
+                            let max_bounds = plot_ui.plot_bounds().max();
+                            let minx = min_bounds[0] as f64;
+                            let miny = min_bounds[1] as f64;
+                            let maxx = max_bounds[0] as f64;
+                            let maxy = max_bounds[1] as f64;
+
+                            let mut data_pos = plot_ui.transform().value_from_position(click_pos);
+                            if data_pos.x > minx && data_pos.x < maxx && data_pos.y > miny && data_pos.y < maxy {
+                                let scale = plot_ui.transform().dpos_dvalue();
+                                let click = [data_pos.x, data_pos.y];
+                                if let Some(point) = nearest_point(&snap_points, &click, scale)
+                                    .filter(|p| scaled_distance(p, &click, scale) < SNAP_DISTANCE_PIXELS)
+                                {
+                                    data_pos.x = point[0];
+                                    data_pos.y = point[1];
+                                }
+
+                                if self.exclusion_curve.len() > 2 {
+                                    let first_point = self.exclusion_curve.first().unwrap();
+                                    if scaled_distance(first_point, &[data_pos.x, data_pos.y], scale) < CLOSE_DISTANCE_PIXELS {
+                                        self.exclusion_curve_is_closed = true;
+                                        data_pos.x = first_point[0];
+                                        data_pos.y = first_point[1];
                                     }
-                                    
-                                    self.exclusion_curve.push([data_pos.x, data_pos.y]);
                                 }
+                                
+                                self.exclusion_curve.push([data_pos.x, data_pos.y]);
                             }
                         }
-                    });
+                    }
+                });
+                self.plot_rect = Some(response.response.rect);
+
+                // Plots of this egui version have no axis titles, so they're painted over it
+                if self.show_legend {
+                    let rect = response.response.rect;
+                    let painter = ui.painter_at(rect);
+                    let font = egui::FontId::proportional(14.0);
+                    let text_color = ui.visuals().strong_text_color();
+                    painter.text(rect.right_bottom() + egui::vec2(-6.0, -20.0), egui::Align2::RIGHT_BOTTOM, format!("{} →", x_label), font.clone(), text_color);
+                    painter.text(rect.center_top() + egui::vec2(0.0, 6.0), egui::Align2::CENTER_TOP, format!("↑ {}", y_label), font, text_color);
+                }
                 }
             } else {
                 ui.add_space(25.0);
-                ui.label("<---\tLoad any timeseries file exported from WindFarmer: Analyst");
+                ui.label("<---\tLoad any timeseries file exported from WindFarmer: Analyst, or drop it on the window");
                 ui.add_space(85.0);
                 ui.label("<---\tSelect X and Y axis to plot");
                 ui.add_space(50.0);
-                ui.label("<---\tSelect some data over the plot with \"E+click\" , write a reason for the exclusion and click on Exclude");
+                ui.label("<---\tSelect some data over the plot with \"E+click\" or the \"Draw exclusion area\" tool, or Ctrl+click a single point, write a reason for the exclusion and click on Exclude");
                 ui.add_space(70.0);
                 ui.label("<---\tClick on Export to save the exclusions");
             }
@@ -0,0 +1,66 @@
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+#[derive(Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub time: NaiveDateTime,
+    pub user: String,
+    pub action: String,
+}
+
+// Chronological record of the actions taken during a session
+#[derive(Default)]
+pub struct AuditLog {
+    pub entries: Vec<AuditEntry>,
+}
+
+pub fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_default()
+}
+
+impl AuditLog {
+    pub fn record(&mut self, action: impl Into<String>) {
+        self.entries.push(AuditEntry {
+            time: Local::now().naive_local(),
+            user: current_user(),
+            action: action.into(),
+        });
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let fmt = "%Y-%m-%d %H:%M:%S";
+        for entry in &self.entries {
+            writeln!(
+                writer,
+                "{}\t{}\t{}",
+                entry.time.format(fmt),
+                entry.user,
+                entry.action
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write() {
+        let mut log = AuditLog::default();
+        log.record("Loaded data.txt");
+        log.record("Excluded 12 points of M1~WS80 by 'icing'");
+
+        let mut out = Vec::new();
+        log.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with("\tExcluded 12 points of M1~WS80 by 'icing'"));
+    }
+}
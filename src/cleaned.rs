@@ -0,0 +1,40 @@
+// What excluded values are written as in the cleaned data export
+#[derive(Clone, PartialEq)]
+pub enum Replacement {
+    Sentinel,
+    Empty,
+    NaN,
+    Custom(String),
+}
+
+impl Replacement {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Replacement::Sentinel => "Missing value",
+            Replacement::Empty => "Empty cell",
+            Replacement::NaN => "NaN",
+            Replacement::Custom(_) => "Custom",
+        }
+    }
+
+    pub fn text(&self, sentinel: f64) -> String {
+        match self {
+            Replacement::Sentinel => sentinel.to_string(),
+            Replacement::Empty => String::new(),
+            Replacement::NaN => "NaN".to_owned(),
+            Replacement::Custom(code) => code.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text() {
+        assert_eq!(Replacement::Sentinel.text(-999.0), "-999");
+        assert_eq!(Replacement::Empty.text(-999.0), "");
+        assert_eq!(Replacement::Custom("#N/A".into()).text(-999.0), "#N/A");
+    }
+}
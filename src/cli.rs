@@ -0,0 +1,105 @@
+use crate::cleaned::Replacement;
+use crate::dataset::{first_marker, parse_markers, Dataset};
+use crate::exclusions::read_exclusions;
+use crate::settings::Settings;
+use crate::windfarmer;
+use std::fs;
+use std::path::Path;
+
+// Batch mode, applying an exclusion file to a data file without the window:
+//
+// manual_data_cleaner --apply exclusions.tsv --input data.txt --output cleaned.txt
+//                     [--nan 99999,-999] [--replace sentinel|empty|nan|<code>]
+//                     [--settings settings.toml]
+//
+// The exclusions are read as exported, or as WindFarmer XML when the file
+// ends in .xml. Excluded values are written as the first missing value by default.
+// Channel names are split into mast and sensor by the naming convention of
+// the settings file, "mast~sensor" without one.
+// Data files ending in .parquet are read and written as Parquet, where excluded
+// and missing values are nulls.
+pub fn is_batch(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "--apply")
+}
+
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|n| args.get(n + 1))
+        .map(String::as_str)
+}
+
+fn required<'a>(args: &'a [String], name: &str) -> Result<&'a str, String> {
+    option(args, name).ok_or(format!("Missing {} <file>", name))
+}
+
+fn replacement(value: &str) -> Replacement {
+    match value.to_lowercase().as_str() {
+        "sentinel" => Replacement::Sentinel,
+        "empty" => Replacement::Empty,
+        "nan" => Replacement::NaN,
+        _ => Replacement::Custom(value.to_owned()),
+    }
+}
+
+// Returns a summary of what was done
+pub fn run(args: &[String]) -> Result<String, String> {
+    let exclusions = required(args, "--apply")?;
+    let input = required(args, "--input")?;
+    let output = required(args, "--output")?;
+    let missing = match option(args, "--nan") {
+        Some(value) => parse_markers(value)?,
+        None => vec![99999.0],
+    };
+    let nan = first_marker(&missing);
+    let replace = option(args, "--replace").map_or(Replacement::Sentinel, replacement);
+    let settings = match option(args, "--settings") {
+        Some(path) => Settings::load(Path::new(path))?,
+        None => Settings::default(),
+    };
+
+    let content = fs::read_to_string(exclusions).map_err(|e| format!("File read error: {}", e))?;
+    let rows = if exclusions.to_lowercase().ends_with(".xml") {
+        windfarmer::read_exclusions(&content)?
+    } else {
+        read_exclusions(&content)?
+    };
+
+    let (mut dataset, warnings) = Dataset::load(input, &missing)?;
+    let (count, unmatched) = dataset.apply(&rows, &settings.name_convention);
+    dataset.write_cleaned(output, &replace.text(nan), &nan.to_string())?;
+
+    let mut summary = warnings;
+    summary.push(format!(
+        "Applied {} exclusions to {}: {} points excluded",
+        rows.len(),
+        input,
+        count
+    ));
+    if unmatched > 0 {
+        summary.push(format!("{} exclusions match no channel", unmatched));
+    }
+    summary.push(format!("Cleaned data written to {}", output));
+    Ok(summary.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let args: Vec<String> = ["app", "--apply", "ex.tsv", "--input"]
+            .map(String::from)
+            .to_vec();
+        assert!(is_batch(&args));
+        assert_eq!(option(&args, "--apply"), Some("ex.tsv"));
+        assert_eq!(option(&args, "--input"), None);
+        assert_eq!(
+            required(&args, "--output").unwrap_err(),
+            "Missing --output <file>"
+        );
+        assert!(replacement("EMPTY") == Replacement::Empty);
+        assert!(replacement("#N/A") == Replacement::Custom("#N/A".into()));
+    }
+}
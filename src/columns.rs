@@ -0,0 +1,442 @@
+use crate::duplicates::retain_rows;
+use memmap2::Mmap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::ops::Range;
+
+// Delimited data file kept memory-mapped, whose columns are parsed only when
+// first needed. Column 0 is the index.
+pub struct RawTable {
+    bytes: Mmap,
+    rows: Vec<Range<usize>>,
+    // Line of each row in the file, counting blank lines and the header
+    lines: Vec<usize>,
+    delimiter: u8,
+}
+
+// A data file read whole, for formats that can't be mapped: the header names,
+// the index, and the values of every column after the index
+pub struct Table {
+    pub headers: Vec<String>,
+    pub index: Vec<String>,
+    pub columns: Vec<Vec<f64>>,
+}
+
+const DELIMITERS: [u8; 3] = [b'\t', b';', b','];
+
+// The delimiter found most often outside quotes in the header line, tab if
+// none is
+pub fn detect_delimiter(header: &[u8]) -> u8 {
+    DELIMITERS
+        .into_iter()
+        .map(|d| (split_fields(header, d).count() - 1, d))
+        .max_by_key(|&(count, d)| (count, d == b'\t'))
+        .filter(|&(count, _)| count > 0)
+        .map_or(b'\t', |(_, d)| d)
+}
+
+// Header names of a delimited header line
+pub fn split_header(header: &str) -> Vec<String> {
+    let delimiter = detect_delimiter(header.as_bytes());
+    split_fields(header.as_bytes(), delimiter)
+        .map(|h| unquote(&String::from_utf8_lossy(h)).to_owned())
+        .collect()
+}
+
+// Fields of a delimited line. Delimiters between double quotes are part of
+// the field, as spreadsheets quote text holding them.
+fn split_fields(line: &[u8], delimiter: u8) -> impl Iterator<Item = &[u8]> + '_ {
+    let mut start = Some(0);
+    std::iter::from_fn(move || {
+        let from = start?;
+        let mut quoted = false;
+        for (i, &b) in line[from..].iter().enumerate() {
+            if b == b'"' {
+                quoted = !quoted;
+            } else if b == delimiter && !quoted {
+                start = Some(from + i + 1);
+                return Some(&line[from..from + i]);
+            }
+        }
+        start = None;
+        Some(&line[from..])
+    })
+}
+
+// Fields exported from spreadsheets may come quoted
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+impl RawTable {
+    // Opens the file keeping one data row every `every`. Returns the header
+    // names along with the table.
+    pub fn open(path: &str, every: usize) -> Result<(Vec<String>, Self), String> {
+        Self::open_with_progress(path, every, &|_| ())
+    }
+
+    // Reports the share of the file scanned as it goes, every 1%
+    pub fn open_with_progress(
+        path: &str,
+        every: usize,
+        progress: &dyn Fn(f32),
+    ) -> Result<(Vec<String>, Self), String> {
+        let file = File::open(path).map_err(|e| format!("File read error: {}", e))?;
+        // The file must not be modified while it is mapped, as for any data file
+        // open in the app
+        let bytes = unsafe { Mmap::map(&file) }.map_err(|e| format!("File read error: {}", e))?;
+
+        let every = every.max(1);
+        let mut lines = split_lines(&bytes);
+        let (_, header) = lines.next().ok_or("Empty file")?;
+        let delimiter = detect_delimiter(&bytes[header.clone()]);
+        let headers = split_header(
+            std::str::from_utf8(&bytes[header]).map_err(|_| "Headers are not valid UTF-8")?,
+        );
+        let step = (bytes.len() / 100).max(1);
+        let mut reported = 0;
+        let (lines, rows) = lines
+            .inspect(|(_, row)| {
+                if row.start >= reported + step {
+                    reported = row.start;
+                    progress(row.start as f32 / bytes.len() as f32);
+                }
+            })
+            .step_by(every)
+            .unzip();
+
+        Ok((
+            headers,
+            Self {
+                bytes,
+                rows,
+                lines,
+                delimiter,
+            },
+        ))
+    }
+
+    pub fn delimiter(&self) -> u8 {
+        self.delimiter
+    }
+
+    pub fn line(&self, row: usize) -> usize {
+        self.lines[row]
+    }
+
+    pub fn retain_rows(&mut self, keep: &[bool]) {
+        retain_rows(&mut self.rows, keep);
+        retain_rows(&mut self.lines, keep);
+    }
+
+    pub fn index(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let value = field(&self.bytes[row.clone()], 0, self.delimiter).unwrap_or_default();
+                unquote(&String::from_utf8_lossy(value)).to_owned()
+            })
+            .collect()
+    }
+
+    // Values of a column, with its cells that are neither numbers nor missing
+    pub fn column(&self, column: usize) -> (Vec<f64>, ParseReport) {
+        parse_column(&self.bytes, &self.rows, &self.lines, column, self.delimiter)
+    }
+
+    // Looks over every data cell for malformed values, leaving out the rows
+    // that have any when `skip` is set. Reports the share of rows checked as
+    // it goes, every 1%.
+    pub fn check(&mut self, skip: bool, progress: &dyn Fn(f32)) -> ParseReport {
+        check_rows(
+            &self.bytes,
+            &mut self.rows,
+            &mut self.lines,
+            self.delimiter,
+            skip,
+            progress,
+        )
+    }
+}
+
+// Number from 1 and byte range of every non-empty line, without the line
+// break
+fn split_lines(bytes: &[u8]) -> impl Iterator<Item = (usize, Range<usize>)> + '_ {
+    let mut start = 0;
+    let mut number = 0;
+    std::iter::from_fn(move || {
+        while start < bytes.len() {
+            number += 1;
+            let end = bytes[start..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(bytes.len(), |n| start + n);
+            let line = start..end;
+            start = end + 1;
+
+            let trimmed = match bytes[line.clone()].last() {
+                Some(b'\r') => line.start..line.end - 1,
+                _ => line,
+            };
+            if !trimmed.is_empty() {
+                return Some((number, trimmed));
+            }
+        }
+        None
+    })
+}
+
+fn field(line: &[u8], column: usize, delimiter: u8) -> Option<&[u8]> {
+    split_fields(line, delimiter).nth(column)
+}
+
+// Cells that mean a missing value rather than an invalid one
+fn is_missing_text(text: &str) -> bool {
+    matches!(
+        text.trim().to_lowercase().as_str(),
+        "" | "nan" | "na" | "n/a" | "null" | "-"
+    )
+}
+
+// Value of a cell, NaN when missing, or its text when not a number.
+// Semicolon separated files come from locales that write the comma as the
+// decimal separator.
+fn parse_cell(value: &[u8], delimiter: u8) -> Result<f64, String> {
+    let text = String::from_utf8_lossy(value);
+    if is_missing_text(unquote(&text)) {
+        return Ok(f64::NAN);
+    }
+    let number = match delimiter {
+        b';' => unquote(&text).replace(',', "."),
+        _ => unquote(&text).to_owned(),
+    };
+    number.parse::<f64>().map_err(|_| text.into_owned())
+}
+
+// Values of one column, NaN where missing or invalid, along with the invalid
+// cells
+fn parse_column(
+    bytes: &[u8],
+    rows: &[Range<usize>],
+    lines: &[usize],
+    column: usize,
+    delimiter: u8,
+) -> (Vec<f64>, ParseReport) {
+    let mut report = ParseReport::default();
+    report.checked.insert(column);
+    let values = rows
+        .iter()
+        .zip(lines)
+        .map(|(row, &line)| {
+            let Some(value) = field(&bytes[row.clone()], column, delimiter) else {
+                return f64::NAN;
+            };
+            parse_cell(value, delimiter).unwrap_or_else(|text| {
+                report.add(line, column, text);
+                f64::NAN
+            })
+        })
+        .collect();
+    report.rows = report.total;
+
+    (values, report)
+}
+
+// Cells of the data columns that are neither numbers nor missing, found when
+// the file is opened or as its columns are parsed. Only the first ones are
+// kept to be listed.
+#[derive(Default)]
+pub struct ParseReport {
+    // Line in the file, column and text of each cell listed
+    pub cells: Vec<(usize, usize, String)>,
+    // Malformed cells by column
+    pub columns: BTreeMap<usize, usize>,
+    pub total: usize,
+    // Rows with any such cell, and whether they were left out of the table
+    pub rows: usize,
+    pub skipped: bool,
+    // Columns whose cells were all looked over
+    pub checked: BTreeSet<usize>,
+}
+
+const MAX_REPORTED_CELLS: usize = 1000;
+
+impl ParseReport {
+    fn add(&mut self, line: usize, column: usize, text: String) {
+        self.total += 1;
+        *self.columns.entry(column).or_default() += 1;
+        if self.cells.len() < MAX_REPORTED_CELLS {
+            self.cells.push((line, column, text));
+        }
+    }
+
+    // The report of columns parsed later. Their rows may overlap the ones
+    // already counted, so only the cells add up.
+    pub fn merge(&mut self, other: ParseReport) {
+        for (line, column, text) in other.cells {
+            if self.cells.len() < MAX_REPORTED_CELLS {
+                self.cells.push((line, column, text));
+            }
+        }
+        self.cells.sort_by_key(|(line, column, _)| (*line, *column));
+        for (column, count) in other.columns {
+            *self.columns.entry(column).or_default() += count;
+        }
+        self.total += other.total;
+        self.rows = self.rows.max(other.rows);
+        self.checked.extend(other.checked);
+    }
+
+    // The first cell listed, as a warning
+    pub fn first(&self) -> Option<String> {
+        self.cells
+            .first()
+            .map(|(line, _, text)| format!("Line {}: Invalid numeric value '{}'", line, text))
+    }
+}
+
+fn check_rows(
+    bytes: &[u8],
+    rows: &mut Vec<Range<usize>>,
+    lines: &mut Vec<usize>,
+    delimiter: u8,
+    skip: bool,
+    progress: &dyn Fn(f32),
+) -> ParseReport {
+    let mut report = ParseReport {
+        skipped: skip,
+        ..Default::default()
+    };
+    let mut keep = Vec::with_capacity(rows.len());
+    let mut width = 0;
+    let step = (rows.len() / 100).max(1);
+    for (n, (row, &line)) in rows.iter().zip(lines.iter()).enumerate() {
+        if n % step == 0 {
+            progress(n as f32 / rows.len() as f32);
+        }
+        let mut malformed = false;
+        let cells = split_fields(&bytes[row.clone()], delimiter);
+        for (column, value) in cells.enumerate().skip(1) {
+            width = width.max(column);
+            if let Err(text) = parse_cell(value, delimiter) {
+                malformed = true;
+                report.add(line, column, text);
+            }
+        }
+        report.rows += malformed as usize;
+        keep.push(!(skip && malformed));
+    }
+    retain_rows(rows, &keep);
+    retain_rows(lines, &keep);
+    report.checked.extend(1..=width);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Lines and byte ranges of the rows after the header
+    fn data_rows(bytes: &[u8]) -> (Vec<usize>, Vec<Range<usize>>) {
+        split_lines(bytes).skip(1).unzip()
+    }
+
+    #[test]
+    fn test_split_lines() {
+        let bytes = b"a\tb\r\n1\t2\n\n3\t4";
+        let lines: Vec<_> = split_lines(bytes).map(|(n, r)| (n, &bytes[r])).collect();
+        assert_eq!(lines, vec![(1, &b"a\tb"[..]), (2, b"1\t2"), (4, b"3\t4")]);
+    }
+
+    #[test]
+    fn test_parse_column() {
+        let bytes = b"time\tws\n00:00\t1.5\n\n00:10\tx\n00:20";
+        let (lines, rows) = data_rows(bytes);
+        let (values, report) = parse_column(bytes, &rows, &lines, 1, b'\t');
+        assert_eq!(values[0], 1.5);
+        assert!(values[1].is_nan() && values[2].is_nan());
+        // The blank line counts
+        assert_eq!(report.first().unwrap(), "Line 4: Invalid numeric value 'x'");
+        assert_eq!(report.checked, BTreeSet::from([1]));
+
+        // Blank and NaN cells are missing, not invalid
+        let bytes = b"time\tws\n00:00\t\n00:10\tNaN\n00:20\t N/A";
+        let (lines, rows) = data_rows(bytes);
+        let (values, report) = parse_column(bytes, &rows, &lines, 1, b'\t');
+        assert!(values.iter().all(|v| v.is_nan()));
+        assert_eq!(report.total, 0);
+    }
+
+    #[test]
+    fn test_check_rows() {
+        let bytes = b"time\tws\tdir\n00:00\t1.5\tx\n00:10\t2\t\n\n00:20\t#\t?";
+        let (mut lines, mut rows) = data_rows(bytes);
+        let report = check_rows(bytes, &mut rows, &mut lines, b'\t', false, &|_| ());
+        assert_eq!(rows.len(), 3);
+        assert_eq!(report.total, 3);
+        assert_eq!(report.rows, 2);
+        assert_eq!(report.cells[0], (2, 2, "x".to_owned()));
+        assert_eq!(report.cells[1], (5, 1, "#".to_owned()));
+        assert_eq!(report.columns, BTreeMap::from([(1, 1), (2, 2)]));
+        assert_eq!(report.checked, BTreeSet::from([1, 2]));
+
+        let report = check_rows(bytes, &mut rows, &mut lines, b'\t', true, &|_| ());
+        assert!(report.skipped);
+        assert_eq!((rows.len(), lines), (1, vec![3]));
+        let (values, report) = parse_column(bytes, &rows, &[3], 1, b'\t');
+        assert_eq!(values, vec![2.0]);
+        assert_eq!(report.total, 0);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut report = ParseReport::default();
+        report.add(5, 1, "x".into());
+        let mut other = ParseReport::default();
+        other.add(3, 2, "y".into());
+        other.add(5, 2, "z".into());
+        other.checked.insert(2);
+        report.merge(other);
+        assert_eq!(report.total, 3);
+        assert_eq!(report.cells[0], (3, 2, "y".to_owned()));
+        assert_eq!(report.columns, BTreeMap::from([(1, 1), (2, 2)]));
+        assert!(report.checked.contains(&2));
+    }
+
+    #[test]
+    fn test_delimiters() {
+        assert_eq!(detect_delimiter(b"time\tws\tdir"), b'\t');
+        assert_eq!(detect_delimiter(b"time;ws;dir"), b';');
+        assert_eq!(detect_delimiter(b"\"time\",\"ws\""), b',');
+        assert_eq!(detect_delimiter(b"time"), b'\t');
+        assert_eq!(split_header("\"time\",\"ws\""), vec!["time", "ws"]);
+        // Delimiters within quotes don't separate fields
+        assert_eq!(detect_delimiter(b"\"a;b;c\",ws"), b',');
+        assert_eq!(
+            split_header("time\t\"ws\tavg\"\tdir"),
+            vec!["time", "ws\tavg", "dir"]
+        );
+
+        let bytes = b"time;ws\n00:00;1,5\n00:10;\"2\"";
+        let (lines, rows) = data_rows(bytes);
+        let (values, report) = parse_column(bytes, &rows, &lines, 1, b';');
+        assert_eq!(values, vec![1.5, 2.0]);
+        assert_eq!(report.total, 0);
+
+        // Only semicolon separated files take the comma as decimal separator
+        let bytes = b"time\tws\tdir\n00:00\t\"1,5\"\t\"2\t0\"";
+        let (lines, rows) = data_rows(bytes);
+        let (values, report) = parse_column(bytes, &rows, &lines, 1, b'\t');
+        assert!(values[0].is_nan());
+        assert_eq!(
+            report.first().unwrap(),
+            "Line 2: Invalid numeric value '\"1,5\"'"
+        );
+        let (_, report) = parse_column(bytes, &rows, &lines, 2, b'\t');
+        assert_eq!(report.cells[0].2, "\"2\t0\"");
+    }
+}
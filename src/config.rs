@@ -0,0 +1,15 @@
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::Path;
+
+// Reads a JSON or TOML file, chosen by its extension
+pub fn read_config<T: DeserializeOwned>(path: &Path, what: &str) -> Result<T, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("File read error: {}", e))?;
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("toml") => {
+            toml::from_str(&content).map_err(|e| format!("Invalid TOML {}: {}", what, e))
+        }
+        _ => serde_json::from_str(&content).map_err(|e| format!("Invalid JSON {}: {}", what, e)),
+    }
+}
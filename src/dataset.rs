@@ -0,0 +1,601 @@
+use crate::columns::{ParseReport, RawTable, Table};
+use crate::exclusions::{Category, ExclusionRow};
+use crate::naming::NameConvention;
+use crate::parquet_file::{is_parquet, read_parquet, write_parquet};
+use crate::timeline::{coverage, detect_format, parse_time, TIMESTAMP_FORMAT};
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// A point of a channel, as read from its columns
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataPoint<'a> {
+    Valid(f64),
+    NaN,
+    Excluded(f64, &'a str),
+}
+
+// Missing-value markers written as a list, as "99999, -999, 9999.9"
+pub fn parse_markers(text: &str) -> Result<Vec<f64>, String> {
+    text.split([',', ';'])
+        .map(str::trim)
+        .filter(|marker| !marker.is_empty())
+        .map(|marker| {
+            marker
+                .parse()
+                .map_err(|_| format!("Invalid missing value '{}'", marker))
+        })
+        .collect()
+}
+
+pub fn format_markers(markers: &[f64]) -> String {
+    let markers: Vec<String> = markers.iter().map(f64::to_string).collect();
+    markers.join(", ")
+}
+
+// Written for missing values in the cleaned data, NaN without markers
+pub fn first_marker(markers: &[f64]) -> f64 {
+    markers.first().copied().unwrap_or(f64::NAN)
+}
+
+const VALID: u8 = 0;
+const MISSING: u8 = 1;
+const EXCLUDED: u8 = 2;
+
+// Bytes taken by each point of a channel
+pub const POINT_BYTES: usize =
+    std::mem::size_of::<f64>() + std::mem::size_of::<u8>() + std::mem::size_of::<u32>();
+
+// What an excluded point was excluded for, and by whom
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stamp {
+    pub reason: String,
+    pub category: Option<Category>,
+    // Empty when unknown
+    pub analyst: String,
+}
+
+impl Stamp {
+    pub fn reason(reason: &str) -> Self {
+        Self {
+            reason: reason.to_owned(),
+            ..Self::default()
+        }
+    }
+}
+
+// The points of a channel stored by column. The stamps of the excluded points
+// are kept once in a table, each point keeping only its position there.
+#[derive(Default)]
+pub struct ChannelData {
+    values: Vec<f64>,
+    status: Vec<u8>,
+    stamp_ids: Vec<u32>,
+    stamps: Vec<Stamp>,
+}
+
+impl ChannelData {
+    // Values read from the data file, missing when NaN or one of the markers
+    pub fn read(values: Vec<f64>, missing: &[f64]) -> Self {
+        let status = values
+            .iter()
+            .map(|&v| match v.is_nan() || missing.contains(&v) {
+                true => MISSING,
+                false => VALID,
+            })
+            .collect();
+        Self {
+            stamp_ids: vec![0; values.len()],
+            values,
+            status,
+            stamps: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn get(&self, row: usize) -> Option<DataPoint<'_>> {
+        let value = *self.values.get(row)?;
+        Some(match self.status[row] {
+            VALID => DataPoint::Valid(value),
+            EXCLUDED => {
+                DataPoint::Excluded(value, &self.stamps[self.stamp_ids[row] as usize].reason)
+            }
+            _ => DataPoint::NaN,
+        })
+    }
+
+    // Reason, category and analyst of the point, when excluded
+    pub fn stamp(&self, row: usize) -> Option<&Stamp> {
+        (self.status.get(row) == Some(&EXCLUDED))
+            .then(|| &self.stamps[self.stamp_ids[row] as usize])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = DataPoint<'_>> + '_ {
+        (0..self.len()).map(|row| self.get(row).unwrap_or(DataPoint::NaN))
+    }
+
+    pub fn push(&mut self, point: DataPoint<'_>) {
+        let (value, status, stamp) = match point {
+            DataPoint::Valid(v) => (v, VALID, 0),
+            DataPoint::NaN => (f64::NAN, MISSING, 0),
+            // Points only carry their reason
+            DataPoint::Excluded(v, reason) => (v, EXCLUDED, self.intern(&Stamp::reason(reason))),
+        };
+        self.values.push(value);
+        self.status.push(status);
+        self.stamp_ids.push(stamp);
+    }
+
+    fn intern(&mut self, stamp: &Stamp) -> u32 {
+        match self.stamps.iter().position(|s| s == stamp) {
+            Some(id) => id as u32,
+            None => {
+                self.stamps.push(stamp.clone());
+                (self.stamps.len() - 1) as u32
+            }
+        }
+    }
+
+    // Excludes a valid point. Returns whether it was valid.
+    pub fn exclude(&mut self, row: usize, stamp: &Stamp) -> bool {
+        if self.status.get(row) != Some(&VALID) {
+            return false;
+        }
+        self.stamp_ids[row] = self.intern(stamp);
+        self.status[row] = EXCLUDED;
+        true
+    }
+
+    // Makes an excluded point valid again. Returns whether it was excluded.
+    pub fn restore(&mut self, row: usize) -> bool {
+        if self.status.get(row) != Some(&EXCLUDED) {
+            return false;
+        }
+        self.status[row] = VALID;
+        true
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.values.capacity() * POINT_BYTES
+            + self
+                .stamps
+                .iter()
+                .map(|stamp| stamp.reason.capacity() + stamp.analyst.capacity())
+                .sum::<usize>()
+    }
+}
+
+impl<'a> FromIterator<DataPoint<'a>> for ChannelData {
+    fn from_iter<I: IntoIterator<Item = DataPoint<'a>>>(points: I) -> Self {
+        let mut data = Self::default();
+        for point in points {
+            data.push(point);
+        }
+        data
+    }
+}
+
+pub struct TimeSeries {
+    pub name: String,
+    pub data: ChannelData,
+    // Channels a derived channel is computed from, empty for measured ones
+    pub sources: Vec<usize>,
+    // Column of the data file still to be parsed, until the channel is used
+    pub column: Option<usize>,
+}
+
+impl TimeSeries {
+    pub fn derived(&self) -> bool {
+        !self.sources.is_empty()
+    }
+
+    // Parses the column still left in the raw file. Returns the column and
+    // its malformed cells, or None when it was parsed already.
+    pub fn parse(
+        &mut self,
+        raw: Option<&RawTable>,
+        missing: &[f64],
+    ) -> Option<(usize, ParseReport)> {
+        let (Some(column), Some(raw)) = (self.column.take(), raw) else {
+            return None;
+        };
+        let (values, report) = raw.column(column);
+        self.data = ChannelData::read(values, missing);
+        Some((column, report))
+    }
+
+    // Valid values, NaN in place of the missing and excluded ones
+    pub fn values(&self) -> Vec<f64> {
+        self.data
+            .iter()
+            .map(|val| match val {
+                DataPoint::Valid(v) => v,
+                _ => f64::NAN,
+            })
+            .collect()
+    }
+
+    pub fn valid_rows(&self) -> Vec<bool> {
+        self.data
+            .iter()
+            .map(|val| matches!(val, DataPoint::Valid(_)))
+            .collect()
+    }
+
+    // Missing and excluded records
+    pub fn counts(&self) -> (usize, usize) {
+        self.data
+            .iter()
+            .fold((0, 0), |(missing, excluded), val| match val {
+                DataPoint::NaN => (missing + 1, excluded),
+                DataPoint::Excluded(..) => (missing, excluded + 1),
+                DataPoint::Valid(_) => (missing, excluded),
+            })
+    }
+}
+
+// A channel and its valid and total records per period
+pub type Coverage = (usize, BTreeMap<NaiveDate, (usize, usize)>);
+
+// Valid and total records of each measured channel per day, or per month
+pub fn coverage_table(timeseries: &[TimeSeries], times: &[f64], monthly: bool) -> Vec<Coverage> {
+    timeseries
+        .iter()
+        .enumerate()
+        .filter(|(_, ts)| !ts.derived())
+        .map(|(n, ts)| (n, coverage(times, &ts.valid_rows(), monthly)))
+        .collect()
+}
+
+// A pair of channels of the scatter matrix, as (x, y), and its points
+pub type MatrixCell = ((usize, usize), Vec<[f64; 2]>);
+
+// Rows where both channels of each pair are valid, taking every step-th row
+pub fn matrix_cells(timeseries: &[TimeSeries], channels: &[usize], step: usize) -> Vec<MatrixCell> {
+    let mut cells = Vec::new();
+    for (row, &y) in channels.iter().enumerate() {
+        for &x in &channels[..row] {
+            let points = timeseries[x]
+                .data
+                .iter()
+                .zip(timeseries[y].data.iter())
+                .step_by(step)
+                .filter_map(|pair| match pair {
+                    (DataPoint::Valid(x_val), DataPoint::Valid(y_val)) => Some([x_val, y_val]),
+                    _ => None,
+                })
+                .collect();
+            cells.push(((x, y), points));
+        }
+    }
+    cells
+}
+
+// Timestamps of an index as seconds, read in the format detected from them
+pub fn index_times(index: &[String]) -> Vec<f64> {
+    let format = detect_format(index).unwrap_or(TIMESTAMP_FORMAT);
+    index.iter().map(|t| parse_time(t, format)).collect()
+}
+
+// Rows whose timestamp lies within the period of an exclusion, both ends included
+pub fn covered_rows(times: &[f64], row: &ExclusionRow) -> Vec<bool> {
+    let start = row.start.and_utc().timestamp() as f64;
+    let end = row.end.and_utc().timestamp() as f64;
+    times
+        .iter()
+        .map(|time| (start..=end).contains(time))
+        .collect()
+}
+
+// Marks the valid points of the given rows as excluded. Returns the rows changed.
+pub fn exclude_rows(data: &mut ChannelData, rows: &[bool], stamp: &Stamp) -> Vec<usize> {
+    rows.iter()
+        .enumerate()
+        .filter(|&(row, &exclude)| exclude && data.exclude(row, stamp))
+        .map(|(row, _)| row)
+        .collect()
+}
+
+// Writes the channels as a tab separated file, the index first, with the
+// given text in place of the excluded and missing values
+pub fn write_cleaned(
+    writer: &mut impl Write,
+    index_name: &str,
+    index: &[String],
+    channels: &[&TimeSeries],
+    excluded: &str,
+    missing: &str,
+) -> io::Result<()> {
+    write!(writer, "{}", index_name)?;
+    for ts in channels {
+        write!(writer, "\t{}", ts.name)?;
+    }
+    writeln!(writer)?;
+
+    for (row, timestamp) in index.iter().enumerate() {
+        write!(writer, "{}", timestamp)?;
+        for ts in channels {
+            match ts.data.get(row) {
+                Some(DataPoint::Valid(v)) => write!(writer, "\t{}", v)?,
+                Some(DataPoint::Excluded(..)) => write!(writer, "\t{}", excluded)?,
+                _ => write!(writer, "\t{}", missing)?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+// A data file loaded in full, for processing without the window
+pub struct Dataset {
+    pub index_name: String,
+    pub index: Vec<String>,
+    pub times: Vec<f64>,
+    pub timeseries: Vec<TimeSeries>,
+}
+
+impl Dataset {
+    // Invalid values are read as missing, and reported along with the dataset
+    pub fn load(path: &str, missing: &[f64]) -> Result<(Self, Vec<String>), String> {
+        if is_parquet(path) {
+            return Ok((Self::from_table(read_parquet(path)?, missing)?, Vec::new()));
+        }
+
+        let (headers, raw) = RawTable::open(path, 1)?;
+        if headers.is_empty() {
+            return Err("No headers found".into());
+        }
+
+        let mut warnings = Vec::new();
+        let timeseries = headers
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(column, name)| {
+                let (values, report) = raw.column(column);
+                if let Some(e) = report.first() {
+                    warnings.push(format!(
+                        "{} in column '{}', {} cells read as missing",
+                        e, name, report.total
+                    ));
+                }
+                TimeSeries {
+                    name: name.clone(),
+                    data: ChannelData::read(values, missing),
+                    sources: Vec::new(),
+                    column: None,
+                }
+            })
+            .collect();
+        let index = raw.index();
+
+        let dataset = Self {
+            index_name: headers[0].clone(),
+            times: index_times(&index),
+            index,
+            timeseries,
+        };
+        Ok((dataset, warnings))
+    }
+
+    fn from_table(table: Table, missing: &[f64]) -> Result<Self, String> {
+        if table.headers.is_empty() {
+            return Err("No headers found".into());
+        }
+        let timeseries = table
+            .headers
+            .iter()
+            .skip(1)
+            .zip(table.columns)
+            .map(|(name, values)| TimeSeries {
+                name: name.clone(),
+                data: ChannelData::read(values, missing),
+                sources: Vec::new(),
+                column: None,
+            })
+            .collect();
+
+        Ok(Self {
+            index_name: table.headers[0].clone(),
+            times: index_times(&table.index),
+            index: table.index,
+            timeseries,
+        })
+    }
+
+    // Excludes the periods of the rows in every channel of their mast and
+    // sensor. Returns the points excluded and the rows matching no channel.
+    pub fn apply(&mut self, rows: &[ExclusionRow], convention: &NameConvention) -> (usize, usize) {
+        let names: Vec<_> = self
+            .timeseries
+            .iter()
+            .map(|ts| convention.split(&ts.name))
+            .collect();
+        let mut count = 0;
+        let mut unmatched = 0;
+        for row in rows {
+            let covered = covered_rows(&self.times, row);
+            let stamp = Stamp {
+                reason: row.reason.clone(),
+                category: row.category,
+                analyst: row.analyst.clone(),
+            };
+            let mut matched = false;
+            for (ts, names) in self.timeseries.iter_mut().zip(&names) {
+                if *names == Ok((row.mast.clone(), row.sensor.clone())) {
+                    count += exclude_rows(&mut ts.data, &covered, &stamp).len();
+                    matched = true;
+                }
+            }
+            if !matched {
+                unmatched += 1;
+            }
+        }
+        (count, unmatched)
+    }
+
+    pub fn write_cleaned(&self, path: &str, excluded: &str, missing: &str) -> Result<(), String> {
+        let channels: Vec<&TimeSeries> = self.timeseries.iter().collect();
+        if is_parquet(path) {
+            return write_parquet(path, &self.index_name, &self.index, &channels);
+        }
+        let file = File::create(path).map_err(|e| format!("File write error: {}", e))?;
+        write_cleaned(
+            &mut BufWriter::new(file),
+            &self.index_name,
+            &self.index,
+            &channels,
+            excluded,
+            missing,
+        )
+        .map_err(|e| format!("File write error: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::time;
+
+    fn dataset() -> Dataset {
+        let index: Vec<String> = ["2024-01-01 00:00", "2024-01-01 00:10", "2024-01-01 00:20"]
+            .map(String::from)
+            .to_vec();
+        let series = |name: &str, values: [f64; 3]| TimeSeries {
+            name: name.to_owned(),
+            data: ChannelData::read(values.into(), &[-999.0]),
+            sources: Vec::new(),
+            column: None,
+        };
+        Dataset {
+            index_name: "Time".into(),
+            times: index_times(&index),
+            index,
+            timeseries: vec![
+                series("M1~WS80~Mean", [5.0, 6.0, -999.0]),
+                series("M1~WS80~Std", [0.5, 0.6, 0.7]),
+                series("M1~WD80", [90.0, 95.0, 100.0]),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_markers() {
+        let markers = parse_markers("99999, -999;9999.9,").unwrap();
+        assert_eq!(markers, [99999.0, -999.0, 9999.9]);
+        assert_eq!(format_markers(&markers), "99999, -999, 9999.9");
+        assert!(parse_markers("99999, n/a").is_err());
+
+        let data = ChannelData::read(vec![9999.9, 5.0, 99999.0], &markers);
+        assert_eq!(
+            data.iter().collect::<Vec<_>>(),
+            [DataPoint::NaN, DataPoint::Valid(5.0), DataPoint::NaN]
+        );
+        assert!(first_marker(&[]).is_nan());
+    }
+
+    #[test]
+    fn test_channel_data() {
+        let mut data = ChannelData::read(vec![1.0, -999.0, 3.0, 4.0], &[-999.0]);
+        let icing = Stamp {
+            reason: "icing".into(),
+            category: Some(Category::Icing),
+            analyst: "JG".into(),
+        };
+        assert!(data.exclude(0, &icing));
+        assert!(!data.exclude(1, &icing));
+        assert!(data.exclude(3, &icing));
+        assert!(data.restore(3));
+        assert!(data.exclude(2, &Stamp::reason("tower shadow")));
+
+        // Each stamp is stored once
+        assert_eq!(data.stamps.len(), 2);
+        assert_eq!(data.stamp(0), Some(&icing));
+        assert_eq!(data.stamp(3), None);
+        assert_eq!(
+            data.iter().collect::<Vec<_>>(),
+            [
+                DataPoint::Excluded(1.0, "icing"),
+                DataPoint::NaN,
+                DataPoint::Excluded(3.0, "tower shadow"),
+                DataPoint::Valid(4.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_channel_tables() {
+        let mut dataset = dataset();
+        dataset.timeseries[1]
+            .data
+            .exclude(1, &Stamp::reason("icing"));
+        dataset.timeseries[2].sources = vec![0];
+
+        let mean = &dataset.timeseries[0];
+        assert_eq!(mean.values()[..2], [5.0, 6.0]);
+        assert!(mean.values()[2].is_nan());
+        assert_eq!(mean.valid_rows(), [true, true, false]);
+        assert_eq!(mean.counts(), (1, 0));
+        assert_eq!(dataset.timeseries[1].counts(), (0, 1));
+
+        // Derived channels have no coverage of their own
+        let table = coverage_table(&dataset.timeseries, &dataset.times, false);
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0], (0, BTreeMap::from([(date, (2, 3))])));
+        assert_eq!(table[1], (1, BTreeMap::from([(date, (2, 3))])));
+
+        let cells = matrix_cells(&dataset.timeseries, &[0, 1, 2], 1);
+        assert_eq!(
+            cells,
+            [
+                ((0, 1), vec![[5.0, 0.5]]),
+                ((0, 2), vec![[5.0, 90.0], [6.0, 95.0]]),
+                ((1, 2), vec![[0.5, 90.0], [0.7, 100.0]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut dataset = dataset();
+        let rows = vec![
+            ExclusionRow {
+                mast: "M1".into(),
+                sensor: "WS80".into(),
+                reason: "icing".into(),
+                start: time("2024-01-01 00:10"),
+                end: time("2024-01-01 00:20"),
+                category: Some(Category::Icing),
+                analyst: "JG".into(),
+            },
+            ExclusionRow {
+                mast: "M2".into(),
+                sensor: "WS80".into(),
+                reason: "icing".into(),
+                start: time("2024-01-01 00:10"),
+                end: time("2024-01-01 00:20"),
+                category: None,
+                analyst: String::new(),
+            },
+        ];
+
+        // The missing value of the mean stays missing
+        assert_eq!(dataset.apply(&rows, &NameConvention::default()), (3, 1));
+
+        let mut output = Vec::new();
+        let channels: Vec<&TimeSeries> = dataset.timeseries.iter().collect();
+        write_cleaned(&mut output, "Time", &dataset.index, &channels, "", "-999").unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Time\tM1~WS80~Mean\tM1~WS80~Std\tM1~WD80\n\
+             2024-01-01 00:00\t5\t0.5\t90\n\
+             2024-01-01 00:10\t\t\t95\n\
+             2024-01-01 00:20\t-999\t\t100\n"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use eframe::egui::plot::{GridInput, GridMark, Plot};
+use std::ops::RangeInclusive;
+
+// Channels with a word of their name being one of the tokens, ignoring case
+// and followed by a height or nothing, are treated as directions in degrees:
+// "Dir80", "WD_120" or "Wind direction" with the default tokens
+pub fn is_direction(name: &str, tokens: &[String]) -> bool {
+    let name = name.to_lowercase();
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| {
+            tokens.iter().any(|token| {
+                word.strip_prefix(token.to_lowercase().as_str())
+                    .is_some_and(|height| height.chars().all(|c| c.is_ascii_digit()))
+            })
+        })
+}
+
+fn direction_marks(input: GridInput) -> Vec<GridMark> {
+    let min = input.bounds.0.max(0.0).ceil() as i64;
+    let max = input.bounds.1.min(360.0).floor() as i64;
+    let fine = if input.base_step_size < 10.0 { 10 } else { 30 };
+
+    (min..=max)
+        .filter(|deg| deg % fine == 0)
+        .map(|deg| GridMark {
+            value: deg as f64,
+            step_size: match deg {
+                d if d % 90 == 0 => 90.0,
+                d if d % 30 == 0 => 30.0,
+                _ => 10.0,
+            },
+        })
+        .collect()
+}
+
+pub fn format_direction(value: f64, compass: bool) -> String {
+    let deg = value.round() as i64;
+    if (value - deg as f64).abs() > 1e-6 || !(0..=360).contains(&deg) {
+        return String::new();
+    }
+
+    match deg % 360 {
+        0 if compass => "N".to_owned(),
+        90 if compass => "E".to_owned(),
+        180 if compass => "S".to_owned(),
+        270 if compass => "W".to_owned(),
+        _ => format!("{}°", deg),
+    }
+}
+
+// Shifts by a full turn along the direction axes, so that data near the
+// 0°/360° seam can be matched from either side. Always includes no shift.
+pub fn seam_offsets(wrap: [bool; 2]) -> Vec<[f64; 2]> {
+    let shifts = |wraps: bool| {
+        if wraps {
+            vec![0.0, 360.0, -360.0]
+        } else {
+            vec![0.0]
+        }
+    };
+
+    shifts(wrap[0])
+        .into_iter()
+        .flat_map(|dx| shifts(wrap[1]).into_iter().map(move |dy| [dx, dy]))
+        .collect()
+}
+
+// Signed difference a - b between two directions, in -180..180 degrees
+pub fn angular_difference(a: f64, b: f64) -> f64 {
+    (a - b + 180.0).rem_euclid(360.0) - 180.0
+}
+
+// Whether a direction lies within the sector from start clockwise to end,
+// both included. Sectors may cross north, as 350°-20°.
+pub fn in_sector(direction: f64, (start, end): (f64, f64)) -> bool {
+    let width = (end - start).rem_euclid(360.0);
+    direction.is_finite() && (direction - start).rem_euclid(360.0) <= width
+}
+
+// Sectors written as "140-180, 350-20"
+pub fn parse_sectors(text: &str) -> Result<Vec<(f64, f64)>, String> {
+    text.split([',', ';'])
+        .filter(|sector| !sector.trim().is_empty())
+        .map(|sector| {
+            let invalid = || format!("Invalid sector '{}', write it as start-end", sector.trim());
+            let (start, end) = sector.split_once('-').ok_or_else(invalid)?;
+            let angle = |a: &str| {
+                a.trim()
+                    .trim_end_matches('°')
+                    .parse::<f64>()
+                    .map_err(|_| invalid())
+            };
+            Ok((angle(start)?, angle(end)?))
+        })
+        .collect()
+}
+
+// Sectors written back as text, in the form parse_sectors reads
+pub fn format_sectors(sectors: &[(f64, f64)]) -> String {
+    sectors
+        .iter()
+        .map(|(start, end)| format!("{}-{}", start, end))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Bounds of sector n of a rose of the given number of sectors, the first
+// centered on north
+pub fn rose_sector(n: usize, sectors: usize) -> (f64, f64) {
+    let width = 360.0 / sectors as f64;
+    let center = n as f64 * width;
+    (
+        (center - width / 2.0).rem_euclid(360.0),
+        (center + width / 2.0).rem_euclid(360.0),
+    )
+}
+
+// Sector of a rose a direction falls in
+pub fn rose_index(direction: f64, sectors: usize) -> usize {
+    let width = 360.0 / sectors as f64;
+    ((direction + width / 2.0).rem_euclid(360.0) / width) as usize % sectors
+}
+
+// Share of the records in each sector of a rose, weighted by the given values
+// (e.g. the wind speed) when any. Records missing either value are left out.
+pub fn rose(directions: &[f64], weights: Option<&[f64]>, sectors: usize) -> Vec<f64> {
+    let mut totals = vec![0.0; sectors];
+    for (row, &direction) in directions.iter().enumerate() {
+        let weight = weights.map_or(1.0, |w| w.get(row).copied().unwrap_or(f64::NAN));
+        if direction.is_finite() && weight.is_finite() {
+            totals[rose_index(direction, sectors)] += weight;
+        }
+    }
+    let sum: f64 = totals.iter().sum();
+    if sum > 0.0 {
+        totals.iter_mut().for_each(|total| *total /= sum);
+    }
+    totals
+}
+
+// Formats the given plot axis (0 = x, 1 = y) as a 0-360° direction axis
+pub fn direction_axis(plot: Plot, axis: usize, compass: bool) -> Plot {
+    let formatter =
+        move |value: f64, _range: &RangeInclusive<f64>| format_direction(value, compass);
+    match axis {
+        0 => plot
+            .x_axis_formatter(formatter)
+            .x_grid_spacer(direction_marks)
+            .include_x(0.0)
+            .include_x(360.0),
+        _ => plot
+            .y_axis_formatter(formatter)
+            .y_grid_spacer(direction_marks)
+            .include_y(0.0)
+            .include_y(360.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_direction() {
+        let tokens = ["Dir", "WD", "Direction"].map(String::from);
+        assert!(is_direction("M1~Dir80", &tokens));
+        assert!(is_direction("M1~WD120~deg", &tokens));
+        assert!(is_direction("Wind Direction", &tokens));
+        assert!(!is_direction("M1~WS80", &tokens));
+        assert!(!is_direction("M1~WDSD80", &tokens));
+        // Names merely holding a token are not directions
+        assert!(!is_direction("M1~Dirt80", &tokens));
+        assert!(!is_direction("Indirect", &tokens));
+        assert!(is_direction("M1~Azimuth", &["azimuth".into()]));
+    }
+
+    #[test]
+    fn test_seam_offsets() {
+        assert_eq!(seam_offsets([false, false]), vec![[0.0, 0.0]]);
+        assert_eq!(seam_offsets([false, true]).len(), 3);
+        assert_eq!(seam_offsets([true, true]).len(), 9);
+        assert_eq!(seam_offsets([true, true])[0], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_angular_difference() {
+        assert_eq!(angular_difference(10.0, 350.0), 20.0);
+        assert_eq!(angular_difference(350.0, 10.0), -20.0);
+        assert_eq!(angular_difference(90.0, 60.0), 30.0);
+        assert_eq!(angular_difference(0.0, 180.0), -180.0);
+    }
+
+    #[test]
+    fn test_sectors() {
+        let sectors = parse_sectors("140-180, 350°-20°").unwrap();
+        assert_eq!(sectors, vec![(140.0, 180.0), (350.0, 20.0)]);
+        assert!(in_sector(160.0, sectors[0]));
+        assert!(!in_sector(200.0, sectors[0]));
+        assert!(in_sector(5.0, sectors[1]) && in_sector(355.0, sectors[1]));
+        assert!(!in_sector(180.0, sectors[1]) && !in_sector(f64::NAN, sectors[1]));
+        assert_eq!(
+            parse_sectors("140").unwrap_err(),
+            "Invalid sector '140', write it as start-end"
+        );
+    }
+
+    #[test]
+    fn test_rose() {
+        let directions = [0.0, 350.0, 90.0, 100.0, f64::NAN];
+        assert_eq!(rose(&directions, None, 4), vec![0.5, 0.5, 0.0, 0.0]);
+        let speeds = [1.0, 1.0, 6.0, f64::NAN, 2.0];
+        assert_eq!(
+            rose(&directions, Some(&speeds), 4),
+            vec![0.25, 0.75, 0.0, 0.0]
+        );
+
+        assert_eq!(rose_sector(0, 4), (315.0, 45.0));
+        assert_eq!(rose_sector(1, 16), (11.25, 33.75));
+        assert_eq!(rose_index(46.0, 4), 1);
+        let sectors = [rose_sector(0, 4), (140.0, 180.0)];
+        assert_eq!(format_sectors(&sectors), "315-45, 140-180");
+        assert_eq!(parse_sectors(&format_sectors(&sectors)).unwrap(), sectors);
+    }
+
+    #[test]
+    fn test_format_direction() {
+        assert_eq!(format_direction(90.0, true), "E");
+        assert_eq!(format_direction(360.0, true), "N");
+        assert_eq!(format_direction(90.0, false), "90°");
+        assert_eq!(format_direction(120.0, true), "120°");
+        assert_eq!(format_direction(12.5, true), "");
+        assert_eq!(format_direction(-30.0, true), "");
+    }
+}
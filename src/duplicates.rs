@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+// What is kept of the rows sharing a timestamp
+#[derive(Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    KeepFirst,
+    KeepLast,
+    Average,
+    Error,
+}
+
+impl DuplicatePolicy {
+    pub const ALL: [DuplicatePolicy; 4] = [
+        DuplicatePolicy::KeepFirst,
+        DuplicatePolicy::KeepLast,
+        DuplicatePolicy::Average,
+        DuplicatePolicy::Error,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DuplicatePolicy::KeepFirst => "Keep first",
+            DuplicatePolicy::KeepLast => "Keep last",
+            DuplicatePolicy::Average => "Average",
+            DuplicatePolicy::Error => "Refuse the file",
+        }
+    }
+}
+
+// Rows sharing each repeated timestamp, in order of first appearance.
+// Timestamps that can't be read are never duplicates.
+pub fn duplicate_groups(times: &[f64]) -> Vec<Vec<usize>> {
+    let mut rows: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (row, time) in times.iter().enumerate() {
+        if time.is_finite() {
+            rows.entry(time.to_bits()).or_default().push(row);
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = rows.into_values().filter(|rows| rows.len() > 1).collect();
+    groups.sort_unstable_by_key(|rows| rows[0]);
+    groups
+}
+
+// Rows left once each group is reduced to one. Averaged groups keep their
+// first row, meant to hold the mean.
+pub fn kept_rows(len: usize, groups: &[Vec<usize>], policy: DuplicatePolicy) -> Vec<bool> {
+    let mut keep = vec![true; len];
+    for rows in groups {
+        let kept = match policy {
+            DuplicatePolicy::KeepLast => rows[rows.len() - 1],
+            _ => rows[0],
+        };
+        for &row in rows {
+            keep[row] = row == kept;
+        }
+    }
+    keep
+}
+
+// Puts in the first row of each group the mean of its readable values,
+// leaving out the missing value markers. Directions in degrees are averaged
+// as angles, so that 350° and 10° give 0°.
+pub fn average_groups(values: &mut [f64], groups: &[Vec<usize>], missing: &[f64], direction: bool) {
+    for rows in groups {
+        let readable: Vec<f64> = rows
+            .iter()
+            .map(|&row| values[row])
+            .filter(|v| v.is_finite() && !missing.contains(v))
+            .collect();
+        values[rows[0]] = match (readable.len(), direction) {
+            (0, _) => f64::NAN,
+            (_, true) => {
+                let (sin, cos) = readable.iter().fold((0.0, 0.0), |(sin, cos), v| {
+                    (sin + v.to_radians().sin(), cos + v.to_radians().cos())
+                });
+                sin.atan2(cos).to_degrees().rem_euclid(360.0)
+            }
+            (n, false) => readable.iter().sum::<f64>() / n as f64,
+        };
+    }
+}
+
+// Items of the rows kept
+pub fn retain_rows<T>(items: &mut Vec<T>, keep: &[bool]) {
+    let mut row = 0;
+    items.retain(|_| {
+        row += 1;
+        keep[row - 1]
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicates() {
+        let times = [0.0, 600.0, 0.0, f64::NAN, f64::NAN, 1200.0, 600.0, 0.0];
+        let groups = duplicate_groups(&times);
+        assert_eq!(groups, vec![vec![0, 2, 7], vec![1, 6]]);
+
+        let keep = kept_rows(times.len(), &groups, DuplicatePolicy::KeepLast);
+        let mut rows: Vec<usize> = (0..times.len()).collect();
+        retain_rows(&mut rows, &keep);
+        assert_eq!(rows, [3, 4, 5, 6, 7]);
+
+        let mut values = [1.0, 5.0, 3.0, 0.0, 0.0, 7.0, f64::NAN, 99999.0];
+        average_groups(&mut values, &groups, &[99999.0], false);
+        assert_eq!(values[..2], [2.0, 5.0]);
+    }
+
+    #[test]
+    fn test_average_directions() {
+        let groups = [vec![0, 1, 2], vec![3, 4]];
+        let mut values = [350.0, 10.0, 99999.0, 80.0, 100.0];
+        average_groups(&mut values, &groups, &[99999.0], true);
+        assert!(values[0].min(360.0 - values[0]) < 1e-9);
+        assert!((values[3] - 90.0).abs() < 1e-9);
+    }
+}
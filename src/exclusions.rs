@@ -0,0 +1,583 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// One exported exclusion period of a sensor
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExclusionRow {
+    pub mast: String,
+    pub sensor: String,
+    pub reason: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub category: Option<Category>,
+    // Who excluded the period, empty when unknown
+    pub analyst: String,
+}
+
+// Kinds of exclusion, for tools that can't rely on the free-text reasons
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Category {
+    Icing,
+    Shadow,
+    Fault,
+    Maintenance,
+    Suspect,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Icing,
+        Category::Shadow,
+        Category::Fault,
+        Category::Maintenance,
+        Category::Suspect,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Category::Icing => "Icing",
+            Category::Shadow => "Shadow",
+            Category::Fault => "Fault",
+            Category::Maintenance => "Maintenance",
+            Category::Suspect => "Suspect",
+        }
+    }
+
+    // The category of a label, None when empty
+    pub fn parse(text: &str) -> Result<Option<Self>, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(None);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|category| category.label().eq_ignore_ascii_case(text))
+            .map(Some)
+            .ok_or(format!("Unknown category '{}'", text))
+    }
+}
+
+// Label of an optional category, empty without one
+pub fn category_label(category: Option<Category>) -> &'static str {
+    category.map_or("", |category| category.label())
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReviewStatus {
+    Proposed,
+    Approved,
+    Rejected,
+}
+
+impl ReviewStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ReviewStatus::Proposed => "Proposed",
+            ReviewStatus::Approved => "Approved",
+            ReviewStatus::Rejected => "Rejected",
+        }
+    }
+}
+
+// Layouts the exclusion periods can be exported in
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    // Tab separated, as read back by the import
+    Tsv,
+    WindFarmer,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 2] = [ExportFormat::Tsv, ExportFormat::WindFarmer];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Tsv => "Tab separated",
+            ExportFormat::WindFarmer => "WindFarmer: Analyst",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Tsv => "txt",
+            ExportFormat::WindFarmer => "xml",
+        }
+    }
+}
+
+// Mast, sensor, reason, category and analyst of a period
+type PeriodKey = (String, String, String, Option<Category>, String);
+
+// Merges overlapping periods of the same mast, sensor, reason, category and
+// analyst into single rows, sorted by them and then by start
+pub fn merge_periods(periods: Vec<ExclusionRow>) -> Vec<ExclusionRow> {
+    merge_period_groups(&periods)
+        .into_iter()
+        .map(|(row, _)| row)
+        .collect()
+}
+
+// Merged as by `merge_periods`, each row with the positions of the periods
+// it's made of
+pub fn merge_period_groups(periods: &[ExclusionRow]) -> Vec<(ExclusionRow, Vec<usize>)> {
+    let mut groups: BTreeMap<PeriodKey, Vec<usize>> = BTreeMap::new();
+    for (n, row) in periods.iter().enumerate() {
+        let key = (
+            row.mast.clone(),
+            row.sensor.clone(),
+            row.reason.clone(),
+            row.category,
+            row.analyst.clone(),
+        );
+        groups.entry(key).or_default().push(n);
+    }
+
+    let mut merged = Vec::new();
+    for ((mast, sensor, reason, category, analyst), mut members) in groups.into_iter() {
+        members.sort_by_key(|&n| periods[n].start);
+
+        let mut push = |start, end, members| {
+            let row = ExclusionRow {
+                mast: mast.clone(),
+                sensor: sensor.clone(),
+                reason: reason.clone(),
+                start,
+                end,
+                category,
+                analyst: analyst.clone(),
+            };
+            merged.push((row, members));
+        };
+
+        let (mut current_start, mut current_end) =
+            (periods[members[0]].start, periods[members[0]].end);
+        let mut current = vec![members[0]];
+        for n in members.into_iter().skip(1) {
+            let (start, end) = (periods[n].start, periods[n].end);
+            if start <= current_end {
+                current_end = current_end.max(end);
+                current.push(n);
+            } else {
+                push(current_start, current_end, std::mem::take(&mut current));
+                current_start = start;
+                current_end = end;
+                current.push(n);
+            }
+        }
+
+        push(current_start, current_end, current);
+    }
+
+    merged
+}
+
+// Whether each period lies within one of the existing periods of the same
+// mast, sensor, reason, category and analyst, e.g. of a file appended to
+pub fn covered(periods: &[ExclusionRow], existing: &[ExclusionRow]) -> Vec<bool> {
+    periods
+        .iter()
+        .map(|period| {
+            existing.iter().any(|row| {
+                row.mast == period.mast
+                    && row.sensor == period.sensor
+                    && row.reason == period.reason
+                    && row.category == period.category
+                    && row.analyst == period.analyst
+                    && row.start <= period.start
+                    && period.end <= row.end
+            })
+        })
+        .collect()
+}
+
+fn check_name(kind: &str, name: &str) -> Option<String> {
+    if name.trim().is_empty() {
+        Some(format!("missing {}", kind))
+    } else if name.trim() != name || name.contains(['\t', '\n', '\r', '~']) {
+        Some(format!("invalid {} name '{}'", kind, name))
+    } else {
+        None
+    }
+}
+
+// Checks the rows against the import schema: every column filled in, names
+// without separators or padding, and periods of each sensor and reason in
+// chronological order without overlaps. Returns one message per violation.
+pub fn validate_rows(rows: &[ExclusionRow]) -> Vec<String> {
+    let mut violations = Vec::new();
+    let mut last_end: BTreeMap<(&str, &str, &str), NaiveDateTime> = BTreeMap::new();
+
+    for (n, row) in rows.iter().enumerate() {
+        let line = n + 1;
+        for (kind, name) in [
+            ("mast", &row.mast),
+            ("sensor", &row.sensor),
+            ("reason", &row.reason),
+        ] {
+            if let Some(problem) = check_name(kind, name) {
+                violations.push(format!("Row {}: {}", line, problem));
+            }
+        }
+
+        if row.start > row.end {
+            violations.push(format!("Row {}: period ends before it starts", line));
+        }
+
+        let key = (row.mast.as_str(), row.sensor.as_str(), row.reason.as_str());
+        if let Some(end) = last_end.get(&key) {
+            if row.start < *end {
+                violations.push(format!(
+                    "Row {}: overlaps or precedes the previous period of {} {} '{}'",
+                    line, row.mast, row.sensor, row.reason
+                ));
+            }
+        }
+        last_end.insert(key, row.end);
+    }
+
+    violations
+}
+
+// Columns of the tab separated export
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ExportColumn {
+    Mast,
+    Sensor,
+    Reason,
+    Start,
+    End,
+    Exported,
+    Analyst,
+    Category,
+    Comment,
+}
+
+impl ExportColumn {
+    pub const ALL: [ExportColumn; 9] = [
+        ExportColumn::Mast,
+        ExportColumn::Sensor,
+        ExportColumn::Reason,
+        ExportColumn::Start,
+        ExportColumn::End,
+        ExportColumn::Exported,
+        ExportColumn::Analyst,
+        ExportColumn::Category,
+        ExportColumn::Comment,
+    ];
+
+    // Also its name in the header row
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportColumn::Mast => "Mast",
+            ExportColumn::Sensor => "Sensor",
+            ExportColumn::Reason => "Reason",
+            ExportColumn::Start => "Start",
+            ExportColumn::End => "End",
+            ExportColumn::Exported => "Exported",
+            ExportColumn::Analyst => "Analyst",
+            ExportColumn::Category => "Category",
+            ExportColumn::Comment => "Comment",
+        }
+    }
+}
+
+// Columns of the tab separated export in order, each with whether it is
+// written, and whether a header row goes first
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExportLayout {
+    pub columns: Vec<(ExportColumn, bool)>,
+    pub header: bool,
+}
+
+impl Default for ExportLayout {
+    fn default() -> Self {
+        Self {
+            columns: ExportColumn::ALL
+                .into_iter()
+                .map(|column| (column, column != ExportColumn::Comment))
+                .collect(),
+            header: false,
+        }
+    }
+}
+
+impl ExportLayout {
+    // Adds the columns a layout saved by an older version lacks, written and
+    // right after the column they follow by default, so that a layout read
+    // back without a header keeps its positions
+    pub fn complete(mut self) -> Self {
+        for (n, column) in ExportColumn::ALL.into_iter().enumerate() {
+            if self.columns.iter().any(|(c, _)| *c == column) {
+                continue;
+            }
+            let after = n
+                .checked_sub(1)
+                .and_then(|previous| {
+                    let previous = ExportColumn::ALL[previous];
+                    self.columns.iter().position(|(c, _)| *c == previous)
+                })
+                .map_or(0, |position| position + 1);
+            self.columns.insert(after, (column, true));
+        }
+        self
+    }
+
+    pub fn written(&self) -> Vec<ExportColumn> {
+        self.columns
+            .iter()
+            .filter(|(_, written)| *written)
+            .map(|(column, _)| *column)
+            .collect()
+    }
+
+    // Whether the header row is written: when chosen, and whenever the
+    // columns don't start with the default ones, as files without it are read
+    // back from those positions
+    pub fn writes_header(&self) -> bool {
+        self.header
+            || !self
+                .written()
+                .starts_with(&ExportLayout::default().written())
+    }
+}
+
+// Positions of the mast, sensor, reason, start and end columns, and of the
+// analyst and category ones if any
+type Positions = ([usize; 5], [Option<usize>; 2]);
+
+// The columns of a header row, None when the line isn't one
+pub fn header_columns(line: &str) -> Option<Vec<ExportColumn>> {
+    line.split('\t')
+        .map(|name| {
+            ExportColumn::ALL
+                .into_iter()
+                .find(|column| column.label().eq_ignore_ascii_case(name.trim()))
+        })
+        .collect()
+}
+
+// Where the columns are when the line is a header row
+fn header_positions(line: &str) -> Option<Result<Positions, String>> {
+    let columns = header_columns(line)?;
+
+    let mut positions = [0; 5];
+    for (position, needed) in positions.iter_mut().zip(ExportColumn::ALL) {
+        match columns.iter().position(|column| *column == needed) {
+            Some(n) => *position = n,
+            None => return Some(Err(format!("The header has no {} column", needed.label()))),
+        }
+    }
+    let optional = [ExportColumn::Analyst, ExportColumn::Category]
+        .map(|needed| columns.iter().position(|column| *column == needed));
+    Some(Ok((positions, optional)))
+}
+
+// Exclusions as written by the export: mast, sensor, reason, start and end,
+// followed by the export time, which is ignored, the analyst and the
+// category. With a header row the columns may come in any order. Periods may
+// be written with or without seconds.
+pub fn read_exclusions(content: &str) -> Result<Vec<ExclusionRow>, String> {
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .peekable();
+    let (mut positions, mut optional) = ([0, 1, 2, 3, 4], [Some(6), Some(7)]);
+    if let Some(header) = lines.peek().and_then(|(_, line)| header_positions(line)) {
+        (positions, optional) = header?;
+        lines.next();
+    }
+    let needed = positions.iter().max().map_or(0, |last| last + 1);
+
+    lines
+        .map(|(n, line)| {
+            let line_number = n + 1;
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < needed {
+                return Err(format!(
+                    "Line {}: Expected at least {} columns, found {}",
+                    line_number,
+                    needed,
+                    fields.len()
+                ));
+            }
+            let time = |value: &str| {
+                ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+                    .iter()
+                    .find_map(|fmt| NaiveDateTime::parse_from_str(value.trim(), fmt).ok())
+                    .ok_or(format!("Line {}: Invalid time '{}'", line_number, value))
+            };
+            let [analyst, category] =
+                optional.map(|n| n.and_then(|n| fields.get(n)).map_or("", |f| f.trim()));
+
+            Ok(ExclusionRow {
+                mast: fields[positions[0]].to_owned(),
+                sensor: fields[positions[1]].to_owned(),
+                reason: fields[positions[2]].to_owned(),
+                start: time(fields[positions[3]])?,
+                end: time(fields[positions[4]])?,
+                category: Category::parse(category)
+                    .map_err(|e| format!("Line {}: {}", line_number, e))?,
+                analyst: analyst.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::time;
+
+    fn row(sensor: &str, start: &str, end: &str) -> ExclusionRow {
+        ExclusionRow {
+            mast: "M1".to_owned(),
+            sensor: sensor.to_owned(),
+            reason: "icing".to_owned(),
+            start: time(start),
+            end: time(end),
+            category: None,
+            analyst: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_merge_periods() {
+        let merged = merge_periods(vec![
+            row("WS80", "2024-01-01 00:20", "2024-01-01 00:40"),
+            row("WS60", "2024-01-01 00:00", "2024-01-01 00:20"),
+            row("WS80", "2024-01-01 00:00", "2024-01-01 00:20"),
+            row("WS80", "2024-01-01 02:00", "2024-01-01 02:20"),
+        ]);
+
+        assert_eq!(merged.len(), 3);
+        assert_eq!(
+            merged[0],
+            row("WS60", "2024-01-01 00:00", "2024-01-01 00:20")
+        );
+        assert_eq!(
+            merged[1],
+            row("WS80", "2024-01-01 00:00", "2024-01-01 00:40")
+        );
+        assert_eq!(
+            merged[2],
+            row("WS80", "2024-01-01 02:00", "2024-01-01 02:20")
+        );
+
+        let periods = [
+            row("WS80", "2024-01-01 00:20", "2024-01-01 00:40"),
+            row("WS60", "2024-01-01 00:00", "2024-01-01 00:20"),
+            row("WS80", "2024-01-01 00:00", "2024-01-01 00:20"),
+        ];
+        let members: Vec<Vec<usize>> = merge_period_groups(&periods)
+            .into_iter()
+            .map(|(_, members)| members)
+            .collect();
+        assert_eq!(members, [vec![1], vec![2, 0]]);
+    }
+
+    #[test]
+    fn test_covered() {
+        let existing = [row("WS80", "2024-01-01 00:00", "2024-01-01 00:40")];
+        let periods = [
+            row("WS80", "2024-01-01 00:10", "2024-01-01 00:20"),
+            row("WS80", "2024-01-01 00:30", "2024-01-01 00:50"),
+            row("WS60", "2024-01-01 00:10", "2024-01-01 00:20"),
+        ];
+        assert_eq!(covered(&periods, &existing), [true, false, false]);
+
+        // Excluded again under another category or by another analyst
+        let mut recategorized = periods[0].clone();
+        recategorized.category = Some(Category::Icing);
+        let mut reassigned = periods[0].clone();
+        reassigned.analyst = "JG".to_owned();
+        assert_eq!(
+            covered(&[recategorized, reassigned], &existing),
+            [false, false]
+        );
+    }
+
+    #[test]
+    fn test_validate_rows() {
+        let mut rows = merge_periods(vec![
+            row("WS80", "2024-01-01 00:00", "2024-01-01 00:20"),
+            row("WS80", "2024-01-01 02:00", "2024-01-01 02:20"),
+        ]);
+        assert!(validate_rows(&rows).is_empty());
+
+        rows.push(row("WS80", "2024-01-01 02:10", "2024-01-01 02:30"));
+        rows.push(row("", "2024-01-01 03:00", "2024-01-01 02:00"));
+        let violations = validate_rows(&rows);
+        assert_eq!(violations.len(), 3);
+        assert!(violations[0].starts_with("Row 3: overlaps"));
+        assert_eq!(violations[1], "Row 4: missing sensor");
+        assert_eq!(violations[2], "Row 4: period ends before it starts");
+    }
+
+    #[test]
+    fn test_read_exclusions() {
+        let content = "M1\tWS80\ticing\t2024-01-01 00:00:00\t2024-01-01 00:20:00\t2024-02-01 10:00:00\tJG\ticing\n\n\
+                       M1\tWS60\ticing\t2024-01-01 00:00\t2024-01-01 00:20\n";
+        let rows = read_exclusions(content).unwrap();
+        assert_eq!(rows[0].analyst, "JG");
+        assert_eq!(rows[0].category, Some(Category::Icing));
+        assert_eq!(rows[1], row("WS60", "2024-01-01 00:00", "2024-01-01 00:20"));
+
+        assert_eq!(
+            read_exclusions("M1\tWS80\ticing\tyesterday\ttoday").unwrap_err(),
+            "Line 1: Invalid time 'yesterday'"
+        );
+        assert!(read_exclusions("M1\tWS80").is_err());
+        assert_eq!(
+            read_exclusions("M1\tWS80\ticing\t2024-01-01 00:00\t2024-01-01 00:20\t\t\tfrost")
+                .unwrap_err(),
+            "Line 1: Unknown category 'frost'"
+        );
+    }
+
+    #[test]
+    fn test_read_with_header() {
+        let content = "Start\tEnd\tComment\tMast\tSensor\tReason\n\
+                       2024-01-01 00:00\t2024-01-01 00:20\tv2\tM1\tWS80\ticing\n";
+        let rows = read_exclusions(content).unwrap();
+        assert_eq!(rows, [row("WS80", "2024-01-01 00:00", "2024-01-01 00:20")]);
+
+        assert_eq!(
+            read_exclusions("Mast\tSensor\tStart\tEnd\n").unwrap_err(),
+            "The header has no Reason column"
+        );
+        let default = ExportLayout::default();
+        assert_eq!(default.written().len(), 8);
+        let old = ExportLayout {
+            columns: vec![(ExportColumn::Mast, true)],
+            header: true,
+        };
+        assert_eq!(old.complete().columns.len(), ExportColumn::ALL.len());
+
+        // A layout saved before the category, with the comment written,
+        // gets it back at its default position
+        let mut before_category = ExportLayout::default();
+        before_category
+            .columns
+            .retain(|(column, _)| *column != ExportColumn::Category);
+        before_category.columns[7].1 = true;
+        let completed = before_category.complete();
+        assert_eq!(completed.columns[7].0, ExportColumn::Category);
+        assert!(!completed.writes_header());
+    }
+
+    #[test]
+    fn test_writes_header() {
+        let mut layout = ExportLayout::default();
+        assert!(!layout.writes_header());
+        // Columns added after the default ones keep their positions
+        layout.columns[8].1 = true;
+        assert!(!layout.writes_header());
+        layout.columns.swap(0, 1);
+        assert!(layout.writes_header());
+        let mut layout = ExportLayout::default();
+        layout.columns[6].1 = false;
+        assert!(layout.writes_header());
+    }
+}
@@ -1,35 +1,162 @@
 use chrono::{NaiveDate, Utc};
-use std::fs::File;
-use std::io::Write;
+use directories_next::ProjectDirs;
+use ed25519_dalek::{Signature, VerifyingKey};
+use std::fs;
+use std::path::PathBuf;
 
+// Expiry of the build, moved three months ahead by build.rs on every build.
+// A license key with a later date extends it.
 const EXPIRATION_DATE: &str = "2025-06-23";
 
-pub fn panic_if_expired() {
-    let expiration_date = NaiveDate::parse_from_str(EXPIRATION_DATE, "%Y-%m-%d").unwrap();
+// Days the app keeps working after the expiry, warning about it
+const GRACE_DAYS: i64 = 14;
+pub const CONTACT: &str = "Javier Guallart <javier.guallart@dnv.com>";
+// Also names the folder eframe keeps the app's storage in, where the key is
+// saved
+pub const APP_NAME: &str = "Manual Data cleaner";
+const LICENSE_FILE: &str = "manual_data_cleaner.license";
+// Public half of the Ed25519 key pair license keys are signed with, as 64 hex
+// digits in this variable at build time. Builds without it accept no key.
+const LICENSE_PUBLIC_KEY: Option<&str> = option_env!("MANUAL_DATA_CLEANER_LICENSE_PUBLIC_KEY");
 
-    let now = Utc::now().date_naive();
-    if now > expiration_date {
-        let message =
-            format!("The software has expired. Please contact the developer for an update: Javier Guallart <javier.guallart@dnv.com>");
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum License {
+    Valid(NaiveDate),
+    // Expired on the date, still working for the given days
+    Grace(NaiveDate, i64),
+    Expired(NaiveDate),
+}
+
+impl License {
+    // State of the license on the given day, with the expiry of the build or
+    // of the key when later
+    pub fn check(today: NaiveDate, key: Option<NaiveDate>) -> Self {
+        let build = NaiveDate::parse_from_str(EXPIRATION_DATE, "%Y-%m-%d").unwrap_or(NaiveDate::MIN);
+        let expiry = key.map_or(build, |date| date.max(build));
+
+        let overdue = (today - expiry).num_days();
+        match overdue {
+            d if d <= 0 => License::Valid(expiry),
+            d if d <= GRACE_DAYS => License::Grace(expiry, GRACE_DAYS - d),
+            _ => License::Expired(expiry),
+        }
+    }
 
-        let mut log = File::create("manual_data_cleaner_expired.log").unwrap();
-        let _ = writeln!(log, "{}", message);
-        panic!("{}", message)
+    // With the saved key, if any
+    pub fn current() -> Self {
+        let key = license_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|key| parse_key(&key).ok());
+        Self::check(Utc::now().date_naive(), key)
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            License::Valid(date) => format!("Licensed until {}", date),
+            License::Grace(date, days) => format!(
+                "The license expired on {}. The app keeps working for {} more days, contact {} for a new key.",
+                date, days, CONTACT
+            ),
+            License::Expired(date) => format!(
+                "The software expired on {}. Please contact the developer for an update or a new key: {}",
+                date, CONTACT
+            ),
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// Keys are written as the expiry date followed by the hex Ed25519 signature
+// of the date, as "2026-12-31-" and 128 hex digits
+pub fn parse_key(key: &str) -> Result<NaiveDate, String> {
+    let public = LICENSE_PUBLIC_KEY
+        .ok_or("This build has no public key to check license keys with")?;
+    let public = decode_hex(public).ok_or("Invalid license public key in this build")?;
+    let public = VerifyingKey::from_bytes(&public).map_err(|e| e.to_string())?;
+    verify_key(key, &public)
+}
 
-    #[test]
-    fn test_chrono() {
-        panic_if_expired();
+fn verify_key(key: &str, public: &VerifyingKey) -> Result<NaiveDate, String> {
+    let invalid = || "Invalid license key".to_owned();
+    let (date, signature) = key.trim().rsplit_once('-').ok_or_else(invalid)?;
+    let signature = decode_hex(signature).ok_or_else(invalid)?;
+    public
+        .verify_strict(date.as_bytes(), &Signature::from_bytes(&signature))
+        .map_err(|_| invalid())?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| invalid())
+}
+
+fn decode_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    let mut bytes = [0; N];
+    if hex.len() != 2 * bytes.len() || !hex.is_ascii() {
+        return None;
+    }
+    for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
     }
+    Some(bytes)
 }
 
+// In the app's storage folder, which unlike the executable's is writable by
+// the user
+fn license_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", APP_NAME).map(|dirs| dirs.data_dir().join(LICENSE_FILE))
+}
+
+// Checks a new key and saves it for the next runs. Returns its expiry.
+pub fn save_key(key: &str) -> Result<NaiveDate, String> {
+    let date = parse_key(key)?;
+    let path = license_path().ok_or("License write error: No storage folder")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("License write error: {}", e))?;
+    }
+    fs::write(path, key.trim()).map_err(|e| format!("License write error: {}", e))?;
+    Ok(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
 
+    #[test]
+    fn test_license() {
+        let build = date(EXPIRATION_DATE);
+        assert_eq!(License::check(build, None), License::Valid(build));
+        let grace = build + chrono::Duration::days(4);
+        assert_eq!(License::check(grace, None), License::Grace(build, GRACE_DAYS - 4));
+        let late = build + chrono::Duration::days(GRACE_DAYS + 1);
+        assert_eq!(License::check(late, None), License::Expired(build));
 
+        // A key extends the build's expiry, but never shortens it
+        let extended = build + chrono::Duration::days(365);
+        assert_eq!(License::check(late, Some(extended)), License::Valid(extended));
+        let earlier = build - chrono::Duration::days(1);
+        assert_eq!(License::check(build, Some(earlier)), License::Valid(build));
+    }
 
+    #[test]
+    fn test_verify_key() {
+        // A throwaway pair, as the real private key isn't in the repository
+        let signing = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let public = signing.verifying_key();
+        let sign = |date: &str| {
+            let signature = ed25519_dalek::Signer::sign(&signing, date.as_bytes()).to_bytes();
+            let hex: String = signature.iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("{}-{}", date, hex)
+        };
 
+        let key = sign("2026-12-31");
+        assert_eq!(verify_key(&key, &public), Ok(date("2026-12-31")));
+        assert_eq!(verify_key(&key.to_lowercase(), &public), Ok(date("2026-12-31")));
+        // The signature of another date, or signed with another key
+        let forged = key.replacen("2026", "2027", 1);
+        assert!(verify_key(&forged, &public).is_err());
+        assert!(parse_key(&key).is_err());
+        assert_eq!(decode_hex::<2>("0aFF"), Some([10, 255]));
+        assert_eq!(decode_hex::<2>("0aF"), None);
+        assert!(verify_key("2026-12-31", &public).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+// Channels computed from others with arithmetic expressions, as
+// "TI = M1~WSSD80 / M1~WS80". Channels are written by name, or between square
+// brackets when the name has spaces or operators, as [Wind speed 80 m].
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+    Open,
+    Close,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || "_~.".contains(c)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                number.push(c);
+            }
+            let value = number
+                .parse()
+                .map_err(|_| format!("Invalid number '{}'", number))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|c| is_name_char(*c)) {
+                name.push(c);
+            }
+            tokens.push(Token::Name(name));
+        } else if c == '[' {
+            chars.next();
+            let name: String = chars.by_ref().take_while(|c| *c != ']').collect();
+            tokens.push(Token::Name(name.trim().to_owned()));
+        } else {
+            chars.next();
+            tokens.push(match c {
+                '+' | '-' | '*' | '/' | '^' => Token::Operator(c),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                _ => return Err(format!("Unexpected '{}'", c)),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Node {
+    Number(f64),
+    // Position in the variables of the expression
+    Variable(usize),
+    Negate(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+    Function(fn(f64) -> f64, Box<Node>),
+}
+
+impl Node {
+    fn eval(&self, values: &[f64]) -> f64 {
+        match self {
+            Node::Number(v) => *v,
+            Node::Variable(n) => values[*n],
+            Node::Negate(a) => -a.eval(values),
+            Node::Binary(operator, a, b) => {
+                let (a, b) = (a.eval(values), b.eval(values));
+                match operator {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    _ => a.powf(b),
+                }
+            }
+            Node::Function(f, a) => f(a.eval(values)),
+        }
+    }
+}
+
+fn function(name: &str) -> Option<fn(f64) -> f64> {
+    match name {
+        "abs" => Some(f64::abs),
+        "sqrt" => Some(f64::sqrt),
+        "ln" => Some(f64::ln),
+        "exp" => Some(f64::exp),
+        _ => None,
+    }
+}
+
+// Recursive descent over the tokens: sums of products of powers
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    variables: Vec<String>,
+}
+
+impl Parser {
+    fn next_if(&mut self, token: &Token) -> bool {
+        let matches = self.tokens.get(self.position) == Some(token);
+        self.position += matches as usize;
+        matches
+    }
+
+    fn binary(
+        &mut self,
+        operators: &[char],
+        operand: fn(&mut Self) -> Result<Node, String>,
+    ) -> Result<Node, String> {
+        let mut node = operand(self)?;
+        while let Some(&operator) = operators
+            .iter()
+            .find(|&&operator| self.next_if(&Token::Operator(operator)))
+        {
+            node = Node::Binary(operator, Box::new(node), Box::new(operand(self)?));
+        }
+        Ok(node)
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        self.binary(&['+', '-'], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        self.binary(&['*', '/'], Self::unary)
+    }
+
+    // Negation binds looser than powers: -x^2 is -(x^2)
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.next_if(&Token::Operator('-')) {
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        let base = self.atom()?;
+        match self.next_if(&Token::Operator('^')) {
+            true => Ok(Node::Binary('^', Box::new(base), Box::new(self.unary()?))),
+            false => Ok(base),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        match token {
+            Some(Token::Number(v)) => Ok(Node::Number(*v)),
+            Some(Token::Name(name)) => {
+                let name = name.clone();
+                if let (Some(f), true) = (function(&name), self.next_if(&Token::Open)) {
+                    return Ok(Node::Function(f, Box::new(self.group()?)));
+                }
+                let n = match self.variables.iter().position(|v| *v == name) {
+                    Some(n) => n,
+                    None => {
+                        self.variables.push(name);
+                        self.variables.len() - 1
+                    }
+                };
+                Ok(Node::Variable(n))
+            }
+            Some(Token::Open) => self.group(),
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Unexpected end of the expression".into()),
+        }
+    }
+
+    // The rest of a parenthesized expression, after its opening parenthesis
+    fn group(&mut self) -> Result<Node, String> {
+        let node = self.sum()?;
+        match self.next_if(&Token::Close) {
+            true => Ok(node),
+            false => Err("Missing )".into()),
+        }
+    }
+}
+
+pub struct Expression {
+    // Name given before an equals sign, if any
+    pub name: Option<String>,
+    // Channels the expression reads, in order of appearance
+    pub variables: Vec<String>,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (name, formula) = match text.split_once('=') {
+            Some((name, formula)) => (Some(name.trim().to_owned()), formula),
+            None => (None, text),
+        };
+        if name.as_deref() == Some("") {
+            return Err("Empty channel name".into());
+        }
+
+        let mut parser = Parser {
+            tokens: tokenize(formula)?,
+            position: 0,
+            variables: Vec::new(),
+        };
+        if parser.tokens.is_empty() {
+            return Err("Empty expression".into());
+        }
+        let root = parser.sum()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(format!("Unexpected {:?}", token));
+        }
+        // A constant would pass for a measured channel, as it has no sources
+        if parser.variables.is_empty() {
+            return Err("The expression reads no channel".into());
+        }
+        Ok(Self {
+            name,
+            variables: parser.variables,
+            root,
+        })
+    }
+
+    // Value for the given values of the variables
+    pub fn eval(&self, values: &[f64]) -> f64 {
+        self.root.eval(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expression() {
+        let ti = Expression::parse("TI = M1~WSSD80 / M1~WS80").unwrap();
+        assert_eq!(ti.name.as_deref(), Some("TI"));
+        assert_eq!(ti.variables, ["M1~WSSD80", "M1~WS80"]);
+        assert_eq!(ti.eval(&[1.0, 8.0]), 0.125);
+
+        let shear = Expression::parse("ln(WS80 / WS40) / ln(80 / 40)").unwrap();
+        assert_eq!(shear.name, None);
+        assert!((shear.eval(&[2.0_f64.powf(0.2), 1.0]) - 0.2).abs() < 1e-12);
+
+        let precedence = Expression::parse("-[Wind speed] ^ 2 + 2 * 3 - (1 - 4)").unwrap();
+        assert_eq!(precedence.variables, ["Wind speed"]);
+        assert_eq!(precedence.eval(&[3.0]), 0.0);
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        assert_eq!(
+            Expression::parse("(WS80 - WS60").err().as_deref(),
+            Some("Missing )")
+        );
+        assert_eq!(
+            Expression::parse("WS80 % 2").err().as_deref(),
+            Some("Unexpected '%'")
+        );
+        assert_eq!(
+            Expression::parse("TI =").err().as_deref(),
+            Some("Empty expression")
+        );
+        assert_eq!(
+            Expression::parse("2 * 3").err().as_deref(),
+            Some("The expression reads no channel")
+        );
+        assert!(Expression::parse("WS80 WS60").is_err());
+    }
+}
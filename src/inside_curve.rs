@@ -1,73 +1,94 @@
-use std::iter::once;
+use rayon::prelude::*;
 
 pub type Point = [f64; 2];
 
-// Given three collinear points p, q, r, the function checks if
-// point q lies on line segment 'pr'
-fn on_segment(p: &Point, q: &Point, r: &Point) -> bool {
-    q[0] <= p[0].max(r[0])
-        && q[0] >= p[0].min(r[0])
-        && q[1] <= p[1].max(r[1])
-        && q[1] >= p[1].min(r[1])
+// The closed curve as its edges, with the box around it to reject the points
+// far from it before counting crossings
+struct Polygon {
+    edges: Vec<[Point; 2]>,
+    min: Point,
+    max: Point,
 }
 
-#[derive(PartialEq)]
-enum Orientation {
-    Colinear,
-    Clockwise,
-    Counterclockwise,
-}
+impl Polygon {
+    fn new(curve: &[Point]) -> Self {
+        let mut min = [f64::INFINITY; 2];
+        let mut max = [f64::NEG_INFINITY; 2];
+        for p in curve {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        let edges = curve
+            .iter()
+            .zip(curve.iter().cycle().skip(1))
+            .map(|(a, b)| [*a, *b])
+            .collect();
+        Self { edges, min, max }
+    }
 
-fn orientation(p: &Point, q: &Point, r: &Point) -> Orientation {
-    let val = (q[1] - p[1]) * (r[0] - q[0]) - (q[0] - p[0]) * (r[1] - q[1]);
-    if val > 0.0 {
-        Orientation::Clockwise
-    } else if val < 0.0 {
-        Orientation::Counterclockwise
-    } else {
-        Orientation::Colinear
+    fn contains(&self, p: &Point) -> bool {
+        // NaN points fail these comparisons too
+        let in_box = (0..2).all(|axis| p[axis] >= self.min[axis] && p[axis] <= self.max[axis]);
+        if !in_box {
+            return false;
+        }
+
+        // Crossings of a ray to the right of the point. Each edge includes its
+        // lower end only, so rays through a vertex count it once.
+        let n_crossings = self
+            .edges
+            .iter()
+            .filter(|[a, b]| {
+                (a[1] > p[1]) != (b[1] > p[1])
+                    && p[0] < a[0] + (b[0] - a[0]) * (p[1] - a[1]) / (b[1] - a[1])
+            })
+            .count();
+        n_crossings % 2 == 1
     }
 }
 
-fn do_intersect(p1: &Point, q1: &Point, p2: &Point, q2: &Point) -> bool {
-    let o1 = orientation(p1, q1, p2);
-    let o2 = orientation(p1, q1, q2);
-    let o3 = orientation(p2, q2, p1);
-    let o4 = orientation(p2, q2, q1);
-
-    // General case
-    if o1 != o2 && o3 != o4 {
-        return true;
+// Whether each point is inside the curve, or any of its copies shifted by one
+// of the offsets is, e.g. to wrap direction axes. Points are checked in parallel.
+pub fn check_inside_curve_with_offsets(
+    curve: Vec<Point>,
+    data: Vec<Point>,
+    offsets: &[Point],
+) -> Vec<bool> {
+    if curve.is_empty() {
+        return vec![false; data.len()];
     }
-
-    // Special Cases: check collinear points lying on the segment
-    matches!(o1, Orientation::Colinear) && on_segment(p1, p2, q1)
-        || matches!(o2, Orientation::Colinear) && on_segment(p1, q2, q1)
-        || matches!(o3, Orientation::Colinear) && on_segment(p2, p1, q2)
-        || matches!(o4, Orientation::Colinear) && on_segment(p2, q1, q2)
+    let polygon = Polygon::new(&curve);
+    data.par_iter()
+        .map(|p| {
+            offsets
+                .iter()
+                .any(|offset| polygon.contains(&[p[0] + offset[0], p[1] + offset[1]]))
+        })
+        .collect()
 }
 
-pub fn check_inside_curve(curve: Vec<Point>, data: Vec<Point>) -> Vec<bool> {
-    let outside = [-100.0, -100.0];
+// Distance between two points after scaling each axis (e.g. by the pixels
+// per data unit of the plot)
+pub fn scaled_distance(a: &Point, b: &Point, scale: [f64; 2]) -> f64 {
+    ((a[0] - b[0]) * scale[0]).hypot((a[1] - b[1]) * scale[1])
+}
 
-    let first_point = curve[0];
-    let last_point = curve[curve.len() - 1];
-    let close_loop = [last_point, first_point];
+// Closest point to target, measuring distances after scaling each axis
+pub fn nearest_point(points: &[Point], target: &Point, scale: [f64; 2]) -> Option<Point> {
+    nearest_index(points, target, scale).map(|n| points[n])
+}
 
-    let results = data
+// Position of the closest point, skipping the NaN ones
+pub fn nearest_index(points: &[Point], target: &Point, scale: [f64; 2]) -> Option<usize> {
+    points
         .iter()
-        .map(|p| {
-            let n_crossings = curve
-                .windows(2)
-                .chain(once(&close_loop[..]))
-                .map(|segment| do_intersect(&outside, p, &segment[0], &segment[1]) as u8)
-                .sum::<u8>();
-
-            n_crossings % 2 == 1
-        })
-        .collect::<Vec<bool>>();
-
-    results
+        .map(|p| scaled_distance(p, target, scale))
+        .enumerate()
+        .filter(|(_, distance)| !distance.is_nan())
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(n, _)| n)
 }
 
 #[cfg(test)]
@@ -75,42 +96,74 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_on_segment() {
-        let p = [0.0, 0.0];
-        let q = [1.0, 1.0];
-        let r = [2.0, 2.0];
-        assert_eq!(on_segment(&p, &q, &r), true);
-
-        let p = [0.0, 0.0];
-        let q = [2.0, 2.0];
-        let r = [1.0, 1.0];
-        assert_eq!(on_segment(&p, &q, &r), false);
+    fn test_check_inside_curve_with_offsets() {
+        // Sector from 350° to 370° drawn across the north seam
+        let curve = vec![[350.0, 0.0], [370.0, 0.0], [370.0, 10.0], [350.0, 10.0]];
+        let data = vec![[355.0, 5.0], [5.0, 5.0], [20.0, 5.0], [f64::NAN, f64::NAN]];
+
+        let inside = check_inside_curve_with_offsets(curve.clone(), data.clone(), &[[0.0, 0.0]]);
+        assert_eq!(inside, vec![true, false, false, false]);
+
+        let offsets = [[0.0, 0.0], [360.0, 0.0], [-360.0, 0.0]];
+        let inside = check_inside_curve_with_offsets(curve, data, &offsets);
+        assert_eq!(inside, vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn test_check_inside_curve() {
+        // Far below and left of any fixed point the rays could be cast to
+        let curve = vec![[-500.0, -500.0], [-400.0, -500.0], [-400.0, -400.0]];
+        let data = vec![[-410.0, -490.0], [-490.0, -410.0], [0.0, 0.0]];
+        let inside = check_inside_curve_with_offsets(curve, data, &[[0.0, 0.0]]);
+        assert_eq!(inside, vec![true, false, false]);
+        let inside = check_inside_curve_with_offsets(Vec::new(), vec![[0.0, 0.0]], &[[0.0, 0.0]]);
+        assert_eq!(inside, vec![false]);
+    }
+
+    #[test]
+    fn test_vertex_crossings() {
+        // Diamond of negative pressure anomalies, with points level with its
+        // side vertices so their rays pass through them
+        let curve = vec![
+            [-300.0, -250.0],
+            [-250.0, -200.0],
+            [-200.0, -250.0],
+            [-250.0, -300.0],
+        ];
+        let data = vec![
+            [-260.0, -250.0],
+            [-350.0, -250.0],
+            [-250.0, -200.0],
+            [-150.0, -250.0],
+        ];
+        let inside = check_inside_curve_with_offsets(curve, data, &[[0.0, 0.0]]);
+        assert_eq!(inside, vec![true, false, false, false]);
+    }
+
+    #[test]
+    fn test_nearest_point() {
+        let points = [[0.0, 0.0], [10.0, 1.0]];
+        let target = [4.0, 1.0];
+        assert_eq!(
+            nearest_point(&points, &target, [1.0, 1.0]),
+            Some([0.0, 0.0])
+        );
+        assert_eq!(
+            nearest_point(&points, &target, [1.0, 100.0]),
+            Some([10.0, 1.0])
+        );
+        assert_eq!(nearest_point(&[], &target, [1.0, 1.0]), None);
+        let points = [[f64::NAN, f64::NAN], [10.0, 1.0]];
+        assert_eq!(nearest_index(&points, &target, [1.0, 1.0]), Some(1));
     }
 
     #[test]
-    fn test_do_intersect() {
-        let p1 = [-1.0, 0.0];
-        let q1 = [1.0, 0.0];
-        let p2 = [0.0, 1.0];
-        let q2 = [0.0, -1.0];
-        assert_eq!(do_intersect(&p1, &q1, &p2, &q2), true);
-
-        let p1 = [2.0, 0.0];
-        let q1 = [3.0, 0.0];
-        let p2 = [0.0, 1.0];
-        let q2 = [0.0, -1.0];
-        assert_eq!(do_intersect(&p1, &q1, &p2, &q2), false);
-
-        let p1 = [0.0, 0.0];
-        let q1 = [3.0, 0.0];
-        let p2 = [0.0, 1.0];
-        let q2 = [0.0, -1.0];
-        assert_eq!(do_intersect(&p1, &q1, &p2, &q2), true);
-
-        let p1 = [0.0, 0.0];
-        let q1 = [3.0, 0.0];
-        let p2 = [0.0, 0.0];
-        let q2 = [0.0, -1.0];
-        assert_eq!(do_intersect(&p1, &q1, &p2, &q2), true);
+    fn test_scaled_distance() {
+        // 40 hPa apart on a pressure axis of 0.25 pixels per hPa
+        assert_eq!(
+            scaled_distance(&[0.0, 1000.0], &[0.0, 1040.0], [1.0, 0.25]),
+            10.0
+        );
+        assert_eq!(scaled_distance(&[0.0, 0.0], &[3.0, 4.0], [1.0, 1.0]), 5.0);
     }
 }
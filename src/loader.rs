@@ -0,0 +1,259 @@
+use crate::columns::{ParseReport, RawTable, Table};
+use crate::dataset::index_times;
+use crate::direction::is_direction;
+use crate::duplicates::{
+    average_groups, duplicate_groups, kept_rows, retain_rows, DuplicatePolicy,
+};
+use crate::timeline::detect_format;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+// A data file read on a background thread, with its index timestamps already
+// parsed so the window doesn't have to either
+pub struct Parsed {
+    pub headers: Vec<String>,
+    pub index: Vec<String>,
+    pub times: Vec<f64>,
+    pub time_format: Option<&'static str>,
+    pub content: Content,
+    // Malformed cells of a text file
+    pub report: Option<Box<ParseReport>>,
+    // Timestamps found on several rows, with their lines in the file
+    pub duplicates: Vec<(String, Vec<usize>)>,
+}
+
+pub enum Content {
+    // A mapped text file, whose columns are parsed when first used
+    Mapped(RawTable),
+    // The values of every column after the index, of a file read whole
+    Columns(Vec<Vec<f64>>),
+}
+
+impl Parsed {
+    // With `skip` set, rows with malformed cells are left out, which takes
+    // looking over every cell right away. Otherwise columns are checked as
+    // they are parsed.
+    pub fn mapped(
+        path: &str,
+        every: usize,
+        skip: bool,
+        progress: &dyn Fn(f32),
+    ) -> Result<Self, String> {
+        let scan_share = if skip { 0.5 } else { 1.0 };
+        let (headers, mut raw) =
+            RawTable::open_with_progress(path, every, &|p| progress(p * scan_share))?;
+        let report = skip.then(|| raw.check(true, &|p| progress(0.5 + 0.5 * p)));
+        let index = raw.index();
+        let mut parsed = Self::new(headers, index, Content::Mapped(raw))?;
+        parsed.report = report.map(Box::new);
+        Ok(parsed)
+    }
+
+    pub fn table(table: Table) -> Result<Self, String> {
+        Self::new(table.headers, table.index, Content::Columns(table.columns))
+    }
+
+    fn new(headers: Vec<String>, index: Vec<String>, content: Content) -> Result<Self, String> {
+        if headers.is_empty() {
+            return Err("No headers found".into());
+        }
+        Ok(Self {
+            headers,
+            times: index_times(&index),
+            time_format: detect_format(&index),
+            index,
+            content,
+            report: None,
+            duplicates: Vec::new(),
+        })
+    }
+
+    // Reduces the rows sharing a timestamp to one as the policy says. Averages
+    // leave out the missing value markers, and go round the circle for the
+    // channels the direction tokens name.
+    pub fn resolve_duplicates(
+        mut self,
+        policy: DuplicatePolicy,
+        missing: &[f64],
+        direction_tokens: &[String],
+    ) -> Result<Self, String> {
+        let groups = duplicate_groups(&self.times);
+        let Some(first) = groups.first() else {
+            return Ok(self);
+        };
+        if policy == DuplicatePolicy::Error {
+            return Err(format!(
+                "{} timestamps are repeated, the first '{}'",
+                groups.len(),
+                self.index[first[0]]
+            ));
+        }
+
+        // Lines of a table read whole follow its header, with no blank ones
+        let line = |row: usize| match &self.content {
+            Content::Mapped(raw) => raw.line(row),
+            Content::Columns(_) => row + 2,
+        };
+        self.duplicates = groups
+            .iter()
+            .map(|rows| {
+                let lines = rows.iter().map(|&row| line(row)).collect();
+                (self.index[rows[0]].clone(), lines)
+            })
+            .collect();
+
+        // Mapped columns are parsed right away to be averaged, their malformed
+        // cells going to the report
+        if let (DuplicatePolicy::Average, Content::Mapped(raw)) = (policy, &self.content) {
+            let report = self.report.get_or_insert_with(Default::default);
+            let columns = (1..self.headers.len())
+                .map(|column| {
+                    let (values, column_report) = raw.column(column);
+                    if !report.checked.contains(&column) {
+                        report.merge(column_report);
+                    }
+                    values
+                })
+                .collect();
+            self.content = Content::Columns(columns);
+        }
+
+        let keep = kept_rows(self.times.len(), &groups, policy);
+        match &mut self.content {
+            Content::Mapped(raw) => raw.retain_rows(&keep),
+            Content::Columns(columns) => {
+                for (values, name) in columns.iter_mut().zip(&self.headers[1..]) {
+                    if policy == DuplicatePolicy::Average {
+                        let direction = is_direction(name, direction_tokens);
+                        average_groups(values, &groups, missing, direction);
+                    }
+                    retain_rows(values, &keep);
+                }
+            }
+        }
+        retain_rows(&mut self.index, &keep);
+        retain_rows(&mut self.times, &keep);
+        Ok(self)
+    }
+}
+
+enum LoadMessage {
+    Progress(f32),
+    Done(Result<Parsed, String>),
+}
+
+// A file being read on a background thread. Dropping the job discards the
+// result once the thread is done.
+pub struct LoadJob {
+    receiver: Receiver<LoadMessage>,
+    pub progress: f32,
+}
+
+impl LoadJob {
+    pub fn spawn(
+        read: impl FnOnce(&dyn Fn(f32)) -> Result<Parsed, String> + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let progress = sender.clone();
+            let report = move |fraction: f32| {
+                let _ = progress.send(LoadMessage::Progress(fraction));
+            };
+            let _ = sender.send(LoadMessage::Done(read(&report)));
+        });
+
+        Self {
+            receiver,
+            progress: 0.0,
+        }
+    }
+
+    // The parsed file once the thread is done
+    pub fn poll(&mut self) -> Option<Result<Parsed, String>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(LoadMessage::Progress(progress)) => self.progress = progress,
+                Ok(LoadMessage::Done(result)) => return Some(result),
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    return Some(Err("The file reader stopped unexpectedly".into()))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_resolve_duplicates() {
+        let table = || Table {
+            headers: vec!["Time".into(), "M1~WS80".into(), "M1~WD80".into()],
+            index: [
+                "2024-01-01 00:00",
+                "2024-01-01 00:10",
+                "2024-01-01 00:00",
+                "2024-01-01 00:00",
+            ]
+            .map(String::from)
+            .to_vec(),
+            columns: vec![
+                vec![5.0, 6.0, 7.0, 99999.0],
+                vec![350.0, 180.0, 10.0, 99999.0],
+            ],
+        };
+        let missing = [99999.0];
+        let tokens = ["WD".to_owned()];
+        let parsed = Parsed::table(table())
+            .unwrap()
+            .resolve_duplicates(DuplicatePolicy::Average, &missing, &tokens)
+            .unwrap();
+        assert_eq!(parsed.index.len(), 2);
+        assert_eq!(
+            parsed.duplicates,
+            [("2024-01-01 00:00".to_owned(), vec![2, 4, 5])]
+        );
+        let Content::Columns(columns) = parsed.content else {
+            panic!("Columns expected");
+        };
+        assert_eq!(columns[0], [6.0, 6.0]);
+        assert!(columns[1][0].min(360.0 - columns[1][0]) < 1e-9);
+
+        let parsed = Parsed::table(table())
+            .unwrap()
+            .resolve_duplicates(DuplicatePolicy::KeepLast, &missing, &tokens)
+            .unwrap();
+        assert!(
+            matches!(parsed.content, Content::Columns(columns) if columns[0] == [6.0, 99999.0])
+        );
+        assert!(Parsed::table(table())
+            .unwrap()
+            .resolve_duplicates(DuplicatePolicy::Error, &missing, &tokens)
+            .is_err());
+    }
+
+    #[test]
+    fn test_load_job() {
+        let table = Table {
+            headers: vec!["Time".into(), "M1~WS80".into()],
+            index: vec!["2024-01-01 00:00".into(), "2024-01-01 00:10".into()],
+            columns: vec![vec![5.0, 6.0]],
+        };
+        let mut job = LoadJob::spawn(move |progress| {
+            progress(0.5);
+            Parsed::table(table)
+        });
+        let parsed = loop {
+            match job.poll() {
+                Some(result) => break result.unwrap(),
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        };
+        assert_eq!(job.progress, 0.5);
+        assert_eq!(parsed.times[1] - parsed.times[0], 600.0);
+        assert!(matches!(parsed.content, Content::Columns(columns) if columns[0] == [5.0, 6.0]));
+    }
+}
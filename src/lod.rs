@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+// Visible region of a plot, as its minimum and maximum corners
+pub type View = ([f64; 2], [f64; 2]);
+
+fn is_valid((min, max): &View) -> bool {
+    (0..2).all(|axis| (max[axis] - min[axis]).is_finite() && max[axis] > min[axis])
+}
+
+// Index of the cell of a grid over the view the point falls in, for points
+// within the view or up to one view size around it
+fn cell(point: &[f64; 2], (min, max): &View, cells: usize) -> Option<[i64; 2]> {
+    let mut index = [0; 2];
+    for axis in 0..2 {
+        let span = max[axis] - min[axis];
+        let position = (point[axis] - min[axis]) / span;
+        if !position.is_finite() || !(-1.0..=2.0).contains(&position) {
+            return None;
+        }
+        index[axis] = (position * cells as f64).floor() as i64;
+    }
+    Some(index)
+}
+
+// Keeps one point per cell of a cells×cells grid over the view, so drawing
+// the result looks the same as drawing every point once the grid is finer
+// than the pixels. Points far outside the view are dropped.
+pub fn decimate_points(points: &[[f64; 2]], view: &View, cells: usize) -> Vec<[f64; 2]> {
+    if !is_valid(view) {
+        return points.to_vec();
+    }
+    let mut occupied = HashSet::new();
+    points
+        .iter()
+        .filter(|p| cell(p, view, cells).is_some_and(|c| occupied.insert(c)))
+        .copied()
+        .collect()
+}
+
+// Keeps, for every column of the grid a line crosses, its first, lowest,
+// highest and last point in their original order, which preserves the
+// spikes and the shape of the line
+pub fn decimate_line(line: &[[f64; 2]], view: &View, cells: usize) -> Vec<[f64; 2]> {
+    if !is_valid(view) {
+        return line.to_vec();
+    }
+    let mut kept = Vec::new();
+    let mut column: Vec<[f64; 2]> = Vec::new();
+    let mut current = None;
+    let flush = |column: &mut Vec<[f64; 2]>, kept: &mut Vec<[f64; 2]>| {
+        if column.is_empty() {
+            return;
+        }
+        let by_y = |a: &&[f64; 2], b: &&[f64; 2]| a[1].total_cmp(&b[1]);
+        let low = column.iter().min_by(by_y).copied().unwrap();
+        let high = column.iter().max_by(by_y).copied().unwrap();
+        let mut extremes = vec![column[0], low, high, column[column.len() - 1]];
+        extremes.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        extremes.dedup();
+        kept.extend(extremes);
+        column.clear();
+    };
+
+    for point in line {
+        let Some([x, _]) = cell(&[point[0], view.0[1]], view, cells) else {
+            continue;
+        };
+        if current != Some(x) {
+            flush(&mut column, &mut kept);
+            current = Some(x);
+        }
+        column.push(*point);
+    }
+    flush(&mut column, &mut kept);
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEW: View = ([0.0, 0.0], [10.0, 10.0]);
+
+    #[test]
+    fn test_decimate_points() {
+        let points = [
+            [1.0, 1.0],
+            [1.01, 1.01],
+            [5.0, 5.0],
+            [100.0, 5.0],
+            [f64::NAN, 1.0],
+        ];
+        assert_eq!(
+            decimate_points(&points, &VIEW, 10),
+            vec![[1.0, 1.0], [5.0, 5.0]]
+        );
+    }
+
+    #[test]
+    fn test_decimate_line() {
+        let line = [[0.1, 1.0], [0.2, 9.0], [0.3, 0.5], [0.4, 2.0], [5.0, 3.0]];
+        assert_eq!(
+            decimate_line(&line, &VIEW, 10),
+            vec![[0.1, 1.0], [0.2, 9.0], [0.3, 0.5], [0.4, 2.0], [5.0, 3.0]]
+        );
+
+        let dense: Vec<[f64; 2]> = (0..1000)
+            .map(|i| [i as f64 / 100.0, (i % 7) as f64])
+            .collect();
+        let kept = decimate_line(&dense, &VIEW, 10);
+        assert!(kept.len() <= 40);
+        assert!(kept.windows(2).all(|w| w[0][0] < w[1][0]));
+    }
+}
@@ -2,25 +2,103 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 mod app;
+mod audit;
+mod cleaned;
+mod cli;
+mod columns;
+mod config;
+mod dataset;
+mod direction;
+mod duplicates;
+mod exclusions;
 mod expiration;
+mod expression;
 mod inside_curve;
+mod loader;
+mod lod;
+mod memory;
+mod naming;
+mod parquet_file;
+mod plot_image;
+mod project;
+mod report;
+mod rules;
+mod sensors;
+mod settings;
+mod shortcuts;
+mod stats;
+mod theme;
+mod timeline;
+mod windfarmer;
+mod workbook;
 use app::ManualDataCleanerApp;
+use expiration::License;
+
+// A release build on Windows has no console of its own, so the batch mode
+// writes to the one it was started from
+#[cfg(windows)]
+fn attach_console() {
+    use winapi::um::wincon::{AttachConsole, ATTACH_PARENT_PROCESS};
+    // Fails when started without a console, e.g. from Explorer, where there's
+    // nothing to write to anyway
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
 
 fn main() -> eframe::Result<()> {
-    expiration::panic_if_expired();
+    let license = License::current();
+
+    let args: Vec<String> = std::env::args().collect();
+    if cli::is_batch(&args) {
+        attach_console();
+        match license {
+            License::Expired(_) => {
+                eprintln!("Error: {}", license.message());
+                std::process::exit(1);
+            }
+            License::Grace(..) => eprintln!("Warning: {}", license.message()),
+            License::Valid(_) => {}
+        }
+        match cli::run(&args) {
+            Ok(summary) => {
+                println!("{}", summary);
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let review = args.iter().any(|arg| arg == "--review");
+
+    #[cfg(feature = "wgpu")]
+    let renderer = if std::env::args().any(|arg| arg == "--glow") {
+        eframe::Renderer::Glow
+    } else {
+        eframe::Renderer::Wgpu
+    };
+    #[cfg(not(feature = "wgpu"))]
+    let renderer = eframe::Renderer::Glow;
 
     let native_options = eframe::NativeOptions {
         initial_window_size: Some([1150.0, 720.0].into()),
-        min_window_size: Some([1150.0, 720.0].into()),
+        min_window_size: Some([640.0, 480.0].into()),
         icon_data: Some(
             eframe::IconData::try_from_png_bytes(&include_bytes!("../assets/icon-256.png")[..])
                 .expect("Failed to load icon"),
         ),
+        renderer,
         ..Default::default()
     };
     eframe::run_native(
-        "Manual Data cleaner",
+        expiration::APP_NAME,
         native_options,
-        Box::new(|cc| Box::new(ManualDataCleanerApp::new(cc))),
+        Box::new(move |cc| Box::new(ManualDataCleanerApp::new(cc, review, license))),
     )
 }
@@ -0,0 +1,81 @@
+// Per row, besides its values: the timestamp string and its plot coordinate
+const INDEX_BYTES: usize = std::mem::size_of::<String>() + 16 + std::mem::size_of::<f64>();
+
+// Memory needed to hold a dataset of the given shape once loaded
+pub fn estimate_dataset_bytes(rows: usize, columns: usize, value_bytes: usize) -> usize {
+    rows * (INDEX_BYTES + columns * value_bytes)
+}
+
+// Memory available to new allocations, when the platform reports it
+#[cfg(not(windows))]
+pub fn available_memory() -> Option<usize> {
+    if cfg!(target_os = "linux") {
+        parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?)
+    } else {
+        None
+    }
+}
+
+#[cfg(windows)]
+pub fn available_memory() -> Option<usize> {
+    use winapi::um::sysinfoapi::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    match unsafe { GlobalMemoryStatusEx(&mut status) } {
+        0 => None,
+        _ => usize::try_from(status.ullAvailPhys).ok(),
+    }
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_meminfo(meminfo: &str) -> Option<usize> {
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+// Human readable size, e.g. "12.3 MB"
+pub fn format_bytes(bytes: usize) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, units[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_dataset_bytes() {
+        assert_eq!(estimate_dataset_bytes(0, 10, 32), 0);
+        assert_eq!(
+            estimate_dataset_bytes(1000, 10, 32) - estimate_dataset_bytes(1000, 9, 32),
+            32000
+        );
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo = "MemTotal:       16000000 kB\nMemAvailable:    8000000 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some(8000000 * 1024));
+        assert_eq!(parse_meminfo("MemTotal: 1 kB"), None);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+}
@@ -0,0 +1,237 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+
+// How the mast and sensor are read from a channel name: the parts between
+// separators at the given positions, counted from 0, or the "mast" and
+// "sensor" groups of a regular expression when one is set. Channels are named
+// "mast~sensor" or "mast~sensor~statistic" by default, a statistic or units
+// being the one part allowed after the mast and sensor.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NameConvention {
+    pub separator: String,
+    pub mast_part: usize,
+    pub sensor_part: usize,
+    pattern: String,
+    #[serde(skip)]
+    compiled: OnceCell<Result<Regex, String>>,
+}
+
+impl Default for NameConvention {
+    fn default() -> Self {
+        Self {
+            separator: "~".into(),
+            mast_part: 0,
+            sensor_part: 1,
+            pattern: String::new(),
+            compiled: OnceCell::new(),
+        }
+    }
+}
+
+impl NameConvention {
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn set_pattern(&mut self, pattern: String) {
+        self.pattern = pattern;
+        self.compiled = OnceCell::new();
+    }
+
+    fn regex(&self) -> Result<&Regex, String> {
+        self.compiled
+            .get_or_init(|| {
+                let regex = Regex::new(&self.pattern).map_err(|e| e.to_string())?;
+                for group in ["mast", "sensor"] {
+                    if !regex.capture_names().any(|name| name == Some(group)) {
+                        return Err(format!("No (?P<{}>...) group in the pattern", group));
+                    }
+                }
+                Ok(regex)
+            })
+            .as_ref()
+            .map_err(|e| format!("Invalid pattern: {}", e))
+    }
+
+    // Mast and sensor of a channel name
+    pub fn split(&self, name: &str) -> Result<(String, String), String> {
+        let (mast, sensor) = if self.pattern.is_empty() {
+            if self.separator.is_empty() {
+                return Err("No separator set".into());
+            }
+            let parts: Vec<&str> = name.split(self.separator.as_str()).collect();
+            let needed = self.mast_part.max(self.sensor_part) + 1;
+            if parts.len() != needed && parts.len() != needed + 1 {
+                return Err("Unsupported number of names".into());
+            }
+            (parts[self.mast_part].trim(), parts[self.sensor_part].trim())
+        } else {
+            let captures = self
+                .regex()?
+                .captures(name)
+                .ok_or("No match of the pattern")?;
+            let group = |group| captures.name(group).map_or("", |m| m.as_str());
+            (group("mast"), group("sensor"))
+        };
+        if mast.is_empty() || sensor.is_empty() {
+            return Err("Empty mast or sensor name".into());
+        }
+        Ok((mast.to_owned(), sensor.to_owned()))
+    }
+
+    // The part after the mast and sensor, when the name has one
+    fn extra_part<'a>(&self, name: &'a str) -> Option<&'a str> {
+        if self.separator.is_empty() {
+            return None;
+        }
+        let parts: Vec<&str> = name.split(self.separator.as_str()).collect();
+        let extra = self.mast_part.max(self.sensor_part) + 1;
+        (parts.len() == extra + 1).then(|| parts[extra])
+    }
+
+    // Splits a channel into the sensor it belongs to and the statistic it
+    // holds, from either the part after the sensor ("M1~WS80~Max") or a suffix
+    // ("M1~WS80_Max")
+    pub fn split_statistic<'a>(&self, name: &'a str) -> Option<(&'a str, Statistic)> {
+        let (base, word) = match self.extra_part(name) {
+            Some(word) => (
+                &name[..name.len() - word.len() - self.separator.len()],
+                word,
+            ),
+            None => name.rsplit_once('_')?,
+        };
+        Some((base, Statistic::parse(word)?))
+    }
+
+    // Units written as the part after the sensor, as "M1~WS80~m/s", unless
+    // the part is a statistic
+    pub fn name_units<'a>(&self, name: &'a str) -> Option<&'a str> {
+        let units = self.extra_part(name)?.trim();
+        (!units.is_empty() && Statistic::parse(units).is_none()).then_some(units)
+    }
+}
+
+// Kind of sensor, from the letters before the height: "WS80" -> "WS"
+pub fn sensor_kind(sensor: &str) -> Option<String> {
+    let kind: String = sensor
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    (!kind.is_empty()).then_some(kind)
+}
+
+// Height of a sensor, from the number after its kind: "WS80" -> 80
+pub fn sensor_height(sensor: &str) -> Option<f64> {
+    let number: String = sensor
+        .trim_start_matches(|c: char| c.is_ascii_alphabetic())
+        .trim_start_matches(['_', '-'])
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    number.parse().ok()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Statistic {
+    Mean,
+    Std,
+    Min,
+    Max,
+}
+
+impl Statistic {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Statistic::Mean => "Mean",
+            Statistic::Std => "Std",
+            Statistic::Min => "Min",
+            Statistic::Max => "Max",
+        }
+    }
+
+    fn parse(word: &str) -> Option<Self> {
+        match word.to_lowercase().as_str() {
+            "mean" | "avg" | "average" => Some(Statistic::Mean),
+            "std" | "sd" | "stdev" | "stddev" => Some(Statistic::Std),
+            "min" | "minimum" => Some(Statistic::Min),
+            "max" | "maximum" => Some(Statistic::Max),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensor_kind() {
+        assert_eq!(sensor_kind("WS80").as_deref(), Some("WS"));
+        assert_eq!(sensor_kind("Dir_120").as_deref(), Some("Dir"));
+        assert_eq!(sensor_kind("80WS"), None);
+    }
+
+    #[test]
+    fn test_sensor_height() {
+        assert_eq!(sensor_height("WS80"), Some(80.0));
+        assert_eq!(sensor_height("Dir_120"), Some(120.0));
+        assert_eq!(sensor_height("WS58.5A"), Some(58.5));
+        assert_eq!(sensor_height("Temp"), None);
+    }
+
+    #[test]
+    fn test_name_convention() {
+        let mut convention = NameConvention::default();
+        let names = |m: &str, s: &str| Ok((m.to_owned(), s.to_owned()));
+        assert_eq!(convention.split("M1~WS80~Mean"), names("M1", "WS80"));
+        assert!(convention.split("Temperature").is_err());
+        assert!(convention.split("M1~WS80~Mean~A").is_err());
+
+        convention.separator = "_".into();
+        convention.mast_part = 1;
+        convention.sensor_part = 0;
+        assert_eq!(convention.split("WS80_M1"), names("M1", "WS80"));
+
+        convention.set_pattern(r"^(?P<sensor>\w+?)(?P<mast>M\d+)$".into());
+        assert_eq!(convention.split("WS80M2"), names("M2", "WS80"));
+        assert!(convention.split("WS80").is_err());
+        convention.set_pattern("(?P<mast>.*)".into());
+        assert!(convention.split("M1").unwrap_err().contains("sensor"));
+    }
+
+    #[test]
+    fn test_split_statistic() {
+        let mut convention = NameConvention::default();
+        let split_statistic = |name| convention.split_statistic(name);
+        assert_eq!(
+            split_statistic("M1~WS80~Mean"),
+            Some(("M1~WS80", Statistic::Mean))
+        );
+        assert_eq!(
+            split_statistic("M1~WS80_SD"),
+            Some(("M1~WS80", Statistic::Std))
+        );
+        assert_eq!(split_statistic("M1~WS80~deg"), None);
+        assert_eq!(split_statistic("M1~WS80"), None);
+        assert_eq!(split_statistic("M1~Dir_120"), None);
+
+        convention.separator = ".".into();
+        assert_eq!(
+            convention.split_statistic("M1.WS80.Max"),
+            Some(("M1.WS80", Statistic::Max))
+        );
+    }
+
+    #[test]
+    fn test_name_units() {
+        let mut convention = NameConvention::default();
+        assert_eq!(convention.name_units("M1~WS80~m/s"), Some("m/s"));
+        assert_eq!(convention.name_units("M1~WS80~Mean"), None);
+        assert_eq!(convention.name_units("M1~WS80"), None);
+        convention.separator = "|".into();
+        assert_eq!(convention.name_units("M1|WS80|m/s"), Some("m/s"));
+        assert_eq!(convention.name_units("M1~WS80~m/s"), None);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::columns::Table;
+use crate::dataset::{DataPoint, TimeSeries};
+use crate::timeline::format_time;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, Int64Type};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::sync::Arc;
+
+pub fn is_parquet(path: &str) -> bool {
+    path.to_lowercase().ends_with(".parquet")
+}
+
+// Reads the whole file, with the first column as the index and the others
+// cast to numbers. Nulls and values that can't be cast are read as NaN.
+pub fn read_parquet(path: &str) -> Result<Table, String> {
+    let file = File::open(path).map_err(|e| format!("File read error: {}", e))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .map_err(|e| format!("Parquet read error: {}", e))?;
+    let schema = builder.schema().clone();
+    let batches = builder
+        .build()
+        .and_then(|reader| reader.collect::<Result<Vec<_>, _>>().map_err(Into::into))
+        .map_err(|e| format!("Parquet read error: {}", e))?;
+    read_batches(&schema, &batches)
+}
+
+fn read_batches(schema: &Schema, batches: &[RecordBatch]) -> Result<Table, String> {
+    let headers: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+    if headers.is_empty() {
+        return Err("No columns found".into());
+    }
+
+    let mut index = Vec::new();
+    let mut columns = vec![Vec::new(); headers.len() - 1];
+    for batch in batches {
+        index.extend(index_text(batch.column(0))?);
+        for (n, values) in columns.iter_mut().enumerate() {
+            let array = cast(batch.column(n + 1), &DataType::Float64).map_err(|e| e.to_string())?;
+            values.extend(
+                array
+                    .as_primitive::<Float64Type>()
+                    .iter()
+                    .map(|v| v.unwrap_or(f64::NAN)),
+            );
+        }
+    }
+
+    Ok(Table {
+        headers,
+        index,
+        columns,
+    })
+}
+
+// Timestamps are written in the format of the text files, anything else as text
+fn index_text(array: &ArrayRef) -> Result<Vec<String>, String> {
+    let text = match array.data_type() {
+        DataType::Timestamp(..) | DataType::Date32 | DataType::Date64 => {
+            let seconds = cast(array, &DataType::Timestamp(TimeUnit::Second, None))
+                .and_then(|seconds| cast(&seconds, &DataType::Int64))
+                .map_err(|e| e.to_string())?;
+            seconds
+                .as_primitive::<Int64Type>()
+                .iter()
+                .map(|t| t.map_or_else(String::new, |t| format_time(t as f64)))
+                .collect()
+        }
+        _ => {
+            let strings = cast(array, &DataType::Utf8).map_err(|e| e.to_string())?;
+            strings
+                .as_string::<i32>()
+                .iter()
+                .map(|t| t.unwrap_or_default().to_owned())
+                .collect()
+        }
+    };
+    Ok(text)
+}
+
+// The channels as one batch, the index as text and the excluded and missing
+// values as nulls
+fn cleaned_batch(
+    index_name: &str,
+    index: &[String],
+    channels: &[&TimeSeries],
+) -> Result<RecordBatch, String> {
+    let mut fields = vec![Field::new(index_name, DataType::Utf8, false)];
+    let mut arrays: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(index))];
+    for ts in channels {
+        fields.push(Field::new(&ts.name, DataType::Float64, true));
+        arrays.push(Arc::new(Float64Array::from_iter(ts.data.iter().map(
+            |val| match val {
+                DataPoint::Valid(v) => Some(v),
+                _ => None,
+            },
+        ))));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).map_err(|e| e.to_string())
+}
+
+pub fn write_parquet(
+    path: &str,
+    index_name: &str,
+    index: &[String],
+    channels: &[&TimeSeries],
+) -> Result<(), String> {
+    let batch = cleaned_batch(index_name, index, channels)?;
+    let file = File::create(path).map_err(|e| format!("File write error: {}", e))?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), None)
+        .map_err(|e| format!("Parquet write error: {}", e))?;
+    writer
+        .write(&batch)
+        .and_then(|_| writer.close().map(|_| ()))
+        .map_err(|e| format!("Parquet write error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::TimestampSecondArray;
+
+    #[test]
+    fn test_cleaned_batch() {
+        let index: Vec<String> = ["2024-01-01 00:00", "2024-01-01 00:10"]
+            .map(String::from)
+            .to_vec();
+        let ts = TimeSeries {
+            name: "M1~WS80".into(),
+            data: [DataPoint::Valid(5.0), DataPoint::Excluded(6.0, "icing")]
+                .into_iter()
+                .collect(),
+            sources: Vec::new(),
+            column: None,
+        };
+        let batch = cleaned_batch("Time", &index, &[&ts]).unwrap();
+        let table = read_batches(&batch.schema(), &[batch]).unwrap();
+        assert_eq!(table.headers, vec!["Time", "M1~WS80"]);
+        assert_eq!(table.index, index);
+        assert_eq!(table.columns[0][0], 5.0);
+        assert!(table.columns[0][1].is_nan());
+    }
+
+    #[test]
+    fn test_timestamp_index() {
+        // 2024-01-01 00:10 UTC
+        let times: ArrayRef = Arc::new(TimestampSecondArray::from(vec![1704067800]));
+        assert_eq!(index_text(&times).unwrap(), vec!["2024-01-01 00:10"]);
+    }
+}
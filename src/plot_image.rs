@@ -0,0 +1,351 @@
+use crate::theme::Rgb;
+use crate::timeline::format_time;
+use eframe::egui::ColorImage;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+// Plot images for cleaning reports: PNG from a screenshot of the window cut to
+// the plot, and SVG drawn again from the plotted points
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 600.0;
+// Room around the plot area for the ticks and the axis labels, as
+// [left, top, right, bottom]
+const MARGINS: [f64; 4] = [80.0, 30.0, 20.0, 50.0];
+const TICKS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub const ALL: [ImageFormat; 2] = [ImageFormat::Png, ImageFormat::Svg];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Svg => "SVG",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+
+    // SVG when the file is named so, PNG otherwise
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("svg") => ImageFormat::Svg,
+            _ => ImageFormat::Png,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Mark {
+    // Markers of the given radius
+    Points(f32),
+    // A line through the points, of the given width
+    Line(f32),
+}
+
+// Points of the plot drawn alike, in the legend unless the name is empty
+pub struct Layer {
+    pub name: String,
+    pub color: Rgb,
+    pub mark: Mark,
+    pub points: Vec<[f64; 2]>,
+}
+
+// What the plot shows: its bounds, the axis labels and the layers, drawn in
+// order
+pub struct PlotDrawing {
+    pub min: [f64; 2],
+    pub max: [f64; 2],
+    pub x_label: String,
+    pub y_label: String,
+    // The x values are timestamps
+    pub time_axis: bool,
+    pub background: Rgb,
+    pub layers: Vec<Layer>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn hex(rgb: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+// Tick value as written on the axis, with the decimals its spacing needs
+fn tick_label(value: f64, step: f64, time: bool) -> String {
+    if time {
+        return format_time(value);
+    }
+    let decimals = (-step.abs().log10().floor()).clamp(0.0, 6.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+impl PlotDrawing {
+    fn to_svg(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        let area = [
+            WIDTH - MARGINS[0] - MARGINS[2],
+            HEIGHT - MARGINS[1] - MARGINS[3],
+        ];
+        // A bound with no span, as of a constant channel, puts everything in
+        // the middle
+        let fraction = |v: f64, n: usize| {
+            let span = self.max[n] - self.min[n];
+            match span > 0.0 && span.is_finite() {
+                true => (v - self.min[n]) / span,
+                false => 0.5,
+            }
+        };
+        [
+            MARGINS[0] + fraction(x, 0) * area[0],
+            MARGINS[1] + (1.0 - fraction(y, 1)) * area[1],
+        ]
+    }
+
+    fn inside(&self, point: &[f64; 2]) -> bool {
+        (0..2).all(|n| point[n] >= self.min[n] && point[n] <= self.max[n])
+    }
+
+    pub fn svg(&self) -> String {
+        let dark = self.background.iter().map(|&c| c as u32).sum::<u32>() < 384;
+        let ink = if dark { "#ffffff" } else { "#000000" };
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"12\">\n",
+            WIDTH, HEIGHT
+        );
+        svg += &format!(
+            "<rect width=\"100%\" height=\"100%\" fill=\"{}\" />\n",
+            hex(self.background)
+        );
+        let area = format!(
+            "x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+            MARGINS[0],
+            MARGINS[1],
+            WIDTH - MARGINS[0] - MARGINS[2],
+            HEIGHT - MARGINS[1] - MARGINS[3]
+        );
+        svg += &format!("<rect {} fill=\"none\" stroke=\"{}\" />\n", area, ink);
+        // Lines are cut to the plot area, off the ticks and labels
+        svg += &format!(
+            "<defs><clipPath id=\"plot-area\"><rect {} /></clipPath></defs>\n",
+            area
+        );
+
+        // Evenly spaced ticks, the first and last on the bounds
+        for n in 0..=TICKS {
+            let fraction = n as f64 / TICKS as f64;
+            let step = [0, 1].map(|axis| (self.max[axis] - self.min[axis]) / TICKS as f64);
+            let value =
+                [0, 1].map(|axis| self.min[axis] + fraction * (self.max[axis] - self.min[axis]));
+            let [x, _] = self.to_svg([value[0], self.min[1]]);
+            let [_, y] = self.to_svg([self.min[0], value[1]]);
+            svg += &format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" fill=\"{}\">{}</text>\n",
+                x,
+                HEIGHT - MARGINS[3] + 16.0,
+                ink,
+                escape(&tick_label(value[0], step[0], self.time_axis))
+            );
+            svg += &format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" fill=\"{}\">{}</text>\n",
+                MARGINS[0] - 6.0,
+                y + 4.0,
+                ink,
+                escape(&tick_label(value[1], step[1], false))
+            );
+        }
+        svg += &format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" fill=\"{}\" font-size=\"14\">{}</text>\n",
+            (WIDTH + MARGINS[0] - MARGINS[2]) / 2.0,
+            HEIGHT - 10.0,
+            ink,
+            escape(&self.x_label)
+        );
+        svg += &format!(
+            "<text transform=\"translate(16 {:.1}) rotate(-90)\" text-anchor=\"middle\" fill=\"{}\" font-size=\"14\">{}</text>\n",
+            (HEIGHT + MARGINS[1] - MARGINS[3]) / 2.0,
+            ink,
+            escape(&self.y_label)
+        );
+
+        svg += "<g clip-path=\"url(#plot-area)\">\n";
+        for layer in &self.layers {
+            let color = hex(layer.color);
+            match layer.mark {
+                Mark::Points(radius) => {
+                    for point in layer.points.iter().filter(|p| self.inside(p)) {
+                        let [x, y] = self.to_svg(*point);
+                        svg += &format!(
+                            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"{}\" />\n",
+                            x, y, radius, color
+                        );
+                    }
+                }
+                Mark::Line(width) if layer.points.len() > 1 => {
+                    let points: Vec<String> = layer
+                        .points
+                        .iter()
+                        .map(|point| {
+                            let [x, y] = self.to_svg(*point);
+                            format!("{:.1},{:.1}", x, y)
+                        })
+                        .collect();
+                    svg += &format!(
+                        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" />\n",
+                        points.join(" "),
+                        color,
+                        width
+                    );
+                }
+                Mark::Line(_) => {}
+            }
+        }
+        svg += "</g>\n";
+
+        // Legend at the top right, one entry per name
+        let mut names: Vec<(&str, Rgb)> = Vec::new();
+        for layer in self.layers.iter().filter(|layer| !layer.name.is_empty()) {
+            if !names.iter().any(|(name, _)| *name == layer.name) {
+                names.push((&layer.name, layer.color));
+            }
+        }
+        for (n, (name, rgb)) in names.into_iter().enumerate() {
+            let y = MARGINS[1] + 16.0 + 18.0 * n as f64;
+            let x = WIDTH - MARGINS[2] - 10.0;
+            svg += &format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{}\" />\n",
+                x,
+                y - 4.0,
+                hex(rgb)
+            );
+            svg += &format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" fill=\"{}\">{}</text>\n",
+                x - 8.0,
+                y,
+                ink,
+                escape(name)
+            );
+        }
+
+        svg += "</svg>\n";
+        svg
+    }
+}
+
+pub fn write_svg(path: &Path, drawing: &PlotDrawing) -> Result<(), String> {
+    fs::write(path, drawing.svg()).map_err(|e| format!("File write error: {}", e))
+}
+
+// RGBA bytes of the part of the image between the corners, in pixels
+fn crop(image: &ColorImage, min: [usize; 2], max: [usize; 2]) -> ([usize; 2], Vec<u8>) {
+    let max = [max[0].min(image.size[0]), max[1].min(image.size[1])];
+    // A rect starting past the image has nothing on it
+    let min = [min[0].min(max[0]), min[1].min(max[1])];
+    let size = [max[0].saturating_sub(min[0]), max[1].saturating_sub(min[1])];
+    let mut bytes = Vec::with_capacity(size[0] * size[1] * 4);
+    for y in min[1]..max[1] {
+        let row = &image.pixels[y * image.size[0]..(y + 1) * image.size[0]];
+        for pixel in &row[min[0]..max[0]] {
+            bytes.extend_from_slice(&pixel.to_array());
+        }
+    }
+    (size, bytes)
+}
+
+// Writes the part of a screenshot between the corners, in pixels
+pub fn write_png(
+    path: &Path,
+    image: &ColorImage,
+    min: [usize; 2],
+    max: [usize; 2],
+) -> Result<(), String> {
+    let (size, bytes) = crop(image, min, max);
+    if size.contains(&0) {
+        return Err("The plot is not on the screen".into());
+    }
+    let file = File::create(path).map_err(|e| format!("File write error: {}", e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size[0] as u32, size[1] as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&bytes))
+        .map_err(|e| format!("PNG write error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::Color32;
+
+    #[test]
+    fn test_svg() {
+        let drawing = PlotDrawing {
+            min: [0.0, 0.0],
+            max: [10.0, 20.0],
+            x_label: "M1~WS80 [m/s]".into(),
+            y_label: "M1~WS60 <m/s>".into(),
+            time_axis: false,
+            background: [255, 255, 255],
+            layers: vec![
+                Layer {
+                    name: "Excluded: icing".into(),
+                    color: [255, 0, 0],
+                    mark: Mark::Points(2.0),
+                    points: vec![[5.0, 10.0], [50.0, 10.0]],
+                },
+                Layer {
+                    name: String::new(),
+                    color: [0, 0, 255],
+                    mark: Mark::Line(1.5),
+                    points: vec![[0.0, 0.0], [10.0, 20.0]],
+                },
+            ],
+        };
+        let svg = drawing.svg();
+        // Points outside the bounds are left out
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert!(svg.contains("cx=\"430.0\" cy=\"290.0\""));
+        assert!(svg.contains("points=\"80.0,550.0 780.0,30.0\""));
+        assert!(svg.contains("M1~WS60 &lt;m/s&gt;"));
+        assert!(svg.contains(">Excluded: icing</text>"));
+        assert!(svg.contains(">16</text>"));
+        assert!(svg.contains("<g clip-path=\"url(#plot-area)\">"));
+
+        // A bound with no span doesn't give NaN coordinates
+        let flat = PlotDrawing {
+            min: [0.0, 5.0],
+            max: [10.0, 5.0],
+            ..drawing
+        };
+        assert!(!flat.svg().contains("NaN"));
+        assert_eq!(flat.to_svg([5.0, 5.0]), [430.0, 290.0]);
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut image = ColorImage::new([3, 2], Color32::BLACK);
+        image.pixels[5] = Color32::WHITE;
+        let (size, bytes) = crop(&image, [1, 1], [5, 5]);
+        assert_eq!(size, [2, 1]);
+        assert_eq!(bytes, [0, 0, 0, 255, 255, 255, 255, 255]);
+        let (size, bytes) = crop(&image, [4, 0], [6, 2]);
+        assert_eq!(size, [0, 2]);
+        assert!(bytes.is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::audit::AuditEntry;
+use crate::config::read_config;
+use crate::exclusions::Category;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// A cleaning session saved to JSON, so it can be resumed later with every
+// exclusion in place. Values are kept, so the data file isn't needed to reopen it.
+#[derive(Serialize, Deserialize)]
+pub struct Project {
+    pub data_file: String,
+    pub index_name: String,
+    pub index: Vec<String>,
+    // Loaded sub-sampled or with some columns left out
+    #[serde(default)]
+    pub partial: bool,
+    // Missing-value markers of the data file
+    #[serde(default)]
+    pub missing: Vec<f64>,
+    // Single marker of older projects, read into `missing`
+    #[serde(default, skip_serializing)]
+    pub nan: Option<f64>,
+    pub channels: Vec<ProjectChannel>,
+    pub xaxis: usize,
+    pub yaxis: usize,
+    #[serde(default)]
+    pub extra_yaxes: Vec<usize>,
+    pub time_buffer: u64,
+    #[serde(default)]
+    pub audit: Vec<AuditEntry>,
+    // The other files of the session, e.g. of other masts
+    #[serde(default)]
+    pub other_files: Vec<ProjectFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectFile {
+    pub data_file: String,
+    pub index_name: String,
+    pub index: Vec<String>,
+    #[serde(default)]
+    pub partial: bool,
+    pub channels: Vec<ProjectChannel>,
+    pub xaxis: usize,
+    pub yaxis: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectChannel {
+    pub name: String,
+    // Channels a derived channel is computed from
+    #[serde(default)]
+    pub sources: Vec<usize>,
+    // One per index row, None where missing
+    pub values: Vec<Option<f64>>,
+    // Excluded rows by reason
+    #[serde(default)]
+    pub excluded: BTreeMap<String, Vec<usize>>,
+    // Excluded rows by the analyst who excluded them, when known
+    #[serde(default)]
+    pub analysts: BTreeMap<String, Vec<usize>>,
+    // Excluded rows by category, when given
+    #[serde(default)]
+    pub categories: BTreeMap<Category, Vec<usize>>,
+}
+
+impl Project {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| format!("File write error: {}", e))?;
+        serde_json::to_writer(BufWriter::new(file), self)
+            .map_err(|e| format!("File write error: {}", e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut project: Self = read_config(path, "project")?;
+        project.missing.extend(project.nan.take());
+        project.validate()?;
+        Ok(project)
+    }
+
+    // Checks the references between the parts of the project, so a damaged
+    // file is refused instead of restoring a broken session
+    fn validate(&self) -> Result<(), String> {
+        validate_channels(&self.index, &self.channels)?;
+        let axes = [self.xaxis, self.yaxis]
+            .into_iter()
+            .chain(self.extra_yaxes.iter().copied());
+        if !self.channels.is_empty() && axes.into_iter().any(|axis| axis >= self.channels.len()) {
+            return Err("Selected axis out of range".into());
+        }
+
+        for file in &self.other_files {
+            validate_channels(&file.index, &file.channels)
+                .map_err(|e| format!("{}: {}", file.data_file, e))?;
+            if [file.xaxis, file.yaxis]
+                .into_iter()
+                .any(|axis| axis >= file.channels.len())
+            {
+                return Err(format!("{}: Selected axis out of range", file.data_file));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_channels(index: &[String], channels: &[ProjectChannel]) -> Result<(), String> {
+    let rows = index.len();
+    for channel in channels {
+        if channel.values.len() != rows {
+            return Err(format!(
+                "Channel '{}' has {} values for {} rows",
+                channel.name,
+                channel.values.len(),
+                rows
+            ));
+        }
+        if channel.sources.iter().any(|&s| s >= channels.len()) {
+            return Err(format!("Channel '{}' has an unknown source", channel.name));
+        }
+        if channel.excluded.values().flatten().any(|&row| row >= rows) {
+            return Err(format!(
+                "Channel '{}' excludes an unknown row",
+                channel.name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> Project {
+        Project {
+            data_file: "data.txt".into(),
+            index_name: "Time".into(),
+            index: vec!["2024-01-01 00:00".into(), "2024-01-01 00:10".into()],
+            partial: false,
+            missing: vec![-999.0],
+            nan: None,
+            channels: vec![ProjectChannel {
+                name: "M1~WS80".into(),
+                sources: Vec::new(),
+                values: vec![Some(5.0), None],
+                excluded: BTreeMap::from([("icing".into(), vec![0])]),
+                analysts: BTreeMap::from([("JG".into(), vec![0])]),
+                categories: BTreeMap::from([(Category::Icing, vec![0])]),
+            }],
+            xaxis: 0,
+            yaxis: 0,
+            extra_yaxes: Vec::new(),
+            time_buffer: 0,
+            audit: Vec::new(),
+            other_files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let json = serde_json::to_string(&project()).unwrap();
+        let loaded: Project = serde_json::from_str(&json).unwrap();
+        assert!(loaded.validate().is_ok());
+        assert_eq!(loaded.channels[0].values, vec![Some(5.0), None]);
+        assert_eq!(loaded.channels[0].excluded["icing"], vec![0]);
+        assert_eq!(loaded.channels[0].analysts["JG"], vec![0]);
+        assert_eq!(loaded.channels[0].categories[&Category::Icing], vec![0]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut broken = project();
+        broken.channels[0].excluded.insert("spike".into(), vec![2]);
+        assert_eq!(
+            broken.validate().unwrap_err(),
+            "Channel 'M1~WS80' excludes an unknown row"
+        );
+
+        let mut broken = project();
+        broken.yaxis = 1;
+        assert!(broken.validate().is_err());
+
+        let mut broken = project();
+        broken.other_files.push(ProjectFile {
+            data_file: "other.txt".into(),
+            index_name: "Time".into(),
+            index: vec!["2024-01-01 00:00".into()],
+            partial: false,
+            channels: Vec::new(),
+            xaxis: 0,
+            yaxis: 0,
+        });
+        assert_eq!(
+            broken.validate().unwrap_err(),
+            "other.txt: Selected axis out of range"
+        );
+    }
+}
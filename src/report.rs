@@ -0,0 +1,230 @@
+use crate::dataset::{ChannelData, DataPoint};
+use crate::exclusions::{category_label, ExclusionRow};
+use chrono::NaiveDateTime;
+use std::fs;
+use std::path::Path;
+
+// Cleaning report of a session, as a single HTML file with the plots inline,
+// for the documentation of a measurement campaign
+
+// Records of a channel, of them those not missing in the file, and those
+// still valid after the cleaning
+pub struct Recovery {
+    pub file: String,
+    pub name: String,
+    pub records: usize,
+    pub available: usize,
+    pub valid: usize,
+}
+
+impl Recovery {
+    pub fn of(file: &str, name: &str, data: &ChannelData) -> Self {
+        let mut recovery = Self {
+            file: file.to_owned(),
+            name: name.to_owned(),
+            records: data.len(),
+            available: 0,
+            valid: 0,
+        };
+        for point in data.iter() {
+            recovery.available += !matches!(point, DataPoint::NaN) as usize;
+            recovery.valid += matches!(point, DataPoint::Valid(_)) as usize;
+        }
+        recovery
+    }
+}
+
+pub struct Report {
+    // Each file of the session, with the first and last timestamps of its
+    // index
+    pub files: Vec<(String, Option<(String, String)>)>,
+    pub analyst: String,
+    pub generated: NaiveDateTime,
+    pub channels: Vec<Recovery>,
+    pub exclusions: Vec<ExclusionRow>,
+    // Exclusions that couldn't be written as periods, and why
+    pub skipped: Vec<String>,
+    // Caption and SVG of each plot
+    pub plots: Vec<(String, String)>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn percent(count: usize, total: usize) -> String {
+    match total {
+        0 => "-".to_owned(),
+        total => format!("{:.1}%", 100.0 * count as f64 / total as f64),
+    }
+}
+
+fn row(cells: &[String], tag: &str) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| format!("<{}>{}</{}>", tag, escape(cell), tag))
+        .collect();
+    format!("<tr>{}</tr>\n", cells.concat())
+}
+
+impl Report {
+    pub fn html(&self) -> String {
+        let fmt = "%Y-%m-%d %H:%M:%S";
+        let title = self.files.first().map_or("", |(file, _)| file.as_str());
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Cleaning report - {}</title>\n\
+             <style>body {{ font-family: sans-serif; margin: 2em; }} table {{ border-collapse: collapse; }} \
+             th, td {{ border: 1px solid #999; padding: 2px 8px; }} td {{ text-align: right; }} \
+             td:first-child {{ text-align: left; }} figure {{ margin: 1em 0; }}</style>\n</head>\n<body>\n",
+            escape(title)
+        );
+        html += "<h1>Cleaning report</h1>\n<p>";
+        for (file, period) in &self.files {
+            html += &format!("File: {}", escape(file));
+            if let Some((first, last)) = period {
+                html += &format!(", {} to {}", escape(first), escape(last));
+            }
+            html += "<br>\n";
+        }
+        if !self.analyst.is_empty() {
+            html += &format!("Analyst: {}<br>\n", escape(&self.analyst));
+        }
+        html += &format!("Generated: {}</p>\n", self.generated.format(fmt));
+
+        // The file of each channel is only told apart with several files
+        let several = self.files.len() > 1;
+        html += "<h2>Recovery</h2>\n<table>\n";
+        let mut header = vec![
+            "Channel",
+            "Records",
+            "Recovery before",
+            "Recovery after",
+            "Excluded",
+        ];
+        if several {
+            header.insert(0, "File");
+        }
+        html += &row(
+            &header.into_iter().map(String::from).collect::<Vec<_>>(),
+            "th",
+        );
+        for channel in &self.channels {
+            let mut cells = vec![
+                channel.name.clone(),
+                channel.records.to_string(),
+                percent(channel.available, channel.records),
+                percent(channel.valid, channel.records),
+                (channel.available - channel.valid).to_string(),
+            ];
+            if several {
+                cells.insert(0, channel.file.clone());
+            }
+            html += &row(&cells, "td");
+        }
+        html += "</table>\n";
+
+        html += &format!("<h2>Exclusions ({})</h2>\n", self.exclusions.len());
+        if self.exclusions.is_empty() {
+            html += "<p>No data was excluded.</p>\n";
+        } else {
+            html += "<table>\n";
+            html += &row(
+                &[
+                    "Mast", "Sensor", "Reason", "Category", "Start", "End", "Analyst",
+                ]
+                .map(String::from),
+                "th",
+            );
+            for ex in &self.exclusions {
+                html += &row(
+                    &[
+                        ex.mast.clone(),
+                        ex.sensor.clone(),
+                        ex.reason.clone(),
+                        category_label(ex.category).to_owned(),
+                        ex.start.format(fmt).to_string(),
+                        ex.end.format(fmt).to_string(),
+                        ex.analyst.clone(),
+                    ],
+                    "td",
+                );
+            }
+            html += "</table>\n";
+        }
+        if !self.skipped.is_empty() {
+            html += "<p>Excluded data left out of the table:</p>\n<ul>\n";
+            for problem in &self.skipped {
+                html += &format!("<li>{}</li>\n", escape(problem));
+            }
+            html += "</ul>\n";
+        }
+
+        if !self.plots.is_empty() {
+            html += "<h2>Plots</h2>\n";
+            for (caption, svg) in &self.plots {
+                html += &format!(
+                    "<figure>\n{}<figcaption>{}</figcaption>\n</figure>\n",
+                    svg,
+                    escape(caption)
+                );
+            }
+        }
+
+        html += "</body>\n</html>\n";
+        html
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.html()).map_err(|e| format!("File write error: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Stamp;
+    use crate::timeline::time;
+
+    #[test]
+    fn test_report() {
+        let mut data = ChannelData::read(vec![5.0, f64::NAN, 6.0, 7.0], &[]);
+        data.exclude(2, &Stamp::reason("icing"));
+        let recovery = Recovery::of("data <1>.txt", "M1~WS80", &data);
+        assert_eq!(
+            (recovery.records, recovery.available, recovery.valid),
+            (4, 3, 2)
+        );
+
+        let report = Report {
+            files: vec![(
+                "data <1>.txt".into(),
+                Some(("2024-01-01 00:00".into(), "2024-01-01 00:30".into())),
+            )],
+            analyst: "JG".into(),
+            generated: time("2024-02-01 10:00"),
+            channels: vec![recovery],
+            exclusions: vec![ExclusionRow {
+                mast: "M1".into(),
+                sensor: "WS80".into(),
+                reason: "icing".into(),
+                start: time("2024-01-01 00:20"),
+                end: time("2024-01-01 00:20"),
+                category: None,
+                analyst: "JG".into(),
+            }],
+            skipped: vec!["Column 'WS80': missing mast".into()],
+            plots: vec![("M1~WS80 over time".into(), "<svg></svg>\n".into())],
+        };
+        let html = report.html();
+        assert!(html.contains("File: data &lt;1&gt;.txt, 2024-01-01 00:00 to 2024-01-01 00:30"));
+        assert!(
+            html.contains("<tr><td>M1~WS80</td><td>4</td><td>75.0%</td><td>50.0%</td><td>1</td>")
+        );
+        assert!(html.contains("<h2>Exclusions (1)</h2>"));
+        assert!(html.contains("<li>Column 'WS80': missing mast</li>"));
+        assert!(html.contains("<svg></svg>\n<figcaption>M1~WS80 over time</figcaption>"));
+    }
+}
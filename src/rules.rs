@@ -0,0 +1,311 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+// Automatic detection of suspect data. Missing values are NaN and never flagged.
+#[derive(Clone, PartialEq)]
+pub enum Rule {
+    // Values outside [min, max]
+    Range { min: f64, max: f64 },
+    // Values jumping by more than max_step from the nearest valid value on
+    // both sides. Values with a missing or no value on a side aren't compared
+    // to it, so they aren't flagged.
+    Spike { max_step: f64 },
+    // Runs of at least min_length values within tolerance of the first one
+    FlatLine { min_length: usize, tolerance: f64 },
+    // Rows where min > mean, max < mean or max < min within a sensor group
+    MinMaxConsistency,
+}
+
+// Statistics of the sensor group a channel leads, for the rules that check
+// the group as a whole
+#[derive(Default)]
+pub struct GroupStats {
+    pub mean: Option<Vec<f64>>,
+    pub min: Option<Vec<f64>>,
+    pub max: Option<Vec<f64>>,
+}
+
+impl GroupStats {
+    fn inconsistent(&self, len: usize) -> Vec<bool> {
+        let value = |stat: &Option<Vec<f64>>, i: usize| {
+            stat.as_ref()
+                .and_then(|values| values.get(i))
+                .copied()
+                .filter(|v| v.is_finite())
+        };
+        (0..len)
+            .map(|i| {
+                let (mean, min, max) = (
+                    value(&self.mean, i),
+                    value(&self.min, i),
+                    value(&self.max, i),
+                );
+                let below = |low: Option<f64>, high: Option<f64>| match (low, high) {
+                    (Some(low), Some(high)) => low > high,
+                    _ => false,
+                };
+                below(min, mean) || below(mean, max) || below(min, max)
+            })
+            .collect()
+    }
+}
+
+impl Rule {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Rule::Range { .. } => "out of range",
+            Rule::Spike { .. } => "spike",
+            Rule::FlatLine { .. } => "stuck sensor",
+            Rule::MinMaxConsistency => "min/max inconsistent",
+        }
+    }
+
+    pub fn evaluate(&self, values: &[f64], group: &GroupStats) -> Vec<bool> {
+        match *self {
+            Rule::Range { min, max } => values
+                .iter()
+                .map(|v| v.is_finite() && (*v < min || *v > max))
+                .collect(),
+            Rule::Spike { max_step } => (0..values.len())
+                .map(|i| {
+                    let jump =
+                        |n: Option<&f64>| n.is_some_and(|n| (values[i] - n).abs() > max_step);
+                    values[i].is_finite()
+                        && jump(values[..i].iter().rev().find(|v| v.is_finite()))
+                        && jump(values[i + 1..].iter().find(|v| v.is_finite()))
+                })
+                .collect(),
+            Rule::FlatLine {
+                min_length,
+                tolerance,
+            } => {
+                let mut flags = vec![false; values.len()];
+                let mut start = 0;
+                while start < values.len() {
+                    let mut end = start + 1;
+                    while end < values.len()
+                        && values[start].is_finite()
+                        && (values[end] - values[start]).abs() <= tolerance
+                    {
+                        end += 1;
+                    }
+                    if values[start].is_finite() && end - start >= min_length.max(2) {
+                        flags[start..end].fill(true);
+                    }
+                    start = end;
+                }
+                flags
+            }
+            Rule::MinMaxConsistency => group.inconsistent(values.len()),
+        }
+    }
+}
+
+// Consecutive flagged values, as row ranges
+pub fn flagged_runs(flags: &[bool]) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, flag) in flags.iter().enumerate() {
+        match (flag, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                runs.push(s..i);
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        runs.push(s..flags.len());
+    }
+    runs
+}
+
+// Likely icing: the temperature below max_temperature while the anemometer
+// standard deviation is at most max_std, as a frozen cup barely turns.
+// Rows missing either value are not flagged.
+pub fn icing_flags(
+    temperature: &[f64],
+    std: &[f64],
+    max_temperature: f64,
+    max_std: f64,
+) -> Vec<bool> {
+    temperature
+        .iter()
+        .zip(std)
+        .map(|(t, s)| t.is_finite() && s.is_finite() && *t < max_temperature && *s <= max_std)
+        .collect()
+}
+
+// Rows of a channel flagged by a rule, proposed for exclusion
+#[derive(Clone)]
+pub struct Detection {
+    pub series: usize,
+    pub rows: Range<usize>,
+    pub reason: String,
+}
+
+pub enum RuleMessage {
+    Flagged(Detection),
+    Progress(f32),
+}
+
+// Rules running on a background thread. Detections are streamed back as soon
+// as they are found; dropping the job cancels it.
+pub struct RuleJob {
+    receiver: Receiver<RuleMessage>,
+    cancel: Arc<AtomicBool>,
+    pub progress: f32,
+}
+
+impl RuleJob {
+    // Evaluates every rule on every (series, values, group) channel
+    pub fn spawn(rules: Vec<Rule>, channels: Vec<(usize, Vec<f64>, GroupStats)>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cancelled = cancel.clone();
+
+        thread::spawn(move || {
+            let steps = (rules.len() * channels.len()).max(1) as f32;
+            let mut done = 0.0;
+            for (series, values, group) in &channels {
+                for rule in &rules {
+                    if cancelled.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    for rows in flagged_runs(&rule.evaluate(values, group)) {
+                        let detection = Detection {
+                            series: *series,
+                            rows,
+                            reason: rule.reason().to_owned(),
+                        };
+                        if sender.send(RuleMessage::Flagged(detection)).is_err() {
+                            return;
+                        }
+                    }
+                    done += 1.0;
+                    let _ = sender.send(RuleMessage::Progress(done / steps));
+                }
+            }
+            let _ = sender.send(RuleMessage::Progress(1.0));
+        });
+
+        Self {
+            receiver,
+            cancel,
+            progress: 0.0,
+        }
+    }
+
+    // Detections found since the last call
+    pub fn poll(&mut self) -> Vec<Detection> {
+        let mut found = Vec::new();
+        for message in self.receiver.try_iter() {
+            match message {
+                RuleMessage::Flagged(detection) => found.push(detection),
+                RuleMessage::Progress(progress) => self.progress = progress,
+            }
+        }
+        found
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.progress >= 1.0
+    }
+}
+
+impl Drop for RuleJob {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range() {
+        let rule = Rule::Range {
+            min: 0.0,
+            max: 10.0,
+        };
+        assert_eq!(
+            rule.evaluate(&[5.0, -1.0, f64::NAN, 11.0], &GroupStats::default()),
+            vec![false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_spike() {
+        let rule = Rule::Spike { max_step: 5.0 };
+        assert_eq!(
+            rule.evaluate(&[1.0, 2.0, 20.0, 2.0, 3.0, 4.0], &GroupStats::default()),
+            vec![false, false, true, false, false, false]
+        );
+        // Ends and values beside a gap are compared to the valid values only
+        assert_eq!(
+            rule.evaluate(
+                &[20.0, 21.0, f64::NAN, 30.0, 2.0, f64::NAN, 1.5, 40.0],
+                &GroupStats::default()
+            ),
+            vec![false, false, false, true, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn test_flat_line() {
+        let rule = Rule::FlatLine {
+            min_length: 3,
+            tolerance: 0.01,
+        };
+        let flags = rule.evaluate(&[1.0, 2.0, 2.0, 2.005, 3.0, 3.0], &GroupStats::default());
+        assert_eq!(flags, vec![false, true, true, true, false, false]);
+        assert_eq!(flagged_runs(&flags), vec![1..4]);
+    }
+
+    #[test]
+    fn test_icing_flags() {
+        let temperature = [-3.0, -3.0, 5.0, f64::NAN, 1.0];
+        let std = [0.0, 0.8, 0.0, 0.0, 0.02];
+        assert_eq!(
+            icing_flags(&temperature, &std, 2.0, 0.05),
+            vec![true, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_min_max_consistency() {
+        let group = GroupStats {
+            mean: Some(vec![5.0, 5.0, 5.0, 5.0, f64::NAN]),
+            min: Some(vec![1.0, 6.0, 1.0, 1.0, 9.0]),
+            max: Some(vec![9.0, 9.0, 4.0, f64::NAN, 2.0]),
+        };
+        assert_eq!(
+            Rule::MinMaxConsistency.evaluate(&[0.0; 5], &group),
+            vec![false, true, true, false, true]
+        );
+        assert_eq!(
+            Rule::MinMaxConsistency.evaluate(&[0.0; 2], &GroupStats::default()),
+            vec![false, false]
+        );
+    }
+
+    #[test]
+    fn test_job() {
+        let mut job = RuleJob::spawn(
+            vec![Rule::Range { min: 0.0, max: 1.0 }],
+            vec![(3, vec![0.5, 2.0, 2.0, 0.5], GroupStats::default())],
+        );
+        let mut found = Vec::new();
+        while !job.is_finished() {
+            found.extend(job.poll());
+        }
+        found.extend(job.poll());
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].series, found[0].rows.clone()), (3, 1..3));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::config::read_config;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Metadata of one channel of the data file, from a sensor configuration file
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorInfo {
+    pub channel: String,
+    pub mast: String,
+    pub sensor: String,
+    pub sensor_type: String,
+    pub height: Option<f64>,
+    pub units: String,
+    pub slope: Option<f64>,
+    pub offset: Option<f64>,
+}
+
+// Data dictionary loaded from JSON or TOML, as a list of channels:
+//
+// [[channels]]
+// channel = "M1~WS80~Mean"
+// mast = "M1"
+// sensor = "WS80"
+// sensor_type = "WS"
+// height = 80.0
+// units = "m/s"
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorConfig {
+    pub channels: Vec<SensorInfo>,
+}
+
+impl SensorConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        read_config(path, "sensor configuration")
+    }
+
+    pub fn get(&self, channel: &str) -> Option<&SensorInfo> {
+        self.channels.iter().find(|info| info.channel == channel)
+    }
+}
+
+impl SensorInfo {
+    // Short description for channel lists, e.g. "WS 80 m, m/s"
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match (self.sensor_type.is_empty(), self.height) {
+            (false, Some(height)) => parts.push(format!("{} {} m", self.sensor_type, height)),
+            (false, None) => parts.push(self.sensor_type.clone()),
+            (true, Some(height)) => parts.push(format!("{} m", height)),
+            (true, None) => (),
+        }
+        if !self.units.is_empty() {
+            parts.push(self.units.clone());
+        }
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_config() {
+        let config: SensorConfig = toml::from_str(
+            "[[channels]]\nchannel = \"Speed 1\"\nmast = \"M1\"\nsensor = \"WS80\"\n\
+             sensor_type = \"WS\"\nheight = 80.0\nunits = \"m/s\"\n",
+        )
+        .unwrap();
+        let info = config.get("Speed 1").unwrap();
+        assert_eq!(info.mast, "M1");
+        assert_eq!(info.summary(), "WS 80 m, m/s");
+        assert!(config.get("Speed 2").is_none());
+    }
+}
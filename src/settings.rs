@@ -0,0 +1,153 @@
+use crate::config::read_config;
+use crate::naming::NameConvention;
+use crate::shortcuts::Shortcuts;
+use crate::theme::UiTheme;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// User settings loaded from a JSON or TOML file. Missing fields keep their
+// defaults:
+//
+// quick_reasons = ["Icing", "Tower shadow"]
+// ui_theme = "Light"
+// units_from_names = true
+// direction_tokens = ["Dir", "WD"]
+//
+// [[plausible_ranges]]
+// sensor_type = "WS"
+// min = 0.0
+// max = 60.0
+//
+// [name_convention]
+// separator = "_"
+// mast_part = 0
+// sensor_part = 1
+// pattern = "^(?P<mast>M\\d+)_(?P<sensor>\\w+)$"
+//
+// [shortcuts]
+// Exclude = "Enter"
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Reasons offered as one-click exclusion buttons, and first in the list
+    // of reasons to pick from
+    pub quick_reasons: Vec<String>,
+    pub plausible_ranges: Vec<PlausibleRange>,
+    pub name_convention: NameConvention,
+    pub ui_theme: UiTheme,
+    // Whether the part of a channel name after the sensor gives its units,
+    // when the sensor configuration doesn't
+    pub units_from_names: bool,
+    // Remapped keyboard shortcuts
+    pub shortcuts: Shortcuts,
+    // Words of channel names, maybe followed by a height, that mark
+    // directions in degrees
+    pub direction_tokens: Vec<String>,
+}
+
+// Values a kind of sensor can report, those outside being excluded when the
+// ranges are applied
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlausibleRange {
+    pub sensor_type: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+fn range(sensor_type: &str, min: f64, max: f64) -> PlausibleRange {
+    PlausibleRange {
+        sensor_type: sensor_type.to_owned(),
+        min,
+        max,
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            quick_reasons: ["Icing", "Tower shadow", "Sensor failure", "Maintenance"]
+                .map(String::from)
+                .to_vec(),
+            plausible_ranges: vec![
+                range("WS", 0.0, 60.0),
+                range("WD", 0.0, 360.0),
+                range("T", -50.0, 60.0),
+                range("RH", 0.0, 100.0),
+            ],
+            name_convention: NameConvention::default(),
+            ui_theme: UiTheme::default(),
+            units_from_names: false,
+            shortcuts: Shortcuts::default(),
+            direction_tokens: ["Dir", "WD", "Direction"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut settings: Self = read_config(path, "settings")?;
+        settings
+            .quick_reasons
+            .retain(|reason| !reason.trim().is_empty());
+        Ok(settings)
+    }
+
+    // The plausible range of a sensor type, matched ignoring case
+    pub fn plausible_range(&self, sensor_type: &str) -> Option<&PlausibleRange> {
+        self.plausible_ranges
+            .iter()
+            .find(|range| range.sensor_type.eq_ignore_ascii_case(sensor_type))
+    }
+
+    // Reasons to pick from: the presets, then the reasons used so far that
+    // are not among them, ignoring case
+    pub fn reason_choices(&self, used: &[String]) -> Vec<String> {
+        let mut choices = self.quick_reasons.clone();
+        for reason in used {
+            if !choices.iter().any(|c| c.eq_ignore_ascii_case(reason)) {
+                choices.push(reason.clone());
+            }
+        }
+        choices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shortcuts::Action;
+
+    #[test]
+    fn test_settings() {
+        let settings: Settings = toml::from_str("quick_reasons = [\"Icing\"]").unwrap();
+        assert_eq!(settings.quick_reasons, vec!["Icing"]);
+        let settings: Settings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings.quick_reasons.len(), 4);
+        let settings: Settings = toml::from_str("[shortcuts]\nExclude = \"Enter\"").unwrap();
+        assert_eq!(settings.shortcuts.label(Action::Exclude), "Enter");
+    }
+
+    #[test]
+    fn test_plausible_ranges() {
+        let settings: Settings = toml::from_str(
+            "[[plausible_ranges]]\nsensor_type = \"P\"\nmin = 800.0\nmax = 1100.0\n",
+        )
+        .unwrap();
+        assert_eq!(settings.plausible_range("p").map(|r| r.max), Some(1100.0));
+        assert!(settings.plausible_range("WS").is_none());
+        assert!(Settings::default().plausible_range("ws").is_some());
+    }
+
+    #[test]
+    fn test_reason_choices() {
+        let settings = Settings {
+            quick_reasons: vec!["Icing".into(), "Maintenance".into()],
+            ..Default::default()
+        };
+        let used = ["icing", "Vandalism", "Vandalism"].map(String::from);
+        assert_eq!(
+            settings.reason_choices(&used),
+            vec!["Icing", "Maintenance", "Vandalism"]
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use eframe::egui::{Event, InputState, Key, Modifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Keyboard shortcuts of the main actions, remappable in the settings. Only
+// the remapped ones are stored, as in a settings file:
+//
+// [shortcuts]
+// Exclude = "Enter"
+// Undo = "Ctrl+Shift+Z"
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    Load,
+    Exclude,
+    RepeatReason,
+    ClearSelection,
+    ToggleExcluded,
+    SwapAxes,
+    NextChannel,
+    PreviousChannel,
+    Undo,
+    Redo,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::Load,
+        Action::Exclude,
+        Action::RepeatReason,
+        Action::ClearSelection,
+        Action::ToggleExcluded,
+        Action::SwapAxes,
+        Action::NextChannel,
+        Action::PreviousChannel,
+        Action::Undo,
+        Action::Redo,
+        Action::Help,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Load => "Load a file",
+            Action::Exclude => "Exclude the selection",
+            Action::RepeatReason => "Exclude with the last reason",
+            Action::ClearSelection => "Clear the selection",
+            Action::ToggleExcluded => "Show or hide excluded data",
+            Action::SwapAxes => "Swap the x and y axes",
+            Action::NextChannel => "Next channel on the y axis",
+            Action::PreviousChannel => "Previous channel on the y axis",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Help => "Show the shortcuts",
+        }
+    }
+
+    fn default_shortcut(self) -> Shortcut {
+        let key = |key| Shortcut::new(Trigger::Key(key));
+        let command = |key| Shortcut {
+            command: true,
+            ..Shortcut::new(Trigger::Key(key))
+        };
+        match self {
+            Action::Load => command(Key::O),
+            Action::Exclude => key(Key::X),
+            Action::RepeatReason => key(Key::R),
+            Action::ClearSelection => key(Key::C),
+            Action::ToggleExcluded => key(Key::H),
+            Action::SwapAxes => key(Key::S),
+            Action::NextChannel => key(Key::ArrowDown),
+            Action::PreviousChannel => key(Key::ArrowUp),
+            Action::Undo => command(Key::Z),
+            Action::Redo => command(Key::Y),
+            Action::Help => Shortcut::new(Trigger::Text('?')),
+        }
+    }
+}
+
+// Keys a shortcut can use, found by name
+const KEYS: [Key; 65] = [
+    Key::ArrowDown,
+    Key::ArrowLeft,
+    Key::ArrowRight,
+    Key::ArrowUp,
+    Key::Escape,
+    Key::Tab,
+    Key::Backspace,
+    Key::Enter,
+    Key::Space,
+    Key::Insert,
+    Key::Delete,
+    Key::Home,
+    Key::End,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Minus,
+    Key::PlusEquals,
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+];
+
+// Keys the plot reads whatever the modifiers, which no shortcut can use
+const PLOT_KEYS: [(Key, &str); 4] = [
+    (Key::E, "Add a selection vertex"),
+    (Key::T, "Start or end a time range"),
+    (Key::P, "Pin the timestamp under the pointer"),
+    (Key::Escape, "Drop the selection being drawn"),
+];
+
+// Keys egui knows, or characters typed such as "?", which egui has no key
+// for and whose modifiers depend on the keyboard layout
+#[derive(Clone, Copy, PartialEq)]
+enum Trigger {
+    Key(Key),
+    Text(char),
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Shortcut {
+    // Ctrl, or Cmd on a Mac
+    command: bool,
+    alt: bool,
+    shift: bool,
+    trigger: Trigger,
+}
+
+impl Shortcut {
+    fn new(trigger: Trigger) -> Self {
+        Self {
+            command: false,
+            alt: false,
+            shift: false,
+            trigger,
+        }
+    }
+
+    // As written by `label`, e.g. "Ctrl+Shift+Z" or "?"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut shortcut = Self::new(Trigger::Text(' '));
+        let mut rest = text.trim();
+        loop {
+            let modifier = match rest.split_once('+') {
+                Some((modifier, key)) if !key.is_empty() => modifier,
+                _ => break,
+            };
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "cmd" => shortcut.command = true,
+                "alt" => shortcut.alt = true,
+                "shift" => shortcut.shift = true,
+                _ => return Err(format!("Unknown modifier '{}' in '{}'", modifier, text)),
+            }
+            rest = &rest[modifier.len() + 1..];
+        }
+
+        let mut chars = rest.chars();
+        shortcut.trigger = match KEYS
+            .iter()
+            .find(|key| key.name().eq_ignore_ascii_case(rest))
+        {
+            Some(&key) => Trigger::Key(key),
+            None => match (chars.next(), chars.next()) {
+                (Some(c), None) if !c.is_alphanumeric() && !c.is_whitespace() => Trigger::Text(c),
+                _ => return Err(format!("Unknown key '{}'", rest)),
+            },
+        };
+        if matches!(shortcut.trigger, Trigger::Text(_)) && shortcut != Self::new(shortcut.trigger) {
+            return Err(format!("'{}' can't be combined with modifiers", rest));
+        }
+        Ok(shortcut)
+    }
+
+    pub fn label(&self) -> String {
+        let mut label = String::new();
+        for (on, name) in [
+            (self.command, "Ctrl+"),
+            (self.alt, "Alt+"),
+            (self.shift, "Shift+"),
+        ] {
+            if on {
+                label += name;
+            }
+        }
+        match self.trigger {
+            Trigger::Key(key) => label += key.name(),
+            Trigger::Text(c) => label.push(c),
+        }
+        label
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            alt: self.alt,
+            shift: self.shift,
+            command: self.command,
+            ..Modifiers::NONE
+        }
+    }
+
+    // Whether the shortcut was pressed, taking the press so that nothing
+    // else sees it
+    fn consume(&self, input: &mut InputState) -> bool {
+        match self.trigger {
+            Trigger::Key(key) => input.consume_key(self.modifiers(), key),
+            Trigger::Text(c) => {
+                let typed = c.to_string();
+                let before = input.events.len();
+                input
+                    .events
+                    .retain(|event| !matches!(event, Event::Text(text) if *text == typed));
+                input.events.len() < before
+            }
+        }
+    }
+
+    // The first key pressed among the events, for remapping a shortcut.
+    // Modifiers pressed alone don't count.
+    pub fn captured(events: &[Event]) -> Option<Self> {
+        let key = events.iter().find_map(|event| match event {
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } if KEYS.contains(key) => Some(Self {
+                command: modifiers.command || modifiers.ctrl,
+                alt: modifiers.alt,
+                shift: modifiers.shift,
+                trigger: Trigger::Key(*key),
+            }),
+            _ => None,
+        });
+        key.or_else(|| {
+            events.iter().find_map(|event| match event {
+                Event::Text(text) => Self::parse(text)
+                    .ok()
+                    .filter(|shortcut| matches!(shortcut.trigger, Trigger::Text(_))),
+                _ => None,
+            })
+        })
+    }
+}
+
+impl TryFrom<String> for Shortcut {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        Self::parse(&text)
+    }
+}
+
+impl From<Shortcut> for String {
+    fn from(shortcut: Shortcut) -> Self {
+        shortcut.label()
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Shortcuts(BTreeMap<Action, Shortcut>);
+
+impl Shortcuts {
+    pub fn get(&self, action: Action) -> Shortcut {
+        self.0
+            .get(&action)
+            .copied()
+            .unwrap_or_else(|| action.default_shortcut())
+    }
+
+    pub fn label(&self, action: Action) -> String {
+        self.get(action).label()
+    }
+
+    pub fn set(&mut self, action: Action, shortcut: Shortcut) {
+        if shortcut == action.default_shortcut() {
+            self.0.remove(&action);
+        } else {
+            self.0.insert(action, shortcut);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.0.clear();
+    }
+
+    // What else the shortcut is already used for, another action or a key
+    // of the plot
+    pub fn conflict(&self, action: Action, shortcut: Shortcut) -> Option<&'static str> {
+        if let Trigger::Key(key) = shortcut.trigger {
+            if let Some((_, label)) = PLOT_KEYS.iter().find(|(plot_key, _)| *plot_key == key) {
+                return Some(label);
+            }
+        }
+        Action::ALL
+            .into_iter()
+            .find(|&other| other != action && self.get(other) == shortcut)
+            .map(Action::label)
+    }
+
+    // Actions whose shortcut was pressed, in the order of `Action::ALL`
+    pub fn pressed(&self, input: &mut InputState) -> Vec<Action> {
+        Action::ALL
+            .into_iter()
+            .filter(|&action| self.get(action).consume(input))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for text in ["Ctrl+Z", "Ctrl+Alt+Shift+F5", "?", "+", "Down", "Plus"] {
+            assert_eq!(Shortcut::parse(text).unwrap().label(), text);
+        }
+        assert_eq!(Shortcut::parse("cmd+z").unwrap().label(), "Ctrl+Z");
+        assert!(Shortcut::parse("Hyper+Z").is_err());
+        assert!(Shortcut::parse("Ctrl+?").is_err());
+        assert!(Shortcut::parse("ZZ").is_err());
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        let mut shortcuts = Shortcuts::default();
+        let ctrl_z = Shortcut::parse("Ctrl+Z").unwrap();
+        assert_eq!(shortcuts.conflict(Action::Redo, ctrl_z), Some("Undo"));
+        assert!(shortcuts.conflict(Action::Undo, ctrl_z).is_none());
+        let shift_p = Shortcut::parse("Shift+P").unwrap();
+        assert_eq!(
+            shortcuts.conflict(Action::Exclude, shift_p),
+            Some("Pin the timestamp under the pointer")
+        );
+
+        // Only the remapped shortcuts are kept
+        shortcuts.set(Action::Exclude, Shortcut::parse("Enter").unwrap());
+        shortcuts.set(Action::Undo, ctrl_z);
+        let json = serde_json::to_string(&shortcuts).unwrap();
+        assert_eq!(json, "{\"Exclude\":\"Enter\"}");
+        let shortcuts: Shortcuts = serde_json::from_str(&json).unwrap();
+        assert_eq!(shortcuts.label(Action::Exclude), "Enter");
+        assert_eq!(shortcuts.label(Action::Help), "?");
+        assert!(serde_json::from_str::<Shortcuts>("{\"Undo\":\"Ctrl+\"}").is_err());
+    }
+
+    #[test]
+    fn test_captured() {
+        let key = |key, modifiers| Event::Key {
+            key,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        };
+        let events = [Event::Text("x".into()), key(Key::X, Modifiers::SHIFT)];
+        assert_eq!(Shortcut::captured(&events).unwrap().label(), "Shift+X");
+        let events = [Event::Text("?".into())];
+        assert_eq!(Shortcut::captured(&events).unwrap().label(), "?");
+        assert!(Shortcut::captured(&[Event::Text("x".into())]).is_none());
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+
+// Mean and standard deviation of the values in a window of `window` rows
+// centred on each row. Missing values (NaN) are skipped; rows whose window
+// has fewer than two values get NaN.
+pub fn rolling_mean_std(values: &[f64], window: usize) -> Vec<(f64, f64)> {
+    let half = window.max(1) / 2;
+    // Running sum, sum of squares and count of the values in the window
+    let mut acc = (0.0, 0.0, 0.0);
+    let mut update = |v: f64, sign: f64| {
+        if v.is_finite() {
+            acc.0 += sign * v;
+            acc.1 += sign * v * v;
+            acc.2 += sign;
+        }
+        acc
+    };
+
+    for &v in values.iter().take(half) {
+        update(v, 1.0);
+    }
+
+    (0..values.len())
+        .map(|i| {
+            let mut current = update(values.get(i + half).copied().unwrap_or(f64::NAN), 1.0);
+            if i > half {
+                current = update(values[i - half - 1], -1.0);
+            }
+
+            let (sum, sum_sq, n) = current;
+            if n < 2.0 {
+                return (f64::NAN, f64::NAN);
+            }
+            let mean = sum / n;
+            let variance = ((sum_sq - n * mean * mean) / (n - 1.0)).max(0.0);
+            (mean, variance.sqrt())
+        })
+        .collect()
+}
+
+// Whether each value lies further than k standard deviations from its mean
+pub fn out_of_band(values: &[f64], stats: &[(f64, f64)], k: f64) -> Vec<bool> {
+    values
+        .iter()
+        .zip(stats)
+        .map(|(v, (mean, std))| v.is_finite() && mean.is_finite() && (v - mean).abs() > k * std)
+        .collect()
+}
+
+// Count, range, mean and sample standard deviation of the values, skipping
+// the missing ones. The statistics are NaN when there's nothing to compute
+// them from.
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std: f64,
+}
+
+pub fn summary(values: &[f64]) -> Summary {
+    let finite: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+    let count = finite.len();
+    let n = count as f64;
+    let mean = finite.iter().sum::<f64>() / n;
+    let variance = finite.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    Summary {
+        count,
+        min: finite.iter().copied().reduce(f64::min).unwrap_or(f64::NAN),
+        max: finite.iter().copied().reduce(f64::max).unwrap_or(f64::NAN),
+        mean,
+        std: if count > 1 { variance.sqrt() } else { f64::NAN },
+    }
+}
+
+// Mean of the finite values of each of the given number of groups, the rows
+// in no group left out
+pub fn group_means(values: &[f64], groups: &[Option<usize>], count: usize) -> Vec<Option<f64>> {
+    let mut sums = vec![(0.0, 0); count];
+    for (value, group) in values.iter().zip(groups) {
+        if let (true, Some(group)) = (value.is_finite(), group) {
+            sums[*group].0 += value;
+            sums[*group].1 += 1;
+        }
+    }
+    sums.into_iter()
+        .map(|(sum, n)| (n > 0).then(|| sum / n as f64))
+        .collect()
+}
+
+// Exponent α of the power law speed = k·height^α fitted to (speed, height)
+// points, by least squares on their logarithms
+pub fn shear_exponent(points: &[[f64; 2]]) -> Option<f64> {
+    let logs: Vec<(f64, f64)> = points
+        .iter()
+        .filter(|[speed, height]| *speed > 0.0 && *height > 0.0)
+        .map(|[speed, height]| (height.ln(), speed.ln()))
+        .collect();
+    let n = logs.len() as f64;
+    let mean_x = logs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = logs.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = logs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = logs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    (logs.len() > 1 && sxx > 0.0).then(|| sxy / sxx)
+}
+
+// Counts of the values in bins of the given width, keyed by bin number n for
+// the bin [n·width, (n+1)·width). Missing values are skipped.
+pub fn histogram(values: &[f64], width: f64) -> BTreeMap<i64, usize> {
+    let mut counts = BTreeMap::new();
+    if width <= 0.0 {
+        return counts;
+    }
+    for v in values.iter().filter(|v| v.is_finite()) {
+        *counts.entry((v / width).floor() as i64).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_mean_std() {
+        let stats = rolling_mean_std(&[1.0, 2.0, 3.0, f64::NAN, 5.0], 3);
+        assert_eq!(stats[0], (1.5, 0.5f64.sqrt()));
+        assert_eq!(stats[1], (2.0, 1.0));
+        assert_eq!(stats[2].0, 2.5);
+        assert!(stats[4].0.is_nan());
+    }
+
+    #[test]
+    fn test_out_of_band() {
+        let values = [1.0, 1.0, 10.0, f64::NAN];
+        let stats = [(1.0, 1.0); 4];
+        assert_eq!(
+            out_of_band(&values, &stats, 3.0),
+            vec![false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let stats = summary(&[2.0, f64::NAN, 4.0, 6.0]);
+        assert_eq!(stats.count, 3);
+        assert_eq!(
+            (stats.min, stats.max, stats.mean, stats.std),
+            (2.0, 6.0, 4.0, 2.0)
+        );
+        let empty = summary(&[f64::NAN]);
+        assert_eq!(empty.count, 0);
+        assert!(empty.mean.is_nan() && empty.std.is_nan() && empty.min.is_nan());
+    }
+
+    #[test]
+    fn test_group_means() {
+        let values = [1.0, 3.0, f64::NAN, 10.0, 7.0];
+        let groups = [Some(0), Some(0), Some(1), Some(2), None];
+        assert_eq!(
+            group_means(&values, &groups, 3),
+            vec![Some(2.0), None, Some(10.0)]
+        );
+    }
+
+    #[test]
+    fn test_shear_exponent() {
+        // speed = 5·(height/10)^0.2
+        let points: Vec<[f64; 2]> = [10.0, 40.0, 80.0]
+            .iter()
+            .map(|&h: &f64| [5.0 * (h / 10.0).powf(0.2), h])
+            .collect();
+        assert!((shear_exponent(&points).unwrap() - 0.2).abs() < 1e-9);
+        assert_eq!(shear_exponent(&points[..1]), None);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let counts = histogram(&[0.2, 0.9, 1.0, -0.5, f64::NAN, 3.5], 1.0);
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            vec![(-1, 1), (0, 2), (1, 1), (3, 1)]
+        );
+        assert!(histogram(&[1.0], 0.0).is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::config::read_config;
+use eframe::egui::Color32;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub type Rgb = [u8; 3];
+
+// Look of the window, dark or light, or as the system is set
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum UiTheme {
+    #[default]
+    Dark,
+    Light,
+    System,
+}
+
+impl UiTheme {
+    pub const ALL: [UiTheme; 3] = [UiTheme::Dark, UiTheme::Light, UiTheme::System];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            UiTheme::Dark => "Dark",
+            UiTheme::Light => "Light",
+            UiTheme::System => "System",
+        }
+    }
+
+    // Dark unless light is chosen, or the system is known to be light
+    pub fn is_dark(&self, system_dark: Option<bool>) -> bool {
+        match self {
+            UiTheme::Dark => true,
+            UiTheme::Light => false,
+            UiTheme::System => system_dark.unwrap_or(true),
+        }
+    }
+}
+
+// Plot appearance, loadable from a JSON or TOML file. Missing keys fall back
+// to the built-in defaults, so a theme file only needs the values it changes.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlotTheme {
+    pub name: String,
+    pub background: Rgb,
+    pub valid: Rgb,
+    pub excluded: Rgb,
+    pub selection_open: Rgb,
+    pub selection_closed: Rgb,
+    pub reason_colors: Vec<Rgb>,
+    pub series_colors: Vec<Rgb>,
+    pub period_colors: [Rgb; 2],
+    // Color stops, low to high, of points colored by a channel's value
+    pub gradient: Vec<Rgb>,
+    pub point_radius: f32,
+    pub vertex_radius: f32,
+    pub line_width: f32,
+}
+
+impl Default for PlotTheme {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_owned(),
+            background: [10, 10, 10],
+            valid: [230, 230, 230],
+            excluded: [230, 51, 51],
+            selection_open: [255, 0, 0],
+            selection_closed: [0, 255, 0],
+            reason_colors: vec![
+                [230, 51, 51],
+                [255, 165, 0],
+                [80, 160, 255],
+                [200, 90, 220],
+                [240, 220, 60],
+                [60, 200, 170],
+            ],
+            series_colors: vec![
+                [100, 200, 255],
+                [255, 200, 90],
+                [150, 230, 120],
+                [255, 130, 200],
+                [190, 160, 255],
+                [120, 230, 230],
+            ],
+            period_colors: [[80, 160, 255], [255, 165, 0]],
+            gradient: vec![
+                [68, 1, 84],
+                [59, 82, 139],
+                [33, 145, 140],
+                [94, 201, 98],
+                [253, 231, 37],
+            ],
+            point_radius: 2.0,
+            vertex_radius: 5.0,
+            line_width: 2.0,
+        }
+    }
+}
+
+pub fn color(rgb: Rgb) -> Color32 {
+    Color32::from_rgb(rgb[0], rgb[1], rgb[2])
+}
+
+impl PlotTheme {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        read_config(path, "theme")
+    }
+
+    // Dark points on a light background, for the light window theme
+    pub fn light() -> Self {
+        Self {
+            name: "Light".to_owned(),
+            background: [250, 250, 250],
+            valid: [40, 40, 40],
+            excluded: [200, 30, 30],
+            selection_open: [220, 0, 0],
+            selection_closed: [0, 150, 0],
+            series_colors: vec![
+                [0, 100, 200],
+                [210, 120, 0],
+                [40, 140, 40],
+                [200, 40, 140],
+                [110, 70, 200],
+                [0, 140, 140],
+            ],
+            ..Self::default()
+        }
+    }
+
+    // Built-in colors matching the window theme
+    pub fn preset(dark: bool) -> Self {
+        match dark {
+            true => Self::default(),
+            false => Self::light(),
+        }
+    }
+
+    // Same colors with points and lines scaled up, e.g. for presenting
+    pub fn scaled(&self, factor: f32) -> Self {
+        Self {
+            point_radius: self.point_radius * factor,
+            vertex_radius: self.vertex_radius * factor,
+            line_width: self.line_width * factor,
+            ..self.clone()
+        }
+    }
+
+    // Color of the n-th additional channel plotted on the y axis
+    pub fn series_color(&self, n: usize) -> Color32 {
+        match self.series_colors.len() {
+            0 => color(self.valid),
+            len => color(self.series_colors[n % len]),
+        }
+    }
+
+    // Color at t between 0 and 1 along the gradient, blending the nearest stops
+    pub fn gradient_color(&self, t: f64) -> Rgb {
+        let stops = &self.gradient;
+        if stops.len() < 2 {
+            return stops.first().copied().unwrap_or(self.valid);
+        }
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+        let n = (position as usize).min(stops.len() - 2);
+        let fraction = position - n as f64;
+        let mut rgb = [0; 3];
+        for (i, channel) in rgb.iter_mut().enumerate() {
+            let (a, b) = (stops[n][i] as f64, stops[n + 1][i] as f64);
+            *channel = (a + (b - a) * fraction).round() as u8;
+        }
+        rgb
+    }
+
+    // Color for a new exclusion reason: the first palette entry not taken yet,
+    // cycling through the palette once every entry is in use
+    pub fn next_reason_color(&self, assigned: &BTreeMap<String, Rgb>) -> Rgb {
+        self.reason_colors
+            .iter()
+            .find(|rgb| !assigned.values().any(|used| used == *rgb))
+            .or_else(|| {
+                self.reason_colors
+                    .get(assigned.len() % self.reason_colors.len().max(1))
+            })
+            .copied()
+            .unwrap_or(self.excluded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_json_theme() {
+        let theme: PlotTheme =
+            serde_json::from_str(r#"{"name": "Report", "valid": [0, 0, 0]}"#).unwrap();
+        assert_eq!(theme.name, "Report");
+        assert_eq!(theme.valid, [0, 0, 0]);
+        assert_eq!(theme.point_radius, PlotTheme::default().point_radius);
+    }
+
+    #[test]
+    fn test_ui_theme() {
+        assert!(UiTheme::System.is_dark(None));
+        assert!(!UiTheme::System.is_dark(Some(false)));
+        assert!(!UiTheme::Light.is_dark(Some(true)));
+        assert!(PlotTheme::preset(false) == PlotTheme::light());
+    }
+
+    #[test]
+    fn test_toml_theme() {
+        let theme: PlotTheme =
+            toml::from_str("background = [255, 255, 255]\nreason_colors = []\n").unwrap();
+        assert_eq!(theme.background, [255, 255, 255]);
+        assert_eq!(theme.next_reason_color(&BTreeMap::new()), theme.excluded);
+    }
+
+    #[test]
+    fn test_scaled() {
+        let theme = PlotTheme::default().scaled(2.0);
+        assert_eq!(theme.point_radius, 4.0);
+        assert_eq!(theme.line_width, 4.0);
+        assert_eq!(theme.valid, PlotTheme::default().valid);
+    }
+
+    #[test]
+    fn test_next_reason_color() {
+        let theme = PlotTheme {
+            reason_colors: vec![[1, 1, 1], [2, 2, 2]],
+            ..Default::default()
+        };
+        let mut assigned = BTreeMap::new();
+        assigned.insert("icing".to_owned(), [2, 2, 2]);
+        assert_eq!(theme.next_reason_color(&assigned), [1, 1, 1]);
+
+        assigned.insert("shadow".to_owned(), [1, 1, 1]);
+        assert_eq!(theme.next_reason_color(&assigned), [1, 1, 1]);
+    }
+
+    #[test]
+    fn test_gradient_color() {
+        let theme = PlotTheme {
+            gradient: vec![[0, 0, 0], [100, 200, 0], [200, 200, 200]],
+            ..Default::default()
+        };
+        assert_eq!(theme.gradient_color(0.0), [0, 0, 0]);
+        assert_eq!(theme.gradient_color(0.25), [50, 100, 0]);
+        assert_eq!(theme.gradient_color(1.0), [200, 200, 200]);
+        assert_eq!(theme.gradient_color(2.0), [200, 200, 200]);
+
+        let plain = PlotTheme {
+            gradient: Vec::new(),
+            ..Default::default()
+        };
+        assert_eq!(plain.gradient_color(0.5), plain.valid);
+    }
+}
@@ -0,0 +1,315 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M";
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Timestamp formats recognised in data files, tried in this order
+const TIMESTAMP_FORMATS: [&str; 11] = [
+    TIMESTAMP_FORMAT,
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M:%SZ",
+    "%Y/%m/%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%d/%m/%Y %H:%M:%S",
+    "%d-%m-%Y %H:%M",
+    "%d.%m.%Y %H:%M",
+];
+
+// Timestamps checked to tell the format of an index
+const DETECTION_SAMPLE: usize = 50;
+
+// Seconds since the Unix epoch, or NaN when the timestamp can't be parsed
+pub fn parse_time(timestamp: &str, format: &str) -> f64 {
+    NaiveDateTime::parse_from_str(timestamp.trim(), format)
+        .map_or(f64::NAN, |t| t.and_utc().timestamp() as f64)
+}
+
+// The first known format reading every one of the first timestamps, if any
+pub fn detect_format(index: &[String]) -> Option<&'static str> {
+    let sample: Vec<&str> = index
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .take(DETECTION_SAMPLE)
+        .collect();
+    TIMESTAMP_FORMATS.into_iter().find(|format| {
+        !sample.is_empty()
+            && sample
+                .iter()
+                .all(|t| NaiveDateTime::parse_from_str(t, format).is_ok())
+    })
+}
+
+// Time range covering the given days, both included, as [start, end) seconds
+pub fn parse_date_range(first_day: &str, last_day: &str) -> Option<(f64, f64)> {
+    let start = NaiveDate::parse_from_str(first_day.trim(), DATE_FORMAT).ok()?;
+    let end = NaiveDate::parse_from_str(last_day.trim(), DATE_FORMAT).ok()?;
+    let seconds = |day: NaiveDate| day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp() as f64;
+
+    (start <= end).then(|| (seconds(start), seconds(end) + 86400.0))
+}
+
+pub fn format_time(seconds: f64) -> String {
+    DateTime::from_timestamp(seconds.round() as i64, 0)
+        .map_or_else(String::new, |t| t.format(TIMESTAMP_FORMAT).to_string())
+}
+
+// Typical spacing between consecutive timestamps (the median step)
+pub fn cadence(times: &[f64]) -> Option<f64> {
+    let mut steps: Vec<f64> = times
+        .windows(2)
+        .map(|w| w[1] - w[0])
+        .filter(|step| step.is_finite() && *step > 0.0)
+        .collect();
+
+    if steps.is_empty() {
+        return None;
+    }
+
+    steps.sort_by(f64::total_cmp);
+    Some(steps[steps.len() / 2])
+}
+
+// A break in the spacing of the index between two consecutive timestamps
+#[derive(Debug, PartialEq)]
+pub struct Gap {
+    pub start: f64,
+    pub end: f64,
+    // Records that would fit in between at the cadence
+    pub missing: usize,
+    // The step isn't a whole number of cadences, or goes backwards
+    pub irregular: bool,
+}
+
+// Breaks in the index at the given cadence, in seconds. Timestamps that can't
+// be read are passed over.
+pub fn gaps(times: &[f64], step: f64) -> Vec<Gap> {
+    let times: Vec<f64> = times.iter().copied().filter(|t| t.is_finite()).collect();
+    times
+        .windows(2)
+        .filter_map(|w| {
+            let cycles = (w[1] - w[0]) / step;
+            let irregular = cycles <= 0.0 || (cycles - cycles.round()).abs() > 0.01;
+            let missing = match irregular {
+                true => cycles.floor(),
+                false => cycles.round() - 1.0,
+            };
+            let missing = missing.max(0.0) as usize;
+            (missing > 0 || irregular).then_some(Gap {
+                start: w[0],
+                end: w[1],
+                missing,
+                irregular,
+            })
+        })
+        .collect()
+}
+
+pub fn write_gaps(writer: &mut impl Write, gaps: &[Gap]) -> io::Result<()> {
+    writeln!(writer, "From\tTo\tMissing records\tType")?;
+    for gap in gaps {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            format_time(gap.start),
+            format_time(gap.end),
+            gap.missing,
+            if gap.irregular { "Irregular" } else { "Gap" }
+        )?;
+    }
+    Ok(())
+}
+
+// Row whose timestamp is closest to t, if it is no further than max_step
+pub fn nearest_time(times: &[f64], t: f64, max_step: f64) -> Option<usize> {
+    times
+        .iter()
+        .enumerate()
+        .filter(|(_, time)| time.is_finite() && (*time - t).abs() <= max_step)
+        .min_by(|a, b| (a.1 - t).abs().total_cmp(&(b.1 - t).abs()))
+        .map(|(row, _)| row)
+}
+
+// Splits a sequence of points into the runs that can be joined by a line:
+// a run ends at every missing point and wherever x jumps by more than max_step
+pub fn line_segments(points: &[Option<[f64; 2]>], max_step: f64) -> Vec<Vec<[f64; 2]>> {
+    let mut segments: Vec<Vec<[f64; 2]>> = Vec::new();
+    let mut current: Vec<[f64; 2]> = Vec::new();
+
+    for point in points {
+        match point {
+            Some(p) => {
+                if let Some(last) = current.last() {
+                    if p[0] - last[0] > max_step {
+                        segments.push(std::mem::take(&mut current));
+                    }
+                }
+                current.push(*p);
+            }
+            None if !current.is_empty() => segments.push(std::mem::take(&mut current)),
+            None => (),
+        }
+    }
+
+    if !current.is_empty() {
+        segments.push(current);
+    }
+
+    segments
+}
+
+// Valid and total records of each day, or of each month keyed by its first
+// day. Rows whose time can't be read are left out.
+pub fn coverage(
+    times: &[f64],
+    valid: &[bool],
+    monthly: bool,
+) -> BTreeMap<NaiveDate, (usize, usize)> {
+    let mut counts = BTreeMap::new();
+    for (time, valid) in times.iter().zip(valid) {
+        let Some(date) = DateTime::from_timestamp(time.floor() as i64, 0)
+            .filter(|_| time.is_finite())
+            .map(|t| t.date_naive())
+        else {
+            continue;
+        };
+        let key = match monthly {
+            true => date.with_day(1).unwrap_or(date),
+            false => date,
+        };
+        let entry = counts.entry(key).or_insert((0, 0));
+        entry.0 += *valid as usize;
+        entry.1 += 1;
+    }
+    counts
+}
+
+// Timestamp of a test fixture, written in the default format
+#[cfg(test)]
+pub fn time(timestamp: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        let t = parse_time("2024-01-01 00:10", TIMESTAMP_FORMAT);
+        assert_eq!(t - parse_time("2024-01-01 00:00", TIMESTAMP_FORMAT), 600.0);
+        assert_eq!(format_time(t), "2024-01-01 00:10");
+        assert!(parse_time("01/01/2024", TIMESTAMP_FORMAT).is_nan());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let index = |times: &[&str]| times.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            detect_format(&index(&["2024-01-01 00:10", ""])),
+            Some(TIMESTAMP_FORMAT)
+        );
+        assert_eq!(
+            detect_format(&index(&["2024-01-01T00:10:00Z"])),
+            Some("%Y-%m-%dT%H:%M:%SZ")
+        );
+        // Day first, as written by European locales
+        let format = detect_format(&index(&["01/02/2024 00:00", "13/02/2024 00:00"]));
+        assert_eq!(format, Some("%d/%m/%Y %H:%M"));
+        assert_eq!(
+            parse_time("13/02/2024 00:10", format.unwrap()),
+            parse_time("2024-02-13 00:10", TIMESTAMP_FORMAT)
+        );
+        assert_eq!(detect_format(&index(&["yesterday"])), None);
+    }
+
+    #[test]
+    fn test_parse_date_range() {
+        let (start, end) = parse_date_range("2024-01-01", " 2024-01-01").unwrap();
+        assert_eq!(start, parse_time("2024-01-01 00:00", TIMESTAMP_FORMAT));
+        assert_eq!(end, parse_time("2024-01-02 00:00", TIMESTAMP_FORMAT));
+        assert_eq!(parse_date_range("2024-01-02", "2024-01-01"), None);
+        assert_eq!(parse_date_range("2024-01-01", ""), None);
+    }
+
+    #[test]
+    fn test_cadence() {
+        assert_eq!(cadence(&[0.0, 600.0, 1200.0, 3600.0, 4200.0]), Some(600.0));
+        assert_eq!(cadence(&[0.0]), None);
+    }
+
+    #[test]
+    fn test_gaps() {
+        let times = [0.0, 600.0, 2400.0, f64::NAN, 3000.0, 3300.0, 3300.0];
+        let found = gaps(&times, 600.0);
+        assert_eq!(found.len(), 3);
+        assert_eq!(
+            (found[0].start, found[0].missing, found[0].irregular),
+            (600.0, 2, false)
+        );
+        assert_eq!(
+            (found[1].start, found[1].missing, found[1].irregular),
+            (3000.0, 0, true)
+        );
+        // Repeated timestamps are irregular too
+        assert!(found[2].irregular);
+
+        let mut text = Vec::new();
+        write_gaps(&mut text, &found[..1]).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "From\tTo\tMissing records\tType\n1970-01-01 00:10\t1970-01-01 00:40\t2\tGap\n"
+        );
+    }
+
+    #[test]
+    fn test_nearest_time() {
+        let times = [0.0, 600.0, f64::NAN, 1800.0];
+        assert_eq!(nearest_time(&times, 700.0, 900.0), Some(1));
+        assert_eq!(nearest_time(&times, 1300.0, 900.0), Some(3));
+        assert_eq!(nearest_time(&times, 5000.0, 900.0), None);
+    }
+
+    #[test]
+    fn test_line_segments() {
+        let points = [
+            Some([0.0, 1.0]),
+            Some([1.0, 1.0]),
+            None,
+            Some([3.0, 1.0]),
+            Some([4.0, 1.0]),
+            Some([9.0, 1.0]),
+        ];
+        let segments = line_segments(&points, 1.5);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], vec![[0.0, 1.0], [1.0, 1.0]]);
+        assert_eq!(segments[1], vec![[3.0, 1.0], [4.0, 1.0]]);
+        assert_eq!(segments[2], vec![[9.0, 1.0]]);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let times: Vec<f64> = [
+            "2024-01-31 23:50",
+            "2024-02-01 00:00",
+            "2024-02-01 00:10",
+            "",
+        ]
+        .iter()
+        .map(|t| parse_time(t, TIMESTAMP_FORMAT))
+        .collect();
+        let valid = [true, false, true, true];
+        let day = |d| NaiveDate::parse_from_str(d, DATE_FORMAT).unwrap();
+        let monthly: Vec<_> = coverage(&times, &valid, true).into_iter().collect();
+        assert_eq!(
+            monthly,
+            vec![(day("2024-01-01"), (1, 1)), (day("2024-02-01"), (1, 2))]
+        );
+        assert_eq!(coverage(&times, &valid, false).len(), 2);
+    }
+}
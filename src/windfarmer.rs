@@ -0,0 +1,168 @@
+use crate::exclusions::{Category, ExclusionRow};
+use chrono::NaiveDateTime;
+
+// Cleaning rules as read and written by WindFarmer:
+//
+// <CleaningRules>
+//   <Exclusion Mast="M1" Sensor="WS80" Reason="icing"
+//              Start="2024-01-01T00:00:00" End="2024-01-01T00:20:00" />
+// </CleaningRules>
+//
+// The analyst and the category aren't part of the format, so they're never
+// written. Analyst and Category attributes written by older versions are
+// still read.
+const ROOT: &str = "CleaningRules";
+const EXCLUSION: &str = "Exclusion";
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+pub fn read_exclusions(content: &str) -> Result<Vec<ExclusionRow>, String> {
+    let doc = roxmltree::Document::parse(content).map_err(|e| format!("Invalid XML: {}", e))?;
+    let root = doc.root_element();
+    if root.tag_name().name() != ROOT {
+        return Err(format!(
+            "Expected <{}> root element, found <{}>",
+            ROOT,
+            root.tag_name().name()
+        ));
+    }
+
+    root.children()
+        .filter(|node| node.has_tag_name(EXCLUSION))
+        .map(|node| {
+            let line = doc.text_pos_at(node.range().start).row;
+            let attribute = |name: &str| {
+                node.attribute(name)
+                    .ok_or(format!("Line {}: Missing attribute '{}'", line, name))
+            };
+            let time = |name: &str| {
+                let value = attribute(name)?;
+                NaiveDateTime::parse_from_str(value, TIME_FORMAT)
+                    .map_err(|_| format!("Line {}: Invalid {} time '{}'", line, name, value))
+            };
+
+            Ok(ExclusionRow {
+                mast: attribute("Mast")?.to_owned(),
+                sensor: attribute("Sensor")?.to_owned(),
+                reason: attribute("Reason")?.to_owned(),
+                start: time("Start")?,
+                end: time("End")?,
+                category: Category::parse(node.attribute("Category").unwrap_or_default())
+                    .map_err(|e| format!("Line {}: {}", line, e))?,
+                analyst: node.attribute("Analyst").unwrap_or_default().to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn element(row: &ExclusionRow) -> String {
+    format!(
+        "  <{} Mast=\"{}\" Sensor=\"{}\" Reason=\"{}\" Start=\"{}\" End=\"{}\" />\n",
+        EXCLUSION,
+        escape(&row.mast),
+        escape(&row.sensor),
+        escape(&row.reason),
+        row.start.format(TIME_FORMAT),
+        row.end.format(TIME_FORMAT),
+    )
+}
+
+pub fn write_exclusions(rows: &[ExclusionRow]) -> String {
+    let mut xml = format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<{}>\n", ROOT);
+    for row in rows {
+        xml += &element(row);
+    }
+    xml += &format!("</{}>\n", ROOT);
+    xml
+}
+
+// The file with the rows added before its closing tag, the rest of it left
+// as it was
+pub fn append_exclusions(content: &str, rows: &[ExclusionRow]) -> Result<String, String> {
+    let closing = format!("</{}>", ROOT);
+    let end = content
+        .rfind(&closing)
+        .ok_or(format!("The file has no {} closing tag", closing))?;
+    let mut xml = content[..end].to_owned();
+    if !xml.ends_with('\n') {
+        xml.push('\n');
+    }
+    for row in rows {
+        xml += &element(row);
+    }
+    xml += &content[end..];
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::time;
+
+    #[test]
+    fn test_round_trip() {
+        let rows = vec![ExclusionRow {
+            mast: "M1".to_owned(),
+            sensor: "WS80".to_owned(),
+            reason: "icing & \"frost\"".to_owned(),
+            start: time("2024-01-01 00:00"),
+            end: time("2024-01-01 00:20"),
+            category: None,
+            analyst: String::new(),
+        }];
+
+        let xml = write_exclusions(&rows);
+        assert_eq!(read_exclusions(&xml).unwrap(), rows);
+        assert_eq!(write_exclusions(&read_exclusions(&xml).unwrap()), xml);
+
+        // The analyst and the category stay out of the WindFarmer file, but
+        // are read from older files
+        let stamped = vec![ExclusionRow {
+            analyst: "JG".to_owned(),
+            category: Some(Category::Icing),
+            ..rows[0].clone()
+        }];
+        assert_eq!(write_exclusions(&stamped), xml);
+        let older = xml.replace(" />", " Category=\"Icing\" Analyst=\"JG\" />");
+        assert_eq!(read_exclusions(&older).unwrap(), stamped);
+    }
+
+    #[test]
+    fn test_append() {
+        let row = |sensor: &str| ExclusionRow {
+            mast: "M1".to_owned(),
+            sensor: sensor.to_owned(),
+            reason: "icing".to_owned(),
+            start: time("2024-01-01 00:00"),
+            end: time("2024-01-01 00:20"),
+            category: None,
+            analyst: String::new(),
+        };
+        // Written by hand, with a comment and its own indentation
+        let content = "<CleaningRules>\n<!-- M1 -->\n\t<Exclusion Mast=\"M1\" Sensor=\"WS80\" Reason=\"icing\" Start=\"2024-01-01T00:00:00\" End=\"2024-01-01T00:20:00\"/>\n</CleaningRules>\n";
+        let appended = append_exclusions(content, &[row("WS60")]).unwrap();
+        assert!(appended.starts_with(&content[..content.len() - "</CleaningRules>\n".len()]));
+        assert_eq!(
+            read_exclusions(&appended).unwrap(),
+            [row("WS80"), row("WS60")]
+        );
+        assert!(append_exclusions("<CleaningRules />", &[]).is_err());
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(read_exclusions("<Other />").is_err());
+        let missing = "<CleaningRules>\n<Exclusion Mast=\"M1\" />\n</CleaningRules>";
+        assert_eq!(
+            read_exclusions(missing).unwrap_err(),
+            "Line 2: Missing attribute 'Sensor'"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::columns::Table;
+use crate::timeline::TIMESTAMP_FORMAT;
+use calamine::{open_workbook_auto, Data, Range, Reader};
+
+pub fn is_workbook(path: &str) -> bool {
+    let path = path.to_lowercase();
+    [".xlsx", ".xlsm", ".xls"]
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+pub fn sheet_names(path: &str) -> Result<Vec<String>, String> {
+    let workbook = open_workbook_auto(path).map_err(|e| format!("File read error: {}", e))?;
+    Ok(workbook.sheet_names())
+}
+
+// A worksheet laid out like a data file: a header row, then the timestamps
+// in the first column and one channel in each of the others
+pub fn read_sheet(path: &str, name: &str) -> Result<Table, String> {
+    let mut workbook = open_workbook_auto(path).map_err(|e| format!("File read error: {}", e))?;
+    let range = workbook
+        .worksheet_range(name)
+        .map_err(|e| format!("Sheet '{}': {}", name, e))?;
+    parse_range(&range)
+}
+
+fn parse_range(range: &Range<Data>) -> Result<Table, String> {
+    let mut rows = range.rows();
+    let headers: Vec<String> = rows
+        .next()
+        .ok_or("Empty sheet")?
+        .iter()
+        .map(cell_text)
+        .collect();
+
+    let mut index = Vec::new();
+    let mut columns = vec![Vec::new(); headers.len().saturating_sub(1)];
+    for row in rows.filter(|row| row.iter().any(|cell| *cell != Data::Empty)) {
+        index.push(row.first().map(cell_text).unwrap_or_default());
+        for (n, values) in columns.iter_mut().enumerate() {
+            values.push(row.get(n + 1).map_or(f64::NAN, cell_value));
+        }
+    }
+
+    Ok(Table {
+        headers,
+        index,
+        columns,
+    })
+}
+
+// Dates are written in the timestamp format of the text files
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(t) => t.as_datetime().map_or_else(
+            || t.as_f64().to_string(),
+            |t| t.format(TIMESTAMP_FORMAT).to_string(),
+        ),
+        _ => cell.to_string().trim().to_owned(),
+    }
+}
+
+// NaN for empty cells, errors and text that is not a number
+fn cell_value(cell: &Data) -> f64 {
+    match cell {
+        Data::Int(v) => *v as f64,
+        Data::Float(v) => *v,
+        Data::String(s) => s.trim().replace(',', ".").parse().unwrap_or(f64::NAN),
+        _ => f64::NAN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{ExcelDateTime, ExcelDateTimeType};
+
+    #[test]
+    fn test_parse_range() {
+        let mut range = Range::new((0, 0), (3, 2));
+        range.set_value((0, 0), Data::String("Time".into()));
+        range.set_value((0, 1), Data::String("M1~WS80".into()));
+        range.set_value((0, 2), Data::String("M1~WD80".into()));
+        // 2024-01-01 00:10 as an Excel serial date
+        let time = ExcelDateTime::new(45292.0 + 10.0 / 1440.0, ExcelDateTimeType::DateTime, false);
+        range.set_value((1, 0), Data::DateTime(time));
+        range.set_value((1, 1), Data::Float(5.5));
+        range.set_value((1, 2), Data::String("9,5".into()));
+        range.set_value((2, 0), Data::String("2024-01-01 00:20".into()));
+        range.set_value((2, 1), Data::Int(6));
+
+        let sheet = parse_range(&range).unwrap();
+        assert_eq!(sheet.headers, vec!["Time", "M1~WS80", "M1~WD80"]);
+        // The empty last row is dropped
+        assert_eq!(sheet.index, vec!["2024-01-01 00:10", "2024-01-01 00:20"]);
+        assert_eq!(sheet.columns[0], vec![5.5, 6.0]);
+        assert_eq!(sheet.columns[1][0], 9.5);
+        assert!(sheet.columns[1][1].is_nan());
+    }
+}